@@ -1,32 +1,34 @@
 #[macro_use] extern crate spectra;
 
-use spectra::app::demo::{Backbuffer, Builder, Demo, Key, Store, Time};
+use spectra::app::demo::{self, Demo, Key, Store, Target, Time};
 use spectra::app::runner::debug::Runner;
 use spectra::logger::StdoutLogger;
+use spectra::time::clock::WallClock;
 
 struct App;
 
-impl<Runner> Demo<Runner> for App {
+impl<R> Demo<R> for App where R: demo::Runner {
   type Context = StdoutLogger;
 
   type Error = ();
 
-  fn init(_runner: &mut Runner, _store: &mut Store<Self::Context, Key>, _context: &mut Self::Context) -> Result<Self, Self::Error> {
+  fn init(_runner: &mut R, _store: &mut Store<Self::Context, Key>, _context: &mut Self::Context) -> Result<Self, Self::Error> {
     Ok(App)
   }
 
-  fn resize(&mut self, _runner: &mut Runner, _context: &mut Self::Context, _width: u32, _height: u32) {
+  fn resize(&mut self, _runner: &mut R, _context: &mut Self::Context, _width: u32, _height: u32) {
     // do nothing
   }
 
-  fn render(&mut self, _runner: &mut Runner, context: &mut Self::Context, t: Time, _back_buffer: &Backbuffer, _builder: Builder) {
-    debug!(context, "time is {}", t);
+  fn render(&mut self, runner: &mut R, context: &mut Self::Context, t: Time, _interpolation: f64, _target: Target) {
+    let fps = runner.frame_stats().fps();
+    debug!(context, "time is {} ({:.1} FPS)", t, fps);
   }
 }
 
 fn main() {
   let mut context = StdoutLogger::default();
-  let result = Runner::run::<App>("simple example", 960, 540, &mut context);
+  let result = Runner::run::<App, _>("simple example", 960, 540, WallClock::new(), &mut context);
 
   if let Err(e) = result {
     error!(&mut context, "{}", e);
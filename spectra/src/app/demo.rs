@@ -6,25 +6,35 @@ use luminance::texture::{Dim2, Flat};
 pub use warmy::Store;
 use std::fmt::Debug;
 
+pub use crate::app::event::Event;
+pub use crate::app::runner::Runner;
 use crate::logger::Logger;
 pub use crate::resource::key::Key;
 pub use crate::time::Time;
+pub use crate::time::marker::{Cue, MarkerTrack};
 
 /// Class of demo applications.
 ///
 /// A demo is basically just a single function that takes the current time and display something.
-pub trait Demo<Runner>: Sized {
+///
+/// Demos are generic over the runner they run in, which is passed to all the methods. Writing the
+/// implementation for any `R: Runner` keeps the demo runner-agnostic.
+pub trait Demo<R>: Sized where R: Runner {
   /// Context carried around with the demo.
   type Context: Logger;
 
   /// Initialization error that might occur.
   type Error: Sized + Debug;
 
+  /// Data attached to the cues of the demo. Defaults to `()`, for demos whose cues only have a
+  /// name.
+  type Payload = ();
+
   /// Initialize the demo with a given store.
   ///
   /// The runner is passed so that specific initialization is possible.
   fn init(
-    runner: &mut Runner,
+    runner: &mut R,
     store: &mut Store<Self::Context, Key>,
     context: &mut Self::Context
   ) -> Result<Self, Self::Error>;
@@ -32,19 +42,98 @@ pub trait Demo<Runner>: Sized {
   /// Resize the demo when the framebuffer gets resized.
   ///
   /// The runner is passed so that specific resizing is possible.
-  fn resize(&mut self, runner: &mut Runner, context: &mut Self::Context, width: u32, height: u32);
+  fn resize(&mut self, runner: &mut R, context: &mut Self::Context, width: u32, height: u32);
+
+  /// Update the demo at a given time, before rendering.
+  ///
+  /// `dt` is the time elapsed since the previous update; it’s negative when the time goes backward,
+  /// whatever the update rate. With a fixed update rate – see [`Demo::update_rate`] – this method
+  /// might be called several times per frame, or not at all, and `dt` is the update period as long
+  /// as the time goes forward. The default implementation does nothing.
+  fn update(&mut self, _runner: &mut R, _context: &mut Self::Context, _t: Time, _dt: Time) {}
+
+  /// Fixed number of updates per second, if any.
+  ///
+  /// Runners query it once, right after initialization. With a fixed update rate, the
+  /// interpolation factor between the last update and the next one is passed to
+  /// [`Demo::render`]. The default implementation asks for one update per frame.
+  fn update_rate(&self) -> Option<u32> {
+    None
+  }
 
   /// Render the demo at a given time. 
   ///
-  /// The runner is passed so that specific rendering is possible.
+  /// The runner is passed so that specific rendering is possible. `interpolation` is the progress,
+  /// in [0; 1], of `t` between the last update and the next one, with a fixed update rate; it’s 0
+  /// otherwise. The GPU work goes through the [`Target`], which skips it on surfaces without
+  /// graphics context.
   fn render(
     &mut self,
-    runner: &mut Runner,
+    runner: &mut R,
     context: &mut Self::Context,
     t: Time,
-    back_buffer: &Backbuffer,
-    builder: Builder
+    interpolation: f64,
+    target: Target
   );
+
+  /// Handle an event coming from the surface.
+  ///
+  /// Events are passed to the demo before the runner handles them. Return `true` to consume the
+  /// event, preventing the runner’s hotkeys from reacting to it; close and resize events are
+  /// always handled by the runner. The default implementation consumes nothing.
+  fn event(&mut self, _runner: &mut R, _context: &mut Self::Context, _event: &Event) -> bool {
+    false
+  }
+
+  /// Named markers on the timeline of the demo.
+  ///
+  /// Runners query them once, right after initialization, to navigate the timeline, to start at a
+  /// given marker and to fire cues. The default implementation has no marker.
+  fn markers(&self) -> MarkerTrack<Self::Payload> {
+    MarkerTrack::new()
+  }
+
+  /// Called whenever a marker is reached, before rendering the frame.
+  ///
+  /// `t` is the time of the frame, not of the cue. The default implementation does nothing.
+  fn cue(&mut self, _runner: &mut R, _context: &mut Self::Context, _cue: &Cue<Self::Payload>, _t: Time) {}
+
+  /// Called once when the runner exits without error, after the last frame.
+  ///
+  /// The default implementation does nothing.
+  fn shutdown(&mut self, _runner: &mut R, _context: &mut Self::Context) {}
 }
 
 pub type Backbuffer = Framebuffer<Flat, Dim2, (), ()>;
+
+/// What a demo renders a frame to.
+///
+/// Surfaces without graphics context – such as [`Headless`](crate::app::runner::headless::Headless)
+/// ones – still have the demo render its frames, but to targets that skip the GPU work.
+pub struct Target<'a> {
+  graphics: Option<(&'a Backbuffer, Builder)>
+}
+
+impl<'a> Target<'a> {
+  pub(crate) fn new(back_buffer: &'a Backbuffer, builder: Builder) -> Self {
+    Target { graphics: Some((back_buffer, builder)) }
+  }
+
+  pub(crate) fn headless() -> Self {
+    Target { graphics: None }
+  }
+
+  /// Whether the target has a graphics context.
+  pub fn has_graphics(&self) -> bool {
+    self.graphics.is_some()
+  }
+
+  /// Render to the back buffer with a pipeline builder.
+  ///
+  /// `f` is not called if the target has no graphics context.
+  pub fn render<F>(self, f: F) where F: FnOnce(&Backbuffer, Builder) {
+    if let Some((back_buffer, builder)) = self.graphics {
+      f(back_buffer, builder);
+    }
+  }
+}
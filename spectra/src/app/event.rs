@@ -0,0 +1,84 @@
+//! Surface-independent events.
+//!
+//! Surfaces translate their native events into [`Event`]s, so that runners – and demos – don’t
+//! depend on a given windowing backend. Demos receive them through
+//! [`Demo::event`](crate::app::demo::Demo::event).
+
+use std::path::PathBuf;
+
+/// A key of the keyboard.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+  A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+  Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+  F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+  Space,
+  Escape,
+  Enter,
+  Tab,
+  Backspace,
+  Insert,
+  Delete,
+  Left,
+  Right,
+  Up,
+  Down,
+  PageUp,
+  PageDown,
+  Home,
+  End,
+  LeftShift,
+  RightShift,
+  LeftControl,
+  RightControl,
+  LeftAlt,
+  RightAlt,
+  /// Any other key.
+  Unknown
+}
+
+/// State change of a key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+  Press,
+  Repeat,
+  Release
+}
+
+impl Action {
+  /// Is the key down after this action?
+  pub fn is_down(self) -> bool {
+    self != Action::Release
+  }
+}
+
+/// A button of the mouse.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MouseButton {
+  Left,
+  Right,
+  Middle,
+  /// Any other button, numbered from 3.
+  Other(u8)
+}
+
+/// An event coming from a surface.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  /// The surface was asked to close.
+  Close,
+  /// The framebuffer got resized, in pixels.
+  Resize(u32, u32),
+  /// A key changed state.
+  Key(Key, Action),
+  /// The cursor moved, in pixels from the upper-left corner of the surface.
+  MouseMove(f64, f64),
+  /// A mouse button changed state.
+  MouseButton(MouseButton, Action),
+  /// The mouse wheel or the touchpad scrolled, horizontally and vertically.
+  Scroll(f64, f64),
+  /// The surface gained (`true`) or lost (`false`) the focus.
+  Focus(bool),
+  /// Files were dropped onto the surface.
+  FileDrop(Vec<PathBuf>)
+}
@@ -1,15 +1,48 @@
 //! Demo runner.
+//!
+//! A runner opens a window (or not), drives a [`Demo`](crate::app::demo::Demo) and owns the main
+//! loop. Demos are written against the [`Runner`] trait, so that the same demo code can run in any
+//! runner.
 
 use std::fmt;
+use std::str::FromStr;
 
+use crate::app::runner::capture::Capture;
+use crate::app::runner::stats::FrameSample;
+use crate::logger::{Buffer, Logger};
+use crate::time::{DurationSpec, DurationSpecError, Monotonic, Time};
+use crate::time::marker::MarkerTrack;
+
+pub mod capture;
+pub mod config;
 pub mod debug;
+pub mod export;
+pub mod headless;
+pub mod osc;
+pub mod pacing;
+pub mod params;
+pub mod release;
+pub mod remote;
+pub mod stats;
+pub mod surface;
+pub mod transport;
+pub mod update;
+
+pub use self::params::Params;
+pub use self::stats::FrameStats;
 
 /// Possible runner errors.
 #[derive(Debug)]
 pub enum Error {
   CannotCreateWindow(String),
   CannotCreateStore(String),
-  DemoInitializationFailure(String)
+  DemoInitializationFailure(String),
+  UnknownMarker(String),
+  SetupFailure(String),
+  InvalidConfig(String),
+  CannotExport(String),
+  CannotStartServer(String),
+  CannotListenOsc(String)
 }
 
 impl Error {
@@ -24,6 +57,30 @@ impl Error {
   pub(crate) fn demo_initialization_failure<R>(reason: R) -> Self where R: Into<String> {
     Error::DemoInitializationFailure(reason.into())
   }
+
+  pub(crate) fn unknown_marker<N>(name: N) -> Self where N: Into<String> {
+    Error::UnknownMarker(name.into())
+  }
+
+  pub(crate) fn setup_failure<R>(reason: R) -> Self where R: Into<String> {
+    Error::SetupFailure(reason.into())
+  }
+
+  pub(crate) fn invalid_config<R>(reason: R) -> Self where R: Into<String> {
+    Error::InvalidConfig(reason.into())
+  }
+
+  pub(crate) fn cannot_export<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotExport(reason.into())
+  }
+
+  pub(crate) fn cannot_start_server<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotStartServer(reason.into())
+  }
+
+  pub(crate) fn cannot_listen_osc<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotListenOsc(reason.into())
+  }
 }
 
 impl fmt::Display for Error {
@@ -32,6 +89,231 @@ impl fmt::Display for Error {
       Error::CannotCreateWindow(ref reason) => write!(f, "cannot create window: {}", reason),
       Error::CannotCreateStore(ref reason) => write!(f, "cannot create store: {}", reason),
       Error::DemoInitializationFailure(ref reason) => write!(f, "demo failed to initialize: {}", reason),
+      Error::UnknownMarker(ref name) => write!(f, "unknown marker: {}", name),
+      Error::SetupFailure(ref reason) => write!(f, "setup failed: {}", reason),
+      Error::InvalidConfig(ref reason) => write!(f, "invalid configuration: {}", reason),
+      Error::CannotExport(ref reason) => write!(f, "cannot export: {}", reason),
+      Error::CannotStartServer(ref reason) => write!(f, "cannot start the remote control server: {}", reason),
+      Error::CannotListenOsc(ref reason) => write!(f, "cannot listen to OSC messages: {}", reason),
+    }
+  }
+}
+
+/// Capabilities common to all runners.
+///
+/// A runner is passed to every method of a [`Demo`](crate::app::demo::Demo), which can use it to
+/// query information about the frame being rendered or to ask the runner to do something.
+pub trait Runner {
+  /// Size of the window, in pixels.
+  fn window_size(&self) -> [u32; 2];
+
+  /// Statistics about the frames rendered so far.
+  fn frame_stats(&self) -> &FrameStats;
+
+  /// State of the clock driving the demo, as of the current frame.
+  fn clock(&self) -> &ClockState;
+
+  /// Ask the runner to exit once the current frame is done.
+  fn request_exit(&mut self);
+
+  /// Ask the runner to take a screenshot of the current frame, once rendered.
+  fn request_screenshot(&mut self);
+
+  /// Ask the runner to capture `frames` consecutive frames, starting with the current one.
+  fn request_capture(&mut self, frames: u32);
+
+  /// Logger of the runner.
+  ///
+  /// Messages are forwarded to the logger of the demo’s context at the end of the frame – or of
+  /// the initialization.
+  fn logger(&mut self) -> &mut dyn Logger;
+
+  /// Named parameters, set from outside of the demo.
+  fn params(&self) -> &Params;
+}
+
+/// State of the clock driving a demo.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockState {
+  t: Time,
+  dt: Time,
+  paused: bool,
+  speed: f64
+}
+
+impl ClockState {
+  /// Time of the current frame.
+  pub fn time(&self) -> Time {
+    self.t
+  }
+
+  /// Time elapsed on the timeline since the previous frame.
+  ///
+  /// This is negative when the time went backward, after a seek or a wrap-around.
+  pub fn delta(&self) -> Time {
+    self.dt
+  }
+
+  /// Is the playback paused?
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  /// Playback speed.
+  pub fn speed(&self) -> f64 {
+    self.speed
+  }
+
+  pub(crate) fn update(&mut self, t: Time, paused: bool, speed: f64) {
+    self.dt = t - self.t;
+    self.t = t;
+    self.paused = paused;
+    self.speed = speed;
+  }
+}
+
+impl Default for ClockState {
+  fn default() -> Self {
+    ClockState {
+      t: Time::default(),
+      dt: Time::default(),
+      paused: false,
+      speed: 1.
+    }
+  }
+}
+
+/// State shared by all runners, backing their [`Runner`] implementation.
+#[derive(Debug)]
+pub(crate) struct RunnerState {
+  pub(crate) size: [u32; 2],
+  pub(crate) stats: FrameStats,
+  pub(crate) clock: ClockState,
+  pub(crate) exit_requested: bool,
+  pub(crate) capture: Capture,
+  pub(crate) logger: Buffer,
+  pub(crate) params: Params,
+  last_frame: Option<Monotonic>
+}
+
+impl RunnerState {
+  pub(crate) fn new(size: [u32; 2]) -> Self {
+    RunnerState {
+      size,
+      stats: FrameStats::default(),
+      clock: ClockState::default(),
+      exit_requested: false,
+      capture: Capture::default(),
+      logger: Buffer::new(),
+      params: Params::new(),
+      last_frame: None
+    }
+  }
+
+  /// Mark the end of a frame, accounting for the time spent since the end of the previous one.
+  pub(crate) fn end_frame(&mut self, render: Time, swap: Time) {
+    let now = Monotonic::now();
+
+    if let Some(last) = self.last_frame.replace(now) {
+      self.stats.record(FrameSample { render, swap, frame: last.elapsed_secs() });
+    }
+  }
+}
+
+/// Position on the timeline at which a runner starts, given either as a duration or as the name of
+/// a marker.
+///
+/// Strings that don’t parse as a [`DurationSpec`] but look like identifiers (letters, digits, `_`,
+/// `-` and `.`, starting with a letter or `_`) are marker names.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StartAt {
+  Duration(DurationSpec),
+  Marker(String)
+}
+
+impl StartAt {
+  /// Resolve the position against the markers of a demo.
+  pub fn resolve<T>(&self, markers: &MarkerTrack<T>) -> Result<Time, Error> {
+    match *self {
+      StartAt::Duration(spec) => Ok(spec.into()),
+      StartAt::Marker(ref name) => {
+        markers.cue(name).map(|cue| cue.time).ok_or_else(|| Error::unknown_marker(name.as_str()))
+      }
+    }
+  }
+}
+
+impl Default for StartAt {
+  fn default() -> Self {
+    StartAt::Duration(DurationSpec::default())
+  }
+}
+
+impl fmt::Display for StartAt {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      StartAt::Duration(ref spec) => spec.fmt(f),
+      StartAt::Marker(ref name) => f.write_str(name)
     }
   }
 }
+
+impl FromStr for StartAt {
+  type Err = DurationSpecError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.parse().map(StartAt::Duration).or_else(|e| {
+      let mut chars = s.chars();
+      let is_ident_start = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+      let is_ident = is_ident_start && chars.all(|c| c.is_alphanumeric() || "_-.".contains(c));
+
+      if is_ident {
+        Ok(StartAt::Marker(s.to_owned()))
+      } else {
+        Err(e)
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::time::marker::Cue;
+
+  use super::*;
+
+  #[test]
+  fn clock_state() {
+    let mut clock = ClockState::default();
+
+    clock.update(Time::from_secs(1.), false, 1.);
+    clock.update(Time::from_secs(1.5), true, 2.);
+    assert_eq!(clock.time(), Time::from_secs(1.5));
+    assert_eq!(clock.delta(), Time::from_secs(0.5));
+    assert!(clock.is_paused());
+    assert_eq!(clock.speed(), 2.);
+
+    clock.update(Time::from_secs(0.), false, 1.);
+    assert_eq!(clock.delta(), Time::from_secs(-1.5));
+  }
+
+  #[test]
+  fn parse_start_at() {
+    assert_eq!("1m30s".parse(), Ok(StartAt::Duration(DurationSpec::new(90, 0))));
+    assert_eq!("drop".parse(), Ok(StartAt::Marker("drop".to_owned())));
+    assert_eq!("bar_16.b".parse(), Ok(StartAt::Marker("bar_16.b".to_owned())));
+    assert!("1m75s".parse::<StartAt>().is_err());
+    assert!("".parse::<StartAt>().is_err());
+    assert_eq!("drop".parse::<StartAt>().unwrap().to_string(), "drop");
+    assert_eq!("90s".parse::<StartAt>().unwrap().to_string(), "1m30s");
+  }
+
+  #[test]
+  fn resolve_start_at() {
+    let markers: MarkerTrack<()> = vec![Cue::new("drop", Time::from_secs(42.), ())].into_iter().collect();
+
+    assert_eq!(StartAt::Marker("drop".to_owned()).resolve(&markers).ok(), Some(Time::from_secs(42.)));
+    assert!(StartAt::Marker("nope".to_owned()).resolve(&markers).is_err());
+    assert_eq!(StartAt::default().resolve(&markers).ok(), Some(Time::from_secs(0.)));
+  }
+}
@@ -0,0 +1,275 @@
+//! Frame capture.
+//!
+//! Runners can capture rendered frames – a single one for a screenshot, or several consecutive
+//! ones in burst mode – and write them as PNG files named after the demo time, never overwriting
+//! earlier captures. Surfaces only read
+//! the raw pixels back from their graphics context; the pixel conversion and the file naming are
+//! done here, independently of any graphics context.
+
+use png::{BitDepth, ColorType, Encoder};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::time::Time;
+
+/// An RGB image, with 8 bits per channel, stored row by row from the top.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+  width: u32,
+  height: u32,
+  pixels: Vec<u8>
+}
+
+impl Image {
+  /// Create an image from RGB pixels, stored row by row from the top.
+  ///
+  /// # Panics
+  ///
+  /// Panics if there are not exactly `width × height` pixels.
+  pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+    assert_eq!(pixels.len(), width as usize * height as usize * 3, "RGB pixels of a {}×{} image", width, height);
+    Image { width, height, pixels }
+  }
+
+  /// Create an image from RGBA pixels stored row by row from the bottom, as read back from an
+  /// OpenGL framebuffer. The alpha channel is dropped.
+  ///
+  /// # Panics
+  ///
+  /// Panics if there are not exactly `width × height` pixels.
+  pub fn from_gl_rgba(width: u32, height: u32, rgba: &[u8]) -> Self {
+    assert_eq!(rgba.len(), width as usize * height as usize * 4, "RGBA pixels of a {}×{} image", width, height);
+
+    let row_len = width as usize * 4;
+    let pixels = rgba
+      .chunks(row_len.max(1))
+      .rev()
+      .flat_map(|row| row.chunks(4).flat_map(|texel| texel[..3].iter().cloned()))
+      .collect();
+
+    Image { width, height, pixels }
+  }
+
+  pub fn width(&self) -> u32 {
+    self.width
+  }
+
+  pub fn height(&self) -> u32 {
+    self.height
+  }
+
+  /// RGB pixels, row by row from the top.
+  pub fn pixels(&self) -> &[u8] {
+    &self.pixels
+  }
+
+  /// Encode the image as PNG.
+  pub fn write_png<W>(&self, w: W) -> io::Result<()> where W: Write {
+    let mut encoder = Encoder::new(w, self.width, self.height);
+    encoder.set_color(ColorType::RGB);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    writer.write_image_data(&self.pixels).map_err(io::Error::from)
+  }
+
+  /// Save the image to a PNG file.
+  pub fn save<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+    self.write_png_file(File::create(path)?)
+  }
+
+  fn write_png_file(&self, file: File) -> io::Result<()> {
+    let mut w = BufWriter::new(file);
+    self.write_png(&mut w)?;
+    w.flush()
+  }
+}
+
+/// A frame to capture.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Frame {
+  /// Index of the frame in its burst.
+  pub index: u32,
+  /// Number of frames of the burst; 1 for a screenshot.
+  pub count: u32
+}
+
+impl Frame {
+  /// Name of the file to save the frame to, at a given time.
+  ///
+  /// Names sort by time: `001m02.250s.png` for a screenshot at 1m2.25s, and
+  /// `001m02.250s-0003.png` for the fourth frame of a burst.
+  pub fn file_name(&self, t: Time) -> String {
+    let millis = t.as_millis().max(0.).round() as u64;
+    let (mins, secs, millis) = (millis / 60_000, millis / 1000 % 60, millis % 1000);
+
+    if self.count > 1 {
+      format!("{:03}m{:02}.{:03}s-{:04}.png", mins, secs, millis, self.index)
+    } else {
+      format!("{:03}m{:02}.{:03}s.png", mins, secs, millis)
+    }
+  }
+
+  /// Save the image of the frame, captured at a given time, to a directory – created if needed.
+  ///
+  /// Existing files are never overwritten: if the [name of the frame](Frame::file_name) is taken –
+  /// by a capture at the same time, earlier in the session or in a previous one – a counter is
+  /// appended to it, as in `001m02.250s_2.png`. The path of the file is returned.
+  pub fn save<P>(&self, image: &Image, dir: P, t: Time) -> io::Result<PathBuf> where P: AsRef<Path> {
+    let dir = dir.as_ref();
+    let name = self.file_name(t);
+    let stem = name.trim_end_matches(".png");
+
+    fs::create_dir_all(dir)?;
+
+    let mut path = dir.join(&name);
+    let mut n = 1;
+
+    loop {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(file) => return image.write_png_file(file).map(|_| path),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+        Err(e) => return Err(e)
+      }
+
+      n += 1;
+      path = dir.join(format!("{}_{}.png", stem, n));
+    }
+  }
+}
+
+/// Pending capture requests.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Capture {
+  /// Number of frames left to capture.
+  remaining: u32,
+  /// Number of frames of the current burst.
+  count: u32
+}
+
+impl Capture {
+  /// Ask to capture `frames` consecutive frames, starting with the next one.
+  ///
+  /// A request made while a burst is ongoing extends it if it asks for more frames.
+  pub fn request(&mut self, frames: u32) {
+    if frames > self.remaining {
+      self.count = self.count - self.remaining + frames;
+      self.remaining = frames;
+    }
+  }
+
+  /// Is a capture pending?
+  pub fn is_pending(&self) -> bool {
+    self.remaining > 0
+  }
+
+  /// Take the next frame to capture, if any.
+  pub fn take(&mut self) -> Option<Frame> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    let frame = Frame { index: self.count - self.remaining, count: self.count };
+
+    self.remaining -= 1;
+
+    if self.remaining == 0 {
+      self.count = 0;
+    }
+
+    Some(frame)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::ScratchDir;
+
+  use super::*;
+
+  #[test]
+  fn gl_rgba() {
+    // 2×2, bottom row first: red, green / blue, white
+    let rgba = [
+      255, 0, 0, 0,  0, 255, 0, 0,
+      0, 0, 255, 255,  255, 255, 255, 255
+    ];
+    let image = Image::from_gl_rgba(2, 2, &rgba);
+
+    assert_eq!(image.pixels(), &[0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0]);
+    assert_eq!(Image::from_gl_rgba(0, 0, &[]).pixels(), &[] as &[u8]);
+  }
+
+  #[test]
+  #[should_panic]
+  fn gl_rgba_wrong_size() {
+    Image::from_gl_rgba(2, 2, &[0; 12]);
+  }
+
+  #[test]
+  fn png() {
+    let image = Image::new(3, 2, (0..18).collect());
+    let mut png = Vec::new();
+    image.write_png(&mut png).unwrap();
+
+    let decoder = png::Decoder::new(png.as_slice());
+    let (info, mut reader) = decoder.read_info().unwrap();
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+
+    assert_eq!((info.width, info.height, info.color_type), (3, 2, ColorType::RGB));
+    assert_eq!(pixels, image.pixels());
+  }
+
+  #[test]
+  fn file_names() {
+    let shot = Frame { index: 0, count: 1 };
+    let burst = Frame { index: 3, count: 60 };
+
+    assert_eq!(shot.file_name(Time::from_secs(62.25)), "001m02.250s.png");
+    assert_eq!(shot.file_name(Time::from_secs(0.0004)), "000m00.000s.png");
+    assert_eq!(shot.file_name(Time::from_secs(-1.)), "000m00.000s.png");
+    assert_eq!(burst.file_name(Time::from_secs(3725.5)), "062m05.500s-0003.png");
+  }
+
+  #[test]
+  fn saving_never_overwrites() {
+    let scratch = ScratchDir::new("capture-saving");
+    let dir = scratch.path().join("captures");
+    let image = Image::new(1, 1, vec![0, 0, 0]);
+    let shot = Frame { index: 0, count: 1 };
+    let t = Time::from_secs(62.25);
+
+    let paths = (0..3).map(|_| shot.save(&image, &dir, t).unwrap()).collect::<Vec<_>>();
+    let burst = Frame { index: 3, count: 60 }.save(&image, &dir, t).unwrap();
+    let mut names = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+    names.sort();
+
+    assert_eq!(paths, vec![dir.join("001m02.250s.png"), dir.join("001m02.250s_2.png"), dir.join("001m02.250s_3.png")]);
+    assert_eq!(burst, dir.join("001m02.250s-0003.png"));
+    assert_eq!(names, vec!["001m02.250s-0003.png", "001m02.250s.png", "001m02.250s_2.png", "001m02.250s_3.png"]);
+  }
+
+  #[test]
+  fn requests() {
+    let mut capture = Capture::default();
+    assert_eq!(capture.take(), None);
+
+    capture.request(1);
+    capture.request(1);
+    assert_eq!(capture.take(), Some(Frame { index: 0, count: 1 }));
+    assert_eq!(capture.take(), None);
+
+    capture.request(3);
+    assert_eq!(capture.take(), Some(Frame { index: 0, count: 3 }));
+    capture.request(1);
+    assert_eq!(capture.take(), Some(Frame { index: 1, count: 3 }));
+    capture.request(3);
+    assert_eq!(capture.take(), Some(Frame { index: 2, count: 5 }));
+    assert_eq!(capture.take(), Some(Frame { index: 3, count: 5 }));
+    assert!(capture.is_pending());
+    assert_eq!(capture.take(), Some(Frame { index: 4, count: 5 }));
+    assert!(!capture.is_pending());
+  }
+}
@@ -0,0 +1,404 @@
+//! Runner configuration.
+//!
+//! The options of the debug runner can come from several sources. From the highest to the lowest
+//! precedence:
+//!
+//!   1. The command line.
+//!   2. Environment variables, named after the options: `SPECTRA_WIDTH`, `SPECTRA_START_AT`,
+//!      `SPECTRA_DATA_ROOT`, etc.
+//!   3. A configuration file, given with `--config` or `SPECTRA_CONFIG` – [`DEFAULT_PATH`] is used
+//!      if it exists. The file is read as JSON, or as TOML if its extension is `toml` and the
+//!      `toml` feature is enabled.
+//!   4. The defaults of the runner.
+//!
+//! Each source is read into a [`Config`], in which every option is optional; configurations are
+//! then [merged](Config::merge) in order of precedence.
+
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app::runner::{Error, StartAt};
+use crate::app::runner::debug::Settings;
+use crate::app::runner::osc::Smoothing;
+use crate::app::runner::pacing::Vsync;
+use crate::logger::Level;
+use crate::time::{DurationSpec, Time};
+
+/// Configuration file read when none is given.
+pub const DEFAULT_PATH: &str = "spectra.json";
+
+/// Environment variable holding the path of the configuration file.
+pub const CONFIG_VAR: &str = "SPECTRA_CONFIG";
+
+/// Prefix of the environment variables holding options.
+const VAR_PREFIX: &str = "SPECTRA_";
+
+/// Options of a runner.
+///
+/// Durations, start positions, vsync modes and log levels are written as strings, with the same
+/// syntax as on the command line.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Title of the window.
+  pub title: Option<String>,
+  /// Width of the viewport.
+  pub width: Option<u32>,
+  /// Height of the viewport.
+  pub height: Option<u32>,
+  /// Fullscreen mode.
+  pub fullscreen: Option<bool>,
+  /// Vertical synchronization.
+  #[serde(with = "string")]
+  pub vsync: Option<Vsync>,
+  /// Duration at which the time wraps around to 0.
+  #[serde(with = "string")]
+  pub wrap_at: Option<DurationSpec>,
+  /// Position on the timeline to start at.
+  #[serde(with = "string")]
+  pub start_at: Option<StartAt>,
+  /// Maximum frame rate; 0 means no limit.
+  pub fps_limit: Option<u32>,
+  /// Interval between two frame statistics reports; 0 disables the reports.
+  #[serde(with = "string")]
+  pub stats_interval: Option<DurationSpec>,
+  /// File to export the frame timings to at exit.
+  pub stats_output: Option<PathBuf>,
+  /// Directory to load resources from.
+  pub data_root: Option<PathBuf>,
+  /// Verbosity of the runner’s logger.
+  #[serde(with = "string")]
+  pub log_level: Option<Level>,
+  /// Directory to save captured frames to.
+  pub capture_dir: Option<PathBuf>,
+  /// Number of frames captured in burst mode.
+  pub burst_frames: Option<u32>,
+  /// Address of the remote control server; see the [`remote`](crate::app::runner::remote) module.
+  pub remote: Option<String>,
+  /// UDP address to listen for OSC messages on; see the [`osc`](crate::app::runner::osc) module.
+  pub osc: Option<String>,
+  /// Time constant of the exponential smoothing of OSC parameters; 0 disables the smoothing.
+  #[serde(with = "string")]
+  pub osc_smoothing: Option<DurationSpec>
+}
+
+impl Config {
+  /// Defaults of the debug runner.
+  pub fn defaults(title: &str, width: u32, height: u32) -> Self {
+    Config {
+      title: Some(title.to_owned()),
+      width: Some(width),
+      height: Some(height),
+      fullscreen: Some(false),
+      vsync: None,
+      wrap_at: None,
+      start_at: Some(StartAt::default()),
+      fps_limit: None,
+      stats_interval: Some(DurationSpec::new(10, 0)),
+      stats_output: None,
+      data_root: Some(PathBuf::from("data")),
+      log_level: Some(Level::Debug),
+      capture_dir: Some(PathBuf::from("captures")),
+      burst_frames: Some(60),
+      remote: None,
+      osc: None,
+      osc_smoothing: None
+    }
+  }
+
+  /// Fill the unset options with the ones of a configuration of lower precedence.
+  pub fn merge(self, lower: Config) -> Self {
+    Config {
+      title: self.title.or(lower.title),
+      width: self.width.or(lower.width),
+      height: self.height.or(lower.height),
+      fullscreen: self.fullscreen.or(lower.fullscreen),
+      vsync: self.vsync.or(lower.vsync),
+      wrap_at: self.wrap_at.or(lower.wrap_at),
+      start_at: self.start_at.or(lower.start_at),
+      fps_limit: self.fps_limit.or(lower.fps_limit),
+      stats_interval: self.stats_interval.or(lower.stats_interval),
+      stats_output: self.stats_output.or(lower.stats_output),
+      data_root: self.data_root.or(lower.data_root),
+      log_level: self.log_level.or(lower.log_level),
+      capture_dir: self.capture_dir.or(lower.capture_dir),
+      burst_frames: self.burst_frames.or(lower.burst_frames),
+      remote: self.remote.or(lower.remote),
+      osc: self.osc.or(lower.osc),
+      osc_smoothing: self.osc_smoothing.or(lower.osc_smoothing)
+    }
+  }
+
+  /// Read a configuration file.
+  pub fn from_file<P>(path: P) -> Result<Self, Error> where P: AsRef<Path> {
+    let path = path.as_ref();
+    let content =
+      fs::read_to_string(path)
+        .map_err(|e| Error::invalid_config(format!("cannot read {}: {}", path.display(), e)))?;
+
+    let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+    let config = if is_toml { from_toml(&content) } else { from_json(&content) };
+
+    config.map_err(|e| Error::invalid_config(format!("{}: {}", path.display(), e)))
+  }
+
+  /// Read the configuration file given by the environment, if any, or the default one if it
+  /// exists.
+  pub fn from_default_file() -> Result<Self, Error> {
+    match std::env::var_os(CONFIG_VAR) {
+      Some(path) => Config::from_file(path),
+      None if Path::new(DEFAULT_PATH).is_file() => Config::from_file(DEFAULT_PATH),
+      None => Ok(Config::default())
+    }
+  }
+
+  /// Read the options from the environment variables.
+  pub fn from_env() -> Result<Self, Error> {
+    Config::from_vars(std::env::vars())
+  }
+
+  /// Read the options from a set of variables. Variables without the `SPECTRA_` prefix or not
+  /// naming an option are ignored.
+  pub fn from_vars<I>(vars: I) -> Result<Self, Error> where I: IntoIterator<Item = (String, String)> {
+    let mut config = Config::default();
+
+    for (var, value) in vars {
+      let name = match var.strip_prefix(VAR_PREFIX) {
+        Some(name) => name,
+        None => continue
+      };
+
+      let invalid = |reason: String| {
+        Error::invalid_config(format!("invalid value “{}” for {}: {}", value, var, reason))
+      };
+
+      match name {
+        "TITLE" => config.title = Some(value.clone()),
+        "WIDTH" => config.width = Some(parse(&value).map_err(invalid)?),
+        "HEIGHT" => config.height = Some(parse(&value).map_err(invalid)?),
+        "FULLSCREEN" => config.fullscreen = Some(parse_bool(&value).map_err(invalid)?),
+        "VSYNC" => config.vsync = Some(parse(&value).map_err(invalid)?),
+        "WRAP_AT" => config.wrap_at = Some(parse(&value).map_err(invalid)?),
+        "START_AT" => config.start_at = Some(parse(&value).map_err(invalid)?),
+        "FPS_LIMIT" => config.fps_limit = Some(parse(&value).map_err(invalid)?),
+        "STATS_INTERVAL" => config.stats_interval = Some(parse(&value).map_err(invalid)?),
+        "STATS_OUTPUT" => config.stats_output = Some(PathBuf::from(&value)),
+        "DATA_ROOT" => config.data_root = Some(PathBuf::from(&value)),
+        "LOG_LEVEL" => config.log_level = Some(parse(&value).map_err(invalid)?),
+        "CAPTURE_DIR" => config.capture_dir = Some(PathBuf::from(&value)),
+        "BURST_FRAMES" => config.burst_frames = Some(parse(&value).map_err(invalid)?),
+        "REMOTE" => config.remote = Some(value.clone()),
+        "OSC" => config.osc = Some(value.clone()),
+        "OSC_SMOOTHING" => config.osc_smoothing = Some(parse(&value).map_err(invalid)?),
+        _ => ()
+      }
+    }
+
+    Ok(config)
+  }
+
+  /// Settings of the main loop of the debug runner.
+  ///
+  /// Unset options take the default values of [`Settings`].
+  pub fn settings(&self) -> Settings {
+    let defaults = Settings::default();
+
+    Settings {
+      store_root: self.data_root.clone().unwrap_or(defaults.store_root),
+      start_at: self.start_at.clone().unwrap_or(defaults.start_at),
+      wrap_at: self.wrap_at.map(Time::from),
+      fps_limit: self.fps_limit.filter(|&fps| fps > 0),
+      stats_interval: self.stats_interval.map(Time::from).filter(|&t| t > Time::default()),
+      stats_output: self.stats_output.clone(),
+      log_level: self.log_level.unwrap_or(defaults.log_level),
+      capture_dir: self.capture_dir.clone().unwrap_or(defaults.capture_dir),
+      burst_frames: self.burst_frames.unwrap_or(defaults.burst_frames),
+      remote: self.remote.clone(),
+      osc: self.osc.clone(),
+      osc_smoothing: self.osc_smoothing
+        .map(Time::from)
+        .filter(|&t| t > Time::default())
+        .map_or(defaults.osc_smoothing, Smoothing::Exponential)
+    }
+  }
+
+  /// Pretty-printed JSON version of the configuration.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).expect("configurations are always serializable")
+  }
+}
+
+fn from_json(content: &str) -> Result<Config, String> {
+  serde_json::from_str(content).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "toml")]
+fn from_toml(content: &str) -> Result<Config, String> {
+  toml::from_str(content).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "toml"))]
+fn from_toml(_: &str) -> Result<Config, String> {
+  Err("TOML configurations require the “toml” feature".to_owned())
+}
+
+fn parse<T>(s: &str) -> Result<T, String> where T: FromStr, T::Err: ToString {
+  s.parse().map_err(|e: T::Err| e.to_string())
+}
+
+fn parse_bool(s: &str) -> Result<bool, String> {
+  match s {
+    "1" | "true" | "yes" | "on" => Ok(true),
+    "0" | "false" | "no" | "off" => Ok(false),
+    _ => Err("expected true or false".to_owned())
+  }
+}
+
+/// (De)serialization of optional values as strings, through their [`Display`](std::fmt::Display)
+/// and [`FromStr`] implementations.
+mod string {
+  use serde::de::{Deserialize, Deserializer, Error};
+  use serde::ser::Serializer;
+  use std::fmt::Display;
+  use std::str::FromStr;
+
+  pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+  where T: Display,
+        S: Serializer {
+    match *value {
+      Some(ref value) => serializer.collect_str(value),
+      None => serializer.serialize_none()
+    }
+  }
+
+  pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+  where T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de> {
+    Option::<String>::deserialize(deserializer)?
+      .map(|s| s.parse().map_err(D::Error::custom))
+      .transpose()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::ScratchDir;
+
+  use super::*;
+
+  fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()
+  }
+
+  #[test]
+  fn merge() {
+    let cli = Config { width: Some(1920), ..Config::default() };
+    let env = Config { width: Some(1280), height: Some(720), ..Config::default() };
+    let file = Config { height: Some(1080), fps_limit: Some(30), ..Config::default() };
+
+    let config = cli.merge(env).merge(file).merge(Config::defaults("demo", 960, 540));
+
+    assert_eq!(config.width, Some(1920));
+    assert_eq!(config.height, Some(720));
+    assert_eq!(config.fps_limit, Some(30));
+    assert_eq!(config.title.as_deref(), Some("demo"));
+  }
+
+  #[test]
+  fn json() {
+    let config = from_json(r#"{
+      "width": 640,
+      "fullscreen": true,
+      "wrap_at": "1m30s",
+      "start_at": "drop",
+      "vsync": "adaptive",
+      "log_level": "warn",
+      "data_root": "assets",
+      "osc": "0.0.0.0:9000",
+      "osc_smoothing": "250ms"
+    }"#).unwrap();
+
+    assert_eq!(config.width, Some(640));
+    assert_eq!(config.fullscreen, Some(true));
+    assert_eq!(config.wrap_at, Some(DurationSpec::new(90, 0)));
+    assert_eq!(config.start_at, Some(StartAt::Marker("drop".to_owned())));
+    assert_eq!(config.vsync, Some(Vsync::Adaptive));
+    assert_eq!(config.log_level, Some(Level::Warn));
+    assert_eq!(config.data_root, Some(PathBuf::from("assets")));
+    assert_eq!(config.osc.as_deref(), Some("0.0.0.0:9000"));
+    assert_eq!(config.settings().osc_smoothing, Smoothing::Exponential(Time::from_secs(0.25)));
+    assert_eq!(config.height, None);
+
+    assert!(from_json(r#"{ "widht": 640 }"#).is_err());
+    assert!(from_json(r#"{ "wrap_at": "1m75s" }"#).is_err());
+  }
+
+  #[test]
+  fn json_round_trip() {
+    let config = Config {
+      wrap_at: Some(DurationSpec::new(3, 250_000_000)),
+      ..Config::defaults("demo", 960, 540)
+    };
+
+    assert_eq!(from_json(&config.to_json()), Ok(config));
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn toml() {
+    let config = from_toml("title = \"demo\"\nwidth = 640\nstart_at = \"@120f\"\n").unwrap();
+
+    assert_eq!(config.title.as_deref(), Some("demo"));
+    assert_eq!(config.width, Some(640));
+    assert_eq!(config.start_at, Some(StartAt::Duration(DurationSpec::Frame { frame: 120, fps: None })));
+  }
+
+  #[test]
+  fn file() {
+    let dir = ScratchDir::new("runner-config-file");
+    let path = dir.write("config.json", r#"{ "fps_limit": 144 }"#);
+
+    assert_eq!(Config::from_file(&path).unwrap().fps_limit, Some(144));
+    assert!(Config::from_file(dir.path().join("no-such-config.json")).is_err());
+  }
+
+  #[test]
+  fn env_vars() {
+    let config = Config::from_vars(vars(&[
+      ("SPECTRA_WIDTH", "800"),
+      ("SPECTRA_FULLSCREEN", "yes"),
+      ("SPECTRA_STATS_INTERVAL", "0s"),
+      ("SPECTRA_DATA_ROOT", "/tmp/data"),
+      ("SPECTRA_REMOTE", "unix:/tmp/spectra.sock"),
+      ("SPECTRA_CONFIG", "ignored.json"),
+      ("HOME", "/root")
+    ])).unwrap();
+
+    assert_eq!(config.width, Some(800));
+    assert_eq!(config.fullscreen, Some(true));
+    assert_eq!(config.stats_interval, Some(DurationSpec::default()));
+    assert_eq!(config.data_root, Some(PathBuf::from("/tmp/data")));
+    assert_eq!(config.remote.as_deref(), Some("unix:/tmp/spectra.sock"));
+
+    assert!(Config::from_vars(vars(&[("SPECTRA_WIDTH", "wide")])).is_err());
+    assert!(Config::from_vars(vars(&[("SPECTRA_LOG_LEVEL", "loud")])).is_err());
+  }
+
+  #[test]
+  fn settings() {
+    let settings = Config {
+      fps_limit: Some(0),
+      stats_interval: Some(DurationSpec::default()),
+      ..Config::defaults("demo", 960, 540)
+    }.settings();
+
+    assert_eq!(settings.fps_limit, None);
+    assert_eq!(settings.stats_interval, None);
+    assert_eq!(settings.store_root, PathBuf::from("data"));
+    assert_eq!(settings.osc_smoothing, Smoothing::None);
+    assert_eq!(Config::default().settings(), Settings::default());
+  }
+}
@@ -1,25 +1,87 @@
 //! The debug runner.
 
-use luminance::context::GraphicsContext;
-use luminance::framebuffer::Framebuffer;
-use luminance_glfw::surface::{
-  Action, GlfwSurface, Key as GlfwKey, Surface, WindowDim, WindowEvent, WindowOpt
-};
+use luminance_glfw::surface::{WindowDim, WindowOpt};
+use std::path::PathBuf;
 use structopt::StructOpt;
 use warmy::{Store, StoreOpt};
 
 use crate::app::demo::Demo;
-use crate::app::runner;
+use crate::app::event::{Action, Event, Key as EventKey};
+use crate::app::runner::{self, ClockState, FrameStats, Params, RunnerState, StartAt};
+use crate::app::runner::config::Config;
+use crate::app::runner::osc::{Listener, Router, Smoothing};
+use crate::app::runner::pacing::{FrameLimiter, RealTimer, Vsync};
+use crate::app::runner::remote::{Request, Response, Server};
+use crate::app::runner::surface::{self, GlfwWindow, Surface};
+use crate::app::runner::transport::{Jump, Transport, TransportKey};
+use crate::app::runner::update::Stepper;
+use crate::logger::{Filter, Level, Logger};
 use crate::resource::key::Key;
-use crate::time::{DurationSpec, Monotonic};
+use crate::resource::timing::{self, SLOWEST_COUNT};
+use crate::time::{DurationSpec, Monotonic, Time};
+use crate::time::clock::Clock;
+use crate::time::marker::CueCursor;
 
 /// Debug runner.
 ///
-/// This runner shall be used whenever wanted to debug a demo.
-pub struct Runner;
+/// This runner shall be used whenever wanted to debug a demo. Playback can be controlled with the
+/// keyboard; see [`Command::from_key`](crate::app::runner::transport::Command::from_key) for the
+/// bindings. F12 captures the current frame, and shift+F12 captures a burst of frames; see the
+/// [`capture`](crate::app::runner::capture) module. It can also be controlled from another process
+/// with the `--remote` option; see the [`remote`](crate::app::runner::remote) module.
+pub struct Runner {
+  state: RunnerState
+}
+
+impl runner::Runner for Runner {
+  fn window_size(&self) -> [u32; 2] {
+    self.state.size
+  }
+
+  fn frame_stats(&self) -> &FrameStats {
+    &self.state.stats
+  }
+
+  fn clock(&self) -> &ClockState {
+    &self.state.clock
+  }
+
+  fn request_exit(&mut self) {
+    self.state.exit_requested = true;
+  }
+
+  fn request_screenshot(&mut self) {
+    self.state.capture.request(1);
+  }
+
+  fn request_capture(&mut self, frames: u32) {
+    self.state.capture.request(frames);
+  }
+
+  fn logger(&mut self) -> &mut dyn Logger {
+    &mut self.state.logger
+  }
+
+  fn params(&self) -> &Params {
+    &self.state.params
+  }
+}
 
 #[derive(StructOpt, Debug)]
 struct Opt {
+  /// Configuration file, read as JSON – or as TOML if its extension is “toml”. Options given on
+  /// the command line and through SPECTRA_* environment variables take precedence over the file.
+  #[structopt(short = "c", long = "config", parse(from_os_str))]
+  config: Option<PathBuf>,
+
+  /// Print the effective configuration and exit.
+  #[structopt(long = "print-config")]
+  print_config: bool,
+
+  /// Title of the window.
+  #[structopt(long = "title")]
+  title: Option<String>,
+
   /// Width of the viewport.
   #[structopt(short = "w", long = "width")]
   width: Option<u32>,
@@ -28,116 +90,581 @@ struct Opt {
   #[structopt(short = "h", long = "height")]
   height: Option<u32>,
 
-  /// Shall the viewport be in fullscreen mode?
+  /// Run in fullscreen mode.
   #[structopt(short = "f", long = "fullscreen")]
   fullscreen: bool,
 
+  /// Run in a window, even if the configuration asks for fullscreen.
+  #[structopt(long = "windowed", conflicts_with = "fullscreen")]
+  windowed: bool,
+
   /// Set a maximum runtime duration. Whenever the time arrives at this duration limit, it will
   /// wrap around to 0. If unset, the demo will run with a forever increasing time.
   ///
-  /// The syntax is “MmSs”, where M is optional. M must be a natural specifiying the number of
-  /// minutes and S a natural specifying the number of seconds. 30s will then be 30 seconds and 1m42
-  /// will be 1 minute and 42 seconds. The number of seconds must not exceed 59.
+  /// The syntax accepts unit-suffixed components (“1h2m3.25s”, “30s”, “500ms”), clock notation
+  /// (“1:02.250”) or frame numbers (“@1440f”, “@1440f/24”). Frame numbers without a frame rate are
+  /// resolved at 60 FPS. When a larger unit is present, the number of seconds must not exceed 59.
   #[structopt(short = "z", long = "wrap-at")]
   wrap_at: Option<DurationSpec>,
 
-  /// Start the demo at a given time.
-  #[structopt(short = "s", long = "start-at", default_value = "0s")]
-  start_at: DurationSpec
+  /// Start the demo at a given time or at a given marker. Times use the same syntax as --wrap-at.
+  /// Defaults to 0s.
+  #[structopt(short = "s", long = "start-at")]
+  start_at: Option<StartAt>,
+
+  /// Limit the frame rate. 0 means no limit.
+  #[structopt(long = "fps-limit")]
+  fps_limit: Option<u32>,
+
+  /// Vertical synchronization: “on”, “off” or “adaptive”. If unset, the driver’s default is used.
+  #[structopt(long = "vsync")]
+  vsync: Option<Vsync>,
+
+  /// Interval between two frame statistics reports. 0 disables the reports. Defaults to 10s.
+  #[structopt(long = "stats-interval")]
+  stats_interval: Option<DurationSpec>,
+
+  /// Export the timings of all the frames at exit, as JSON if the file has the “json” extension
+  /// and as CSV otherwise.
+  #[structopt(long = "stats-output", parse(from_os_str))]
+  stats_output: Option<PathBuf>,
+
+  /// Directory to load resources from. Defaults to “data”.
+  #[structopt(long = "data-root", parse(from_os_str))]
+  data_root: Option<PathBuf>,
+
+  /// Verbosity of the runner’s messages, including the ones the demo logs through the runner:
+  /// “error”, “warn”, “info” or “debug”. Defaults to debug.
+  #[structopt(long = "log-level")]
+  log_level: Option<Level>,
+
+  /// Directory to save captured frames to. Defaults to “captures”.
+  #[structopt(long = "capture-dir", parse(from_os_str))]
+  capture_dir: Option<PathBuf>,
+
+  /// Number of frames captured in burst mode. Defaults to 60.
+  #[structopt(long = "burst-frames")]
+  burst_frames: Option<u32>,
+
+  /// Listen for remote control commands on an address: “host:port” for TCP, or “unix:path” for a
+  /// Unix socket. The spectra-remote client connects to 127.0.0.1:6660 by default.
+  #[structopt(long = "remote")]
+  remote: Option<String>,
+
+  /// Listen for OSC messages on a UDP address, such as “0.0.0.0:9000”. Messages sent to
+  /// /spectra/param/<name> set the parameter <name>.
+  #[structopt(long = "osc")]
+  osc: Option<String>,
+
+  /// Smooth the parameters set through OSC exponentially, with a given time constant. Defaults to
+  /// 0s – no smoothing.
+  #[structopt(long = "osc-smoothing")]
+  osc_smoothing: Option<DurationSpec>
+}
+
+impl Opt {
+  /// Options set on the command line.
+  fn to_config(&self) -> Config {
+    Config {
+      title: self.title.clone(),
+      width: self.width,
+      height: self.height,
+      fullscreen: match (self.fullscreen, self.windowed) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None
+      },
+      vsync: self.vsync,
+      wrap_at: self.wrap_at,
+      start_at: self.start_at.clone(),
+      fps_limit: self.fps_limit,
+      stats_interval: self.stats_interval,
+      stats_output: self.stats_output.clone(),
+      data_root: self.data_root.clone(),
+      log_level: self.log_level,
+      capture_dir: self.capture_dir.clone(),
+      burst_frames: self.burst_frames,
+      remote: self.remote.clone(),
+      osc: self.osc.clone(),
+      osc_smoothing: self.osc_smoothing
+    }
+  }
+}
+
+/// Settings of the main loop of the debug runner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+  /// Directory to load resources from.
+  pub store_root: PathBuf,
+  /// Position on the timeline to start at.
+  pub start_at: StartAt,
+  /// Duration at which the time wraps around to 0.
+  pub wrap_at: Option<Time>,
+  /// Maximum frame rate.
+  pub fps_limit: Option<u32>,
+  /// Interval between two frame statistics reports.
+  pub stats_interval: Option<Time>,
+  /// File to export the frame timings to at exit.
+  pub stats_output: Option<PathBuf>,
+  /// Verbosity of the runner’s logger.
+  pub log_level: Level,
+  /// Directory to save captured frames to.
+  pub capture_dir: PathBuf,
+  /// Number of frames captured in burst mode.
+  pub burst_frames: u32,
+  /// Address to listen for remote control commands on.
+  pub remote: Option<String>,
+  /// UDP address to listen for OSC messages on.
+  pub osc: Option<String>,
+  /// Smoothing of the parameters set through OSC.
+  pub osc_smoothing: Smoothing
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      store_root: PathBuf::from("data"),
+      start_at: StartAt::default(),
+      wrap_at: None,
+      fps_limit: None,
+      stats_interval: None,
+      stats_output: None,
+      log_level: Level::Debug,
+      capture_dir: PathBuf::from("captures"),
+      burst_frames: 60,
+      remote: None,
+      osc: None,
+      osc_smoothing: Smoothing::None
+    }
+  }
+}
+
+/// Interval between two frame pacing reports, in seconds.
+const PACING_REPORT_INTERVAL: f64 = 5.;
+
+/// Logger of the runner’s messages: the context, filtered by the log level.
+fn log<L>(context: &mut L, level: Level) -> Filter<&mut L> where L: Logger {
+  Filter::new(context, level)
 }
 
 impl Runner {
-  pub fn run<D>(
+  /// Run a demo.
+  ///
+  /// The options are read from the command line, the environment and a configuration file – see
+  /// the [`config`](crate::app::runner::config) module; `title`, `def_width` and `def_height` are
+  /// used when they’re not set.
+  ///
+  /// The demo is driven by `clock`, started at the `start_at` option and wrapped by the `wrap_at`
+  /// option. The playback transport sits on top of it; if `clock` is a
+  /// [playback](crate::time::clock::Clock::is_playback), such as an
+  /// [audio player](crate::audio::Player), it follows the transport. Markers of the demo are used
+  /// for the marker navigation, to resolve `start_at` and to fire cues.
+  pub fn run<D, C>(
     title: &str,
     def_width: u32,
     def_height: u32,
+    clock: C,
     context: &mut D::Context
   ) -> Result<(), runner::Error>
-  where D: Demo<Self> {
-    info!(context, "starting « {} »", title);
-
-    // get CLI options
+  where D: Demo<Self>,
+        C: Clock {
+    // get the options, by order of precedence
     let opt = Opt::from_args();
-    let width = opt.width.unwrap_or(def_width);
-    let height = opt.height.unwrap_or(def_height);
-    let fullscreen = opt.fullscreen;
+    let file = match opt.config {
+      Some(ref path) => Config::from_file(path)?,
+      None => Config::from_default_file()?
+    };
+    let config = opt.to_config().merge(Config::from_env()?).merge(file);
+    let explicit_size = config.width.is_some() && config.height.is_some();
+    let config = config.merge(Config::defaults(title, def_width, def_height));
+
+    if opt.print_config {
+      println!("{}", config.to_json());
+      return Ok(());
+    }
+
+    let settings = config.settings();
+    let level = settings.log_level;
+    let title = config.title.as_deref().unwrap_or(title);
+    let width = config.width.unwrap_or(def_width);
+    let height = config.height.unwrap_or(def_height);
+
+    info!(log(context, level), "starting « {} »", title);
 
     // build the WindowDim
-    let win_dim = if fullscreen {
-      if opt.width.is_some() && opt.height.is_some() {
-        info!(context, "window mode: fullscreen restricted ({}×{})", width, height);
+    let win_dim = if config.fullscreen == Some(true) {
+      if explicit_size {
+        info!(log(context, level), "window mode: fullscreen restricted ({}×{})", width, height);
         WindowDim::FullscreenRestricted(width, height)
       } else {
-        info!(context, "window mode: fullscreen");
+        info!(log(context, level), "window mode: fullscreen");
         WindowDim::Fullscreen
       }
     } else {
-      info!(context, "window mode: windowed ({}×{})", width, height);
+      info!(log(context, level), "window mode: windowed ({}×{})", width, height);
       WindowDim::Windowed(width, height)
     };
 
     let win_opt = WindowOpt::default();
 
     // create the rendering surface
-    let mut surface =
-      GlfwSurface::new(win_dim, title, win_opt)
-        .map_err(|e| runner::Error::cannot_create_window(format!("{}", e)))?;
+    let mut surface = GlfwWindow::new(win_dim, title, win_opt)?;
+
+    if let Some(vsync) = config.vsync {
+      info!(log(context, level), "vsync: {}", vsync);
+      surface.set_vsync(vsync);
+    }
+
+    Self::run_on::<D, _, _>(&mut surface, &settings, clock, context)
+  }
+
+  /// Run a demo on a given surface.
+  ///
+  /// This is the main loop of [`Runner::run`], which can be used with any [`Surface`] – for
+  /// instance a [`Headless`](crate::app::runner::headless::Headless) one.
+  pub fn run_on<D, C, S>(
+    surface: &mut S,
+    settings: &Settings,
+    clock: C,
+    context: &mut D::Context
+  ) -> Result<(), runner::Error>
+  where D: Demo<Self>,
+        C: Clock,
+        S: Surface {
+    let level = settings.log_level;
 
     // create the store
-    let store_opt = StoreOpt::default().set_root("data");
+    let store_opt = StoreOpt::default().set_root(&settings.store_root);
     let mut store: Store<D::Context, Key> =
       Store::new(store_opt)
         .map_err(|e| runner::Error::cannot_create_store(format!("{}", e)))?;
 
     // create an instance of our runner to pass to the demo
-    let mut runner = Runner;
+    let mut runner = Runner { state: RunnerState::new(surface.size()) };
+
+    if settings.stats_output.is_some() {
+      runner.state.stats = FrameStats::default().record_history();
+    }
 
     // initialize the demo
+    timing::start_recording();
+    let init = D::init(&mut runner, &mut store, context);
+    runner.state.logger.flush(&mut log(context, level));
+    timing::log_slowest(&mut log(context, level), SLOWEST_COUNT);
     let mut demo =
-      D::init(&mut runner, &mut store, context)
-        .map_err(|e| runner::Error::demo_initialization_failure(format!("{:?}", e)))?;
+      init.map_err(|e| runner::Error::demo_initialization_failure(format!("{:?}", e)))?;
 
-    // create a bunch of objects needed for rendering
-    let mut back_buffer = Framebuffer::back_buffer(surface.size());
+    surface.initialized();
 
     // loop over time and run the demo
-    let start_time = Monotonic::now();
-    let start_at = opt.start_at;
-    let wrap_at = opt.wrap_at;
+    let markers = demo.markers();
+    let start_at = settings.start_at.resolve(&markers)?;
+    let mut transport = Transport::new(clock).wrap_at(settings.wrap_at);
+    transport.seek(start_at);
+    transport.set_markers(markers.times());
+
+    let mut cue_cursor = CueCursor::new();
+    let mut stepper = Stepper::new(demo.update_rate());
+    let mut shift = false;
+
+    // frame pacing
+    let mut limiter = settings.fps_limit.filter(|&fps| fps > 0).map(FrameLimiter::new);
+    let mut timer = RealTimer::new();
+    let mut last_pacing_report = Monotonic::now();
+
+    if let Some(fps) = settings.fps_limit {
+      info!(log(context, level), "frame rate limit: {}", fps);
+    }
+
+    let mut last_stats_report = Monotonic::now();
+
+    // remote control
+    let server = match settings.remote {
+      Some(ref address) => {
+        let server =
+          Server::bind(address)
+            .map_err(|e| runner::Error::cannot_start_server(format!("{}: {}", address, e)))?;
 
-    info!(context, "initialized; running…");
+        info!(log(context, level), "remote control listening on {}", (server.address()));
+        Some(server)
+      }
+
+      None => None
+    };
+
+    // OSC input
+    let osc = match settings.osc {
+      Some(ref address) => {
+        let listener =
+          Listener::bind(address)
+            .map_err(|e| runner::Error::cannot_listen_osc(format!("{}: {}", address, e)))?;
+
+        info!(log(context, level), "listening to OSC messages on {}", (listener.address()));
+        Some(listener)
+      }
+
+      None => None
+    };
+    let mut router = Router::new().smoothing(settings.osc_smoothing);
+    let mut last_osc_update = Monotonic::now();
+
+    info!(log(context, level), "initialized; running…");
 
     'run: loop {
       // treat events first
       for event in surface.poll_events() {
+        // shift modifier, used by the transport
+        if let Event::Key(EventKey::LeftShift, action) | Event::Key(EventKey::RightShift, action) = event {
+          shift = action.is_down();
+        }
+
+        let consumed = demo.event(&mut runner, context, &event);
+
         match event {
           // quit event
-          WindowEvent::Close | WindowEvent::Key(GlfwKey::Escape, _, Action::Release, _) => {
-            break 'run;
-          }
+          Event::Close => break 'run,
 
           // resize event
-          WindowEvent::FramebufferSize(w, h) => {
-            let size = [w as u32, h as u32];
+          Event::Resize(w, h) => {
+            let size = [w, h];
+
+            surface.resize(size);
+            runner.state.size = size;
+            demo.resize(&mut runner, context, w, h);
+          }
+
+          // the demo gets priority over hotkeys
+          _ if consumed => (),
+
+          Event::Key(EventKey::Escape, Action::Release) => break 'run,
+
+          // captures
+          Event::Key(EventKey::F12, Action::Press) => {
+            let frames = if shift { settings.burst_frames } else { 1 };
+            runner.state.capture.request(frames);
+          }
 
-            back_buffer = Framebuffer::back_buffer(size);
-            demo.resize(&mut runner, context, size[0], size[1]);
+          // transport controls
+          Event::Key(key, Action::Press) | Event::Key(key, Action::Repeat) => {
+            if let Some(command) = transport_key(key).and_then(|key| transport.key(key, shift)) {
+              let t = transport.position();
+              let speed = transport.speed();
+              let state = if transport.is_paused() { "paused" } else { "playing" };
+
+              info!(log(context, level), "{:?}: t = {}, ×{}, {}", command, t, speed, state);
+            }
           }
 
           _ => ()
         }
       }
 
+      // treat remote requests
+      for pending in server.iter().flat_map(Server::poll) {
+        debug!(log(context, level), "remote: {}", (pending.request()));
+
+        let transport = &mut transport;
+        let state = |transport: &Transport<_>| Response::State {
+          time: transport.position(),
+          paused: transport.is_paused(),
+          speed: transport.speed()
+        };
+
+        let response = match *pending.request() {
+          Request::Seek(ref at) => match at.resolve(&markers) {
+            Ok(t) => {
+              transport.seek(t);
+              state(transport)
+            }
+
+            Err(e) => Response::error(e.to_string())
+          },
+
+          Request::Pause => {
+            transport.set_paused(true);
+            state(transport)
+          }
+
+          Request::Play => {
+            transport.set_paused(false);
+            state(transport)
+          }
+
+          Request::Speed(speed) => {
+            transport.set_speed(speed);
+            state(transport)
+          }
+
+          Request::Reload => {
+            store.sync(context);
+            Response::Ok
+          }
+
+          Request::Set(ref name, value) => {
+            runner.state.params.set(name.as_str(), value);
+            Response::Ok
+          }
+
+          Request::Time => state(transport),
+
+          Request::Stats => {
+            let stats = &runner.state.stats;
+
+            Response::Stats {
+              frames: stats.frames(),
+              fps: stats.fps(),
+              frame: stats.frame(),
+              render: stats.render(),
+              swap: stats.swap()
+            }
+          }
+
+          Request::Quit => {
+            pending.reply(Response::Ok);
+            info!(log(context, level), "exit requested remotely");
+            break 'run;
+          }
+        };
+
+        pending.reply(response);
+      }
+
+      // treat OSC messages; parameters are smoothed in real time, even when paused
+      if let Some(ref listener) = osc {
+        for packet in listener.poll() {
+          match packet {
+            Ok(packet) => router.route_packet(&packet),
+            Err(e) => warn!(log(context, level), "invalid OSC packet: {}", e)
+          }
+        }
+
+        router.advance(last_osc_update.elapsed_secs(), &mut runner.state.params);
+        last_osc_update = Monotonic::now();
+      }
+
       // render a frame
-      let t = start_time.elapsed_secs();
-      let t = if let Some(wrap_t) = wrap_at { t.wrap_around(wrap_t.into()) } else { t };
-      let t = t.offset(start_at.into());
-      let builder = surface.pipeline_builder();
+      let t = transport.tick();
 
-      demo.render(&mut runner, context, t, &back_buffer, builder);
-      surface.swap_buffers();
+      match transport.take_jump() {
+        Some(Jump::Seek) => cue_cursor.reset(),
+        Some(Jump::Loop { end, start }) => cue_cursor.loop_back(end, start),
+        None => ()
+      }
+
+      runner.state.clock.update(t, transport.is_paused(), transport.speed());
+
+      for cue in cue_cursor.advance(&markers, t) {
+        demo.cue(&mut runner, context, cue, t);
+      }
+
+      let steps = stepper.advance(t);
+      let interpolation = steps.interpolation();
+
+      for (ut, dt) in steps {
+        demo.update(&mut runner, context, ut, dt);
+      }
+
+      let frame = runner.state.capture.take();
+      let (render, swap, image) = surface::render_timed(surface, t, frame.is_some(), |target| {
+        demo.render(&mut runner, context, t, interpolation, target);
+      });
+      runner.state.end_frame(render, swap);
+      runner.state.logger.flush(&mut log(context, level));
+
+      if let (Some(frame), Some(image)) = (frame, image) {
+        match frame.save(&image, &settings.capture_dir, t) {
+          Ok(path) => info!(log(context, level), "captured {}", (path.display())),
+          Err(e) => {
+            let path = settings.capture_dir.join(frame.file_name(t));
+            error!(log(context, level), "cannot capture {}: {}", (path.display()), e)
+          }
+        }
+      }
+
+      if let Some(interval) = settings.stats_interval {
+        if last_stats_report.elapsed_secs() >= interval {
+          let stats = &runner.state.stats;
+
+          info!(log(context, level), "frame stats: {}", stats);
+          last_stats_report = Monotonic::now();
+        }
+      }
+
+      if runner.state.exit_requested {
+        info!(log(context, level), "exit requested by the demo");
+        break;
+      }
+
+      if let Some(ref mut limiter) = limiter {
+        limiter.wait(&mut timer);
+
+        if last_pacing_report.elapsed_secs().as_secs() >= PACING_REPORT_INTERVAL {
+          let stats = *limiter.stats();
+
+          info!(log(context, level), "pacing: {}", stats);
+          limiter.reset_stats();
+          last_pacing_report = Monotonic::now();
+        }
+      }
+    }
+
+    demo.shutdown(&mut runner, context);
+    runner.state.logger.flush(&mut log(context, level));
+
+    if let Some(stats) = limiter.map(|limiter| *limiter.stats()).filter(|stats| stats.frames() > 0) {
+      info!(log(context, level), "pacing: {}", stats);
+    }
+
+    if let Some(ref path) = settings.stats_output {
+      let result = runner.state.stats.export(path);
+      let path = path.display();
+
+      match result {
+        Ok(_) => info!(log(context, level), "frame stats exported to {}", path),
+        Err(e) => error!(log(context, level), "cannot export frame stats to {}: {}", path, e)
+      }
     }
 
     Ok(())
   }
 }
+
+/// Map a key to a transport key.
+fn transport_key(key: EventKey) -> Option<TransportKey> {
+  match key {
+    EventKey::Space => Some(TransportKey::Space),
+    EventKey::Left => Some(TransportKey::Left),
+    EventKey::Right => Some(TransportKey::Right),
+    EventKey::Up => Some(TransportKey::Up),
+    EventKey::Down => Some(TransportKey::Down),
+    EventKey::PageUp => Some(TransportKey::PageUp),
+    EventKey::PageDown => Some(TransportKey::PageDown),
+    EventKey::Backspace => Some(TransportKey::Backspace),
+    EventKey::A => Some(TransportKey::A),
+    EventKey::B => Some(TransportKey::B),
+    EventKey::C => Some(TransportKey::C),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fullscreen(args: &[&str]) -> Option<bool> {
+    Opt::from_iter(Some("demo").iter().chain(args)).to_config().fullscreen
+  }
+
+  #[test]
+  fn fullscreen_flags() {
+    assert_eq!(fullscreen(&[]), None);
+    assert_eq!(fullscreen(&["--fullscreen"]), Some(true));
+    assert_eq!(fullscreen(&["--windowed"]), Some(false));
+    assert!(Opt::from_iter_safe(&["demo", "--fullscreen", "--windowed"]).is_err());
+
+    // the command line overrides the configuration file
+    let file = Config { fullscreen: Some(true), ..Config::default() };
+    let opt = Opt::from_iter(&["demo", "--windowed"]);
+    assert_eq!(opt.to_config().merge(file).fullscreen, Some(false));
+  }
+}
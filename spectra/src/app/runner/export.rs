@@ -0,0 +1,502 @@
+//! The export runner.
+//!
+//! This runner renders a demo offline, at a perfect frame rate regardless of how fast the machine
+//! is: a [`FixedStep`] clock is stepped from a start time to an end time, and every rendered frame
+//! is read back and streamed to a [`FrameSink`]. Two sinks are provided:
+//!
+//!   - [`PngSequence`] writes numbered PNG files to a directory.
+//!   - [`Y4m`] writes a raw YUV4MPEG2 stream, typically to the standard output to pipe it into a
+//!     video encoder – `ffmpeg -i - out.mp4`, for instance.
+
+use luminance_glfw::surface::{WindowDim, WindowOpt};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use warmy::{Store, StoreOpt};
+
+use crate::app::demo::Demo;
+use crate::app::event::{Action, Event, Key as EventKey};
+use crate::app::runner::{self, ClockState, FrameStats, Params, RunnerState, StartAt};
+use crate::app::runner::capture::Image;
+use crate::app::runner::surface::{self, GlfwWindow, Surface};
+use crate::app::runner::update::Stepper;
+use crate::logger::Logger;
+use crate::resource::key::Key;
+use crate::resource::timing::{self, SLOWEST_COUNT};
+use crate::time::{Monotonic, Time};
+use crate::time::clock::{Clock, FixedStep};
+use crate::time::marker::CueCursor;
+
+/// Destination of exported frames.
+pub trait FrameSink {
+  /// Write a frame, given its index – starting at 0 – and its time.
+  fn write_frame(&mut self, index: u64, t: Time, image: &Image) -> io::Result<()>;
+
+  /// Called once all the frames were written.
+  fn finish(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Sink writing numbered PNG files – `000000.png`, `000001.png`, etc. – to a directory.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PngSequence {
+  dir: PathBuf
+}
+
+impl PngSequence {
+  /// Write the files to `dir`, which is created if needed.
+  pub fn new<P>(dir: P) -> Self where P: Into<PathBuf> {
+    PngSequence { dir: dir.into() }
+  }
+
+  /// Path of the file of a frame.
+  pub fn path(&self, index: u64) -> PathBuf {
+    self.dir.join(format!("{:06}.png", index))
+  }
+}
+
+impl FrameSink for PngSequence {
+  fn write_frame(&mut self, index: u64, _: Time, image: &Image) -> io::Result<()> {
+    if index == 0 {
+      fs::create_dir_all(&self.dir)?;
+    }
+
+    image.save(self.path(index))
+  }
+}
+
+/// Sink writing a YUV4MPEG2 stream, with full-resolution chroma (4:4:4) in the BT.601 limited
+/// range.
+///
+/// When streaming to the standard output, nothing else should be written to it: use a context
+/// logger writing elsewhere.
+#[derive(Debug)]
+pub struct Y4m<W> {
+  w: W,
+  fps: u32,
+  size: Option<[u32; 2]>
+}
+
+impl<W> Y4m<W> where W: Write {
+  pub fn new(w: W, fps: u32) -> Self {
+    Y4m { w, fps, size: None }
+  }
+
+  /// Get the underlying writer back.
+  pub fn into_inner(self) -> W {
+    self.w
+  }
+}
+
+impl Y4m<io::Stdout> {
+  /// Stream to the standard output.
+  pub fn stdout(fps: u32) -> Self {
+    Y4m::new(io::stdout(), fps)
+  }
+}
+
+impl<W> FrameSink for Y4m<W> where W: Write {
+  fn write_frame(&mut self, _: u64, _: Time, image: &Image) -> io::Result<()> {
+    let size = [image.width(), image.height()];
+
+    match self.size {
+      None => {
+        writeln!(self.w, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", size[0], size[1], self.fps)?;
+        self.size = Some(size);
+      }
+
+      Some(stream_size) if stream_size != size => {
+        let msg = format!(
+          "frame size {}×{} differs from the stream size {}×{}",
+          size[0],
+          size[1],
+          stream_size[0],
+          stream_size[1]
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+      }
+
+      _ => ()
+    }
+
+    self.w.write_all(b"FRAME\n")?;
+    self.w.write_all(&rgb_to_yuv444(image.pixels()))
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    self.w.flush()
+  }
+}
+
+/// Convert RGB pixels to planar YCbCr 4:4:4 (BT.601, limited range).
+fn rgb_to_yuv444(rgb: &[u8]) -> Vec<u8> {
+  let len = rgb.len() / 3;
+  let mut yuv = vec![0; len * 3];
+  let (y, uv) = yuv.split_at_mut(len);
+  let (u, v) = uv.split_at_mut(len);
+
+  for (i, texel) in rgb.chunks(3).enumerate() {
+    let (r, g, b) = (texel[0] as i32, texel[1] as i32, texel[2] as i32);
+
+    y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+    u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+    v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+  }
+
+  yuv
+}
+
+/// Export runner.
+pub struct Runner {
+  state: RunnerState
+}
+
+impl runner::Runner for Runner {
+  fn window_size(&self) -> [u32; 2] {
+    self.state.size
+  }
+
+  fn frame_stats(&self) -> &FrameStats {
+    &self.state.stats
+  }
+
+  fn clock(&self) -> &ClockState {
+    &self.state.clock
+  }
+
+  fn request_exit(&mut self) {
+    self.state.exit_requested = true;
+  }
+
+  fn request_screenshot(&mut self) {
+    self.state.capture.request(1);
+  }
+
+  fn request_capture(&mut self, frames: u32) {
+    self.state.capture.request(frames);
+  }
+
+  fn logger(&mut self) -> &mut dyn Logger {
+    &mut self.state.logger
+  }
+
+  fn params(&self) -> &Params {
+    &self.state.params
+  }
+}
+
+/// Export runner configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+  title: String,
+  width: u32,
+  height: u32,
+  fps: u32,
+  start_at: StartAt,
+  end_at: StartAt,
+  store_root: PathBuf
+}
+
+impl Config {
+  /// Create a configuration exporting `width`×`height` frames at `fps` frames per second, from 0
+  /// to `end_at`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `fps` is 0.
+  pub fn new<T>(title: T, width: u32, height: u32, fps: u32, end_at: StartAt) -> Self where T: Into<String> {
+    assert!(fps > 0, "an export needs a non-zero frame rate");
+
+    Config {
+      title: title.into(),
+      width,
+      height,
+      fps,
+      start_at: StartAt::default(),
+      end_at,
+      store_root: PathBuf::from("data")
+    }
+  }
+
+  /// Start the export at a given time or marker.
+  pub fn start_at(self, start_at: StartAt) -> Self {
+    Config { start_at, ..self }
+  }
+
+  /// Directory to load resources from.
+  pub fn store_root<P>(self, store_root: P) -> Self where P: Into<PathBuf> {
+    Config { store_root: store_root.into(), ..self }
+  }
+}
+
+/// Number of frames between two progress reports.
+const PROGRESS_REPORT_FRAMES: u64 = 100;
+
+impl Runner {
+  /// Export a demo, rendering it in a window.
+  pub fn run<D, K>(
+    config: &Config,
+    sink: &mut K,
+    context: &mut D::Context
+  ) -> Result<(), runner::Error>
+  where D: Demo<Self>,
+        K: ?Sized + FrameSink {
+    info!(context, "exporting « {} » ({}×{} at {} FPS)", (config.title), (config.width), (config.height), (config.fps));
+
+    let win_dim = WindowDim::Windowed(config.width, config.height);
+    let mut surface = GlfwWindow::new(win_dim, &config.title, WindowOpt::default())?;
+
+    Self::run_on::<D, _, _>(&mut surface, config, sink, context)
+  }
+
+  /// Export a demo rendered on a given surface, which must be able to capture frames.
+  pub fn run_on<D, S, K>(
+    surface: &mut S,
+    config: &Config,
+    sink: &mut K,
+    context: &mut D::Context
+  ) -> Result<(), runner::Error>
+  where D: Demo<Self>,
+        S: Surface,
+        K: ?Sized + FrameSink {
+    // create the store
+    let store_opt = StoreOpt::default().set_root(&config.store_root);
+    let mut store: Store<D::Context, Key> =
+      Store::new(store_opt)
+        .map_err(|e| runner::Error::cannot_create_store(format!("{}", e)))?;
+
+    let mut runner = Runner { state: RunnerState::new(surface.size()) };
+
+    timing::start_recording();
+    let init = D::init(&mut runner, &mut store, context);
+    runner.state.logger.flush(context);
+    timing::log_slowest(context, SLOWEST_COUNT);
+    let mut demo =
+      init.map_err(|e| runner::Error::demo_initialization_failure(format!("{:?}", e)))?;
+
+    surface.initialized();
+
+    let markers = demo.markers();
+    let start_at = config.start_at.resolve(&markers)?;
+    let end_at = config.end_at.resolve(&markers)?;
+    let mut clock = FixedStep::new(config.fps).offset(start_at);
+    let mut cue_cursor = CueCursor::new();
+    let mut stepper = Stepper::new(demo.update_rate());
+
+    info!(context, "exporting from {} to {}…", start_at, end_at);
+
+    let export_start = Monotonic::now();
+    let mut frames = 0;
+
+    'run: loop {
+      for event in surface.poll_events() {
+        let consumed = demo.event(&mut runner, context, &event);
+
+        match event {
+          Event::Key(EventKey::Escape, Action::Release) if consumed => (),
+
+          Event::Close | Event::Key(EventKey::Escape, Action::Release) => {
+            info!(context, "interrupted");
+            break 'run;
+          }
+
+          _ => ()
+        }
+      }
+
+      let t = clock.tick();
+
+      if t >= end_at {
+        break;
+      }
+
+      runner.state.clock.update(t, false, 1.);
+
+      for cue in cue_cursor.advance(&markers, t) {
+        demo.cue(&mut runner, context, cue, t);
+      }
+
+      let steps = stepper.advance(t);
+      let interpolation = steps.interpolation();
+
+      for (ut, dt) in steps {
+        demo.update(&mut runner, context, ut, dt);
+      }
+
+      let (render, swap, image) = surface::render_timed(surface, t, true, |target| {
+        demo.render(&mut runner, context, t, interpolation, target);
+      });
+      runner.state.end_frame(render, swap);
+      runner.state.logger.flush(context);
+
+      let image = image.ok_or_else(|| runner::Error::cannot_export("the surface cannot capture frames"))?;
+
+      sink
+        .write_frame(frames, t, &image)
+        .map_err(|e| runner::Error::cannot_export(format!("frame {}: {}", frames, e)))?;
+      frames += 1;
+
+      if frames % PROGRESS_REPORT_FRAMES == 0 {
+        info!(context, "exported {} frames (t = {})", frames, t);
+      }
+
+      if runner.state.exit_requested {
+        info!(context, "exit requested by the demo");
+        break;
+      }
+    }
+
+    sink.finish().map_err(|e| runner::Error::cannot_export(format!("{}", e)))?;
+
+    demo.shutdown(&mut runner, context);
+    runner.state.logger.flush(context);
+
+    let elapsed = export_start.elapsed_secs();
+    let speed = if elapsed.as_secs() > 0. { frames as f64 / elapsed.as_secs() } else { 0. };
+    info!(context, "exported {} frames in {} ({:.1} FPS)", frames, elapsed, speed);
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::app::demo::{Key as ResourceKey, Target};
+  use crate::app::runner::headless::{Headless, Script};
+  use crate::testing::{Recorder, ScratchDir};
+  use crate::time::DurationSpec;
+  use crate::time::marker::{Cue, MarkerTrack};
+
+  use super::*;
+
+  /// Sink keeping the frames it gets.
+  #[derive(Default)]
+  struct Frames {
+    frames: Vec<(u64, f64, [u32; 2])>,
+    finished: bool
+  }
+
+  impl FrameSink for Frames {
+    fn write_frame(&mut self, index: u64, t: Time, image: &Image) -> io::Result<()> {
+      self.frames.push((index, t.as_millis().round(), [image.width(), image.height()]));
+      Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+      self.finished = true;
+      Ok(())
+    }
+  }
+
+  struct Blank;
+
+  impl<R> Demo<R> for Blank where R: runner::Runner {
+    type Context = Recorder<Vec<f64>>;
+
+    type Error = ();
+
+    fn init(
+      _: &mut R,
+      _: &mut Store<Self::Context, ResourceKey>,
+      _: &mut Self::Context
+    ) -> Result<Self, Self::Error> {
+      Ok(Blank)
+    }
+
+    fn resize(&mut self, _: &mut R, _: &mut Self::Context, _: u32, _: u32) {}
+
+    fn render(&mut self, _: &mut R, renders: &mut Self::Context, t: Time, _: f64, _: Target) {
+      renders.push(t.as_millis().round());
+    }
+
+    fn markers(&self) -> MarkerTrack<()> {
+      vec![Cue::new("outro", Time::from_secs(1.), ())].into_iter().collect()
+    }
+
+    fn cue(&mut self, runner: &mut R, _: &mut Self::Context, _: &Cue<()>, _: Time) {
+      runner.request_exit();
+    }
+  }
+
+  fn secs(secs: u64) -> StartAt {
+    StartAt::Duration(DurationSpec::new(secs, 0))
+  }
+
+  #[test]
+  fn yuv() {
+    let rgb = [255, 255, 255, 0, 0, 0, 255, 0, 0];
+    assert_eq!(rgb_to_yuv444(&rgb), vec![235, 16, 82, 128, 128, 90, 128, 128, 240]);
+  }
+
+  #[test]
+  fn y4m() {
+    let mut y4m = Y4m::new(Vec::new(), 25);
+    let image = Image::new(2, 1, vec![255, 255, 255, 0, 0, 0]);
+
+    y4m.write_frame(0, Time::from_secs(0.), &image).unwrap();
+    y4m.write_frame(1, Time::from_secs(0.04), &image).unwrap();
+    assert!(y4m.write_frame(2, Time::from_secs(0.08), &Image::new(1, 1, vec![0; 3])).is_err());
+    y4m.finish().unwrap();
+
+    let mut expected = b"YUV4MPEG2 W2 H1 F25:1 Ip A1:1 C444\n".to_vec();
+    for _ in 0..2 {
+      expected.extend_from_slice(b"FRAME\n");
+      expected.extend_from_slice(&[235, 16, 128, 128, 128, 128]);
+    }
+
+    assert_eq!(y4m.into_inner(), expected);
+  }
+
+  #[test]
+  fn png_sequence() {
+    let scratch = ScratchDir::new("export-png-sequence");
+    let dir = scratch.path().join("frames");
+
+    let mut sink = PngSequence::new(&dir);
+    let image = Image::new(1, 1, vec![1, 2, 3]);
+
+    for index in 0..3 {
+      sink.write_frame(index, Time::from_secs(0.), &image).unwrap();
+    }
+
+    assert!(sink.path(2).ends_with("000002.png"));
+
+    let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    names.sort();
+    assert_eq!(names, vec!["000000.png", "000001.png", "000002.png"]);
+  }
+
+  #[test]
+  fn export() {
+    let store = ScratchDir::new("export-export");
+    let mut surface = Headless::new([4, 2], Script::new(100));
+    let config = Config::new("test", 4, 2, 10, secs(2)).start_at(secs(1)).store_root(store.path());
+    let mut sink = Frames::default();
+    let mut renders = Recorder::default();
+
+    Runner::run_on::<Blank, _, _>(&mut surface, &config, &mut sink, &mut renders).unwrap();
+
+    // the cue at 1s asks to exit, but only once the frame is rendered and exported
+    assert_eq!(*renders, vec![1000.]);
+    assert_eq!(sink.frames, vec![(0, 1000., [4, 2])]);
+    assert!(sink.finished);
+  }
+
+  #[test]
+  fn export_range() {
+    let store = ScratchDir::new("export-export-range");
+    let mut surface = Headless::new([4, 2], Script::new(100));
+    let config =
+      Config::new("test", 4, 2, 10, StartAt::Marker("outro".to_owned())).store_root(store.path());
+    let mut sink = Frames::default();
+    let mut renders = Recorder::default();
+
+    Runner::run_on::<Blank, _, _>(&mut surface, &config, &mut sink, &mut renders).unwrap();
+
+    let times: Vec<_> = sink.frames.iter().map(|&(_, t, _)| t).collect();
+    assert_eq!(times, (0..10).map(|i| i as f64 * 100.).collect::<Vec<_>>());
+    assert_eq!(*renders, times);
+    assert_eq!(sink.frames.last().map(|f| f.0), Some(9));
+  }
+}
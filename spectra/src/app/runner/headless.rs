@@ -0,0 +1,495 @@
+//! Headless running.
+//!
+//! A [`Headless`] surface has no window and no graphics context: it plays a [`Script`] of events
+//! and records what the runner loop does with it. Paired with a deterministic clock – such as a
+//! [`FixedStep`](crate::time::clock::FixedStep) – it allows to test demo lifecycles and runner
+//! logic on machines without a display or a GPU.
+//!
+//! Because there’s no graphics context, demos render to [headless targets](Target::has_graphics),
+//! which skip the GPU work; the render calls are recorded, and captured frames are black.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use crate::app::demo::{Demo, Target};
+use crate::app::event::{Action, Event, Key};
+use crate::app::runner;
+use crate::app::runner::capture::Image;
+use crate::app::runner::debug::{self, Settings};
+use crate::app::runner::surface::Surface;
+use crate::time::Time;
+use crate::time::clock::Clock;
+
+/// A list of events to play, frame by frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+  /// Events, sorted by frame.
+  events: VecDeque<(u64, Event)>,
+  /// Number of frames to render before closing.
+  frames: u64
+}
+
+impl Script {
+  /// Create a script closing the surface after `frames` frames.
+  pub fn new(frames: u64) -> Self {
+    Script { events: VecDeque::new(), frames }
+  }
+
+  /// Send an event before a given frame.
+  pub fn at(mut self, frame: u64, event: Event) -> Self {
+    let index = self.events.iter().position(|&(f, _)| f > frame).unwrap_or(self.events.len());
+    self.events.insert(index, (frame, event));
+    self
+  }
+
+  /// Press and release a key before a given frame.
+  pub fn key(self, frame: u64, key: Key) -> Self {
+    self.at(frame, Event::Key(key, Action::Press)).at(frame, Event::Key(key, Action::Release))
+  }
+
+  /// Resize the framebuffer before a given frame.
+  pub fn resize(self, frame: u64, width: u32, height: u32) -> Self {
+    self.at(frame, Event::Resize(width, height))
+  }
+}
+
+/// A call made by a runner to a [`Headless`] surface.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+  /// The demo got initialized.
+  Init,
+  /// The demo got resized.
+  Resize([u32; 2]),
+  /// A frame got rendered at a given time.
+  Render(Time)
+}
+
+/// Mock surface, without window nor graphics context.
+#[derive(Clone, Debug)]
+pub struct Headless {
+  size: [u32; 2],
+  script: Script,
+  frame: u64,
+  calls: Vec<Call>
+}
+
+impl Headless {
+  /// Create a headless surface of a given size, playing a script.
+  pub fn new(size: [u32; 2], script: Script) -> Self {
+    Headless { size, script, frame: 0, calls: Vec::new() }
+  }
+
+  /// Number of frames rendered so far.
+  pub fn frames(&self) -> u64 {
+    self.frame
+  }
+
+  /// Calls recorded so far.
+  pub fn calls(&self) -> &[Call] {
+    &self.calls
+  }
+
+  /// Times of the rendered frames.
+  pub fn render_times(&self) -> Vec<Time> {
+    self.calls.iter().filter_map(|call| match *call {
+      Call::Render(t) => Some(t),
+      _ => None
+    }).collect()
+  }
+}
+
+impl Surface for Headless {
+  fn size(&self) -> [u32; 2] {
+    self.size
+  }
+
+  fn poll_events(&mut self) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    while self.script.events.front().is_some_and(|&(frame, _)| frame <= self.frame) {
+      events.extend(self.script.events.pop_front().map(|(_, event)| event));
+    }
+
+    if self.frame >= self.script.frames {
+      events.push(Event::Close);
+    }
+
+    events
+  }
+
+  fn resize(&mut self, size: [u32; 2]) {
+    self.size = size;
+    self.calls.push(Call::Resize(size));
+  }
+
+  fn render<F>(&mut self, t: Time, f: F) where F: FnOnce(Target) {
+    f(Target::headless());
+    self.calls.push(Call::Render(t));
+    self.frame += 1;
+  }
+
+  /// Captured frames are black.
+  fn render_captured<F>(&mut self, t: Time, f: F) -> Option<Image> where F: FnOnce(Target) {
+    self.render(t, f);
+
+    let [width, height] = self.size;
+    Some(Image::new(width, height, vec![0; width as usize * height as usize * 3]))
+  }
+
+  fn initialized(&mut self) {
+    self.calls.push(Call::Init);
+  }
+}
+
+/// Run a demo on a headless surface with the debug runner, from 0 and without wrap-around.
+///
+/// Resources are loaded from `store_root`.
+pub fn run<D, C, P>(
+  surface: &mut Headless,
+  store_root: P,
+  clock: C,
+  context: &mut D::Context
+) -> Result<(), runner::Error>
+where D: Demo<debug::Runner>,
+      C: Clock,
+      P: AsRef<Path> {
+  let settings = Settings { store_root: store_root.as_ref().to_owned(), ..Settings::default() };
+  debug::Runner::run_on::<D, _, _>(surface, &settings, clock, context)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use crate::app::demo::{Key as ResourceKey, Store};
+  use crate::app::runner::Runner;
+  use crate::logger::{Level, Logger, StdoutLogger};
+  use crate::testing::{Recorder, ScratchDir};
+  use crate::time::clock::FixedStep;
+  use crate::time::marker::{Cue, MarkerTrack};
+
+  use super::*;
+
+  /// Demo counting what happens to it.
+  #[derive(Default)]
+  struct Counter {
+    size: [u32; 2],
+    cues: Vec<String>
+  }
+
+  impl<R> Demo<R> for Counter where R: Runner {
+    type Context = StdoutLogger;
+
+    type Error = ();
+
+    type Payload = u32;
+
+    fn init(
+      runner: &mut R,
+      _: &mut Store<Self::Context, ResourceKey>,
+      _: &mut Self::Context
+    ) -> Result<Self, Self::Error> {
+      Ok(Counter { size: runner.window_size(), cues: Vec::new() })
+    }
+
+    fn resize(&mut self, runner: &mut R, _: &mut Self::Context, width: u32, height: u32) {
+      assert_eq!(runner.window_size(), [width, height]);
+      self.size = [width, height];
+    }
+
+    fn render(&mut self, _: &mut R, _: &mut Self::Context, _: Time, _: f64, target: Target) {
+      assert!(!target.has_graphics());
+      target.render(|_, _| unreachable!("headless targets skip the GPU work"));
+    }
+
+    fn markers(&self) -> MarkerTrack<u32> {
+      vec![Cue::new("drop", Time::from_secs(0.2), 7)].into_iter().collect()
+    }
+
+    fn cue(&mut self, runner: &mut R, _: &mut Self::Context, cue: &Cue<u32>, _: Time) {
+      assert_eq!(cue.payload, 7);
+      self.cues.push(cue.name.clone());
+
+      if self.cues.len() == 1 {
+        runner.request_exit();
+      }
+    }
+  }
+
+  /// Demo consuming all the key events.
+  struct Greedy {
+    keys: Vec<Key>
+  }
+
+  impl<R> Demo<R> for Greedy where R: Runner {
+    type Context = StdoutLogger;
+
+    type Error = ();
+
+    fn init(
+      _: &mut R,
+      _: &mut Store<Self::Context, ResourceKey>,
+      _: &mut Self::Context
+    ) -> Result<Self, Self::Error> {
+      Ok(Greedy { keys: Vec::new() })
+    }
+
+    fn resize(&mut self, _: &mut R, _: &mut Self::Context, _: u32, _: u32) {}
+
+    fn render(&mut self, _: &mut R, _: &mut Self::Context, _: Time, _: f64, _: Target) {}
+
+    fn event(&mut self, runner: &mut R, _: &mut Self::Context, event: &Event) -> bool {
+      match *event {
+        Event::Key(key, Action::Press) => {
+          self.keys.push(key);
+
+          // exit once both keys went through
+          if self.keys.len() == 2 {
+            runner.request_exit();
+          }
+
+          true
+        }
+
+        Event::Key(..) => true,
+        _ => false
+      }
+    }
+  }
+
+  /// Context journaling the updates and shutdown of [`Stepped`].
+  #[derive(Default)]
+  struct Journal {
+    updates: Vec<(f64, f64)>,
+    /// Value of the “foo” parameter at the last update.
+    foo: Option<f32>,
+    shut_down: bool,
+    /// Times of the rendered frames.
+    renders: Vec<Time>
+  }
+
+  /// Demo updated at a fixed rate of 20 updates per second.
+  struct Stepped;
+
+  impl<R> Demo<R> for Stepped where R: Runner {
+    type Context = Recorder<Journal>;
+
+    type Error = ();
+
+    fn init(
+      runner: &mut R,
+      _: &mut Store<Self::Context, ResourceKey>,
+      _: &mut Self::Context
+    ) -> Result<Self, Self::Error> {
+      info!(runner.logger(), "stepped demo ready");
+      Ok(Stepped)
+    }
+
+    fn resize(&mut self, _: &mut R, _: &mut Self::Context, _: u32, _: u32) {}
+
+    fn update(&mut self, runner: &mut R, journal: &mut Self::Context, t: Time, dt: Time) {
+      journal.updates.push((t.as_millis().round(), dt.as_millis().round()));
+      journal.foo = runner.params().get("foo");
+    }
+
+    fn update_rate(&self) -> Option<u32> {
+      Some(20)
+    }
+
+    fn render(&mut self, _: &mut R, journal: &mut Self::Context, t: Time, _: f64, _: Target) {
+      journal.renders.push(t);
+    }
+
+    fn shutdown(&mut self, runner: &mut R, journal: &mut Self::Context) {
+      info!(runner.logger(), "stepped demo done");
+      journal.shut_down = true;
+    }
+  }
+
+  fn secs(t: f64) -> Time {
+    Time::from_secs(t)
+  }
+
+  #[test]
+  fn lifecycle() {
+    let store = ScratchDir::new("headless-lifecycle");
+    let script = Script::new(3).resize(1, 640, 480);
+    let mut surface = Headless::new([800, 600], script);
+
+    run::<Counter, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut StdoutLogger).unwrap();
+
+    assert_eq!(surface.calls(), &[
+      Call::Init,
+      Call::Render(secs(0.)),
+      Call::Resize([640, 480]),
+      Call::Render(secs(0.1)),
+      Call::Render(secs(0.2))
+    ]);
+  }
+
+  #[test]
+  fn transport_keys() {
+    let store = ScratchDir::new("headless-transport-keys");
+    let script = Script::new(5).key(1, Key::Space).key(3, Key::Space).key(4, Key::Right);
+    let mut surface = Headless::new([800, 600], script);
+
+    run::<Counter, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut StdoutLogger).unwrap();
+
+    let millis: Vec<_> = surface.render_times().into_iter().map(|t| t.as_millis().round()).collect();
+    assert_eq!(millis, vec![0., 0., 0., 100., 1200.]);
+  }
+
+  #[test]
+  fn escape_closes() {
+    let store = ScratchDir::new("headless-escape-closes");
+    let script = Script::new(10).key(2, Key::Escape);
+    let mut surface = Headless::new([800, 600], script);
+
+    run::<Counter, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut StdoutLogger).unwrap();
+
+    assert_eq!(surface.frames(), 2);
+  }
+
+  #[test]
+  fn consumed_events() {
+    let store = ScratchDir::new("headless-consumed-events");
+    let script = Script::new(10).key(1, Key::Space).key(2, Key::Escape);
+    let mut surface = Headless::new([800, 600], script);
+
+    run::<Greedy, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut StdoutLogger).unwrap();
+
+    // neither the transport nor the escape hotkey saw the keys
+    let millis: Vec<_> = surface.render_times().into_iter().map(|t| t.as_millis().round()).collect();
+    assert_eq!(millis, vec![0., 100., 200.]);
+  }
+
+  #[test]
+  fn exit_request() {
+    let store = ScratchDir::new("headless-exit-request");
+    let mut surface = Headless::new([800, 600], Script::new(10));
+
+    run::<Counter, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut StdoutLogger).unwrap();
+
+    // the demo requests to exit when the cue at 0.2s fires
+    assert_eq!(surface.render_times(), vec![secs(0.), secs(0.1), secs(0.2)]);
+  }
+
+  #[test]
+  fn fixed_rate_updates() {
+    let store = ScratchDir::new("headless-fixed-rate-updates");
+    let mut surface = Headless::new([800, 600], Script::new(3));
+    let mut journal = Recorder::<Journal>::default();
+
+    run::<Stepped, _, _>(&mut surface, store.path(), FixedStep::new(10), &mut journal).unwrap();
+
+    assert_eq!(journal.updates, vec![(0., 50.), (50., 50.), (100., 50.), (150., 50.), (200., 50.)]);
+    assert_eq!(journal.renders, surface.render_times());
+    assert!(journal.shut_down);
+
+    // messages logged through the runner end up in the context
+    let demo_logs: Vec<_> =
+      journal.logs.messages().iter().filter(|(_, log)| log.starts_with("stepped demo")).collect();
+    assert_eq!(demo_logs, vec![
+      &(Level::Info, "stepped demo ready".to_owned()),
+      &(Level::Info, "stepped demo done".to_owned())
+    ]);
+  }
+
+  #[test]
+  fn log_level() {
+    let store = ScratchDir::new("headless-log-level");
+    let mut surface = Headless::new([800, 600], Script::new(3));
+    let settings = Settings { store_root: store.path().to_owned(), log_level: Level::Warn, ..Settings::default() };
+    let mut journal = Recorder::<Journal>::default();
+
+    debug::Runner::run_on::<Stepped, _, _>(&mut surface, &settings, FixedStep::new(10), &mut journal).unwrap();
+
+    // neither the runner’s messages nor the demo’s ones logged through the runner went through
+    assert!(journal.logs.messages().iter().all(|&(level, _)| level <= Level::Warn), "{:?}", journal.logs);
+    assert!(journal.shut_down);
+  }
+
+  #[test]
+  fn captures() {
+    let script = Script::new(6)
+      .key(1, Key::F12)
+      .at(3, Event::Key(Key::LeftShift, Action::Press))
+      .key(3, Key::F12)
+      .at(3, Event::Key(Key::LeftShift, Action::Release));
+    let mut surface = Headless::new([4, 2], script);
+
+    let dir = ScratchDir::new("headless-captures");
+    let capture_dir = dir.path().join("captures");
+
+    let settings = Settings {
+      store_root: dir.path().to_owned(),
+      capture_dir: capture_dir.clone(),
+      burst_frames: 2,
+      ..Settings::default()
+    };
+
+    debug::Runner::run_on::<Stepped, _, _>(&mut surface, &settings, FixedStep::new(10), &mut Recorder::<Journal>::default())
+      .unwrap();
+
+    let mut names: Vec<_> =
+      fs::read_dir(&capture_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    names.sort();
+
+    assert_eq!(names, vec!["000m00.100s.png", "000m00.300s-0000.png", "000m00.400s-0001.png"]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn remote_control() {
+    use serde_json::Value;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::app::runner::remote::Client;
+
+    let dir = ScratchDir::new("headless-remote-control");
+    let socket = dir.path().join("runner.sock");
+    let address = format!("unix:{}", socket.display());
+    let settings = Settings { store_root: dir.path().to_owned(), remote: Some(address.clone()), ..Settings::default() };
+
+    // the runner plays until the client quits it – or gives up, if the client fails
+    let mut surface = Headless::new([800, 600], Script::new(1_000_000));
+
+    let client = thread::spawn(move || {
+      let mut client = (0..1000)
+        .find_map(|_| Client::connect(&address).map_err(|_| thread::sleep(Duration::from_millis(5))).ok())
+        .expect("cannot connect to the runner");
+      let mut send = |command: &str| serde_json::from_str::<Value>(&client.send(command).unwrap()).unwrap();
+
+      let mut responses: Vec<_> = ["set foo 0.5", "pause", "seek 10s", "seek nowhere", "time", "seek 0s"]
+        .iter()
+        .map(|command| send(command))
+        .collect();
+
+      // let a frame go by, so that the demo gets updated after the last seek
+      let frames = send("stats")["frames"].as_u64().unwrap();
+      let next_frame = (0..1000).any(|_| send("stats")["frames"].as_u64().unwrap() > frames);
+      assert!(next_frame);
+
+      responses.push(send("quit"));
+      responses
+    });
+
+    let mut journal = Recorder::<Journal>::default();
+    debug::Runner::run_on::<Stepped, _, _>(&mut surface, &settings, FixedStep::new(10), &mut journal).unwrap();
+
+    let responses = client.join().unwrap();
+    let types: Vec<_> = responses.iter().map(|r| r["type"].as_str().unwrap()).collect();
+
+    assert_eq!(types, vec!["ok", "state", "state", "error", "state", "state", "ok"]);
+    assert_eq!(responses[4]["time"], 10.);
+    assert_eq!(responses[4]["paused"], true);
+
+    assert_eq!(journal.foo, Some(0.5));
+    assert!(journal.shut_down);
+    assert!(!socket.exists());
+
+    // the demo got updated at the seek, but not further while paused
+    assert_eq!(journal.updates.last().unwrap().0, 0.);
+  }
+}
@@ -0,0 +1,1058 @@
+//! Open Sound Control input.
+//!
+//! Controllers such as TouchOSC, Max or SuperCollider send OSC 1.0 packets over UDP. A [`Listener`]
+//! receives and decodes them in the background, and a [`Router`] maps their addresses onto named
+//! [parameters](crate::app::runner::Params), optionally smoothing the changes.
+//!
+//! Messages are routed by address:
+//!
+//!   - Explicit [`Binding`]s map an address to a parameter, with their own smoothing. Their
+//!     addresses can be patterns too, to bind a whole group of controls to the same parameter.
+//!   - Messages sent under the [prefix](Router::prefix) of a router – `/spectra/param/` by default
+//!     – set the parameter named after the rest of the address: `/spectra/param/glow` sets `glow`.
+//!
+//! Message addresses can be OSC patterns – `/spectra/param/{glow,bloom}`, `/spectra/param/*` – in
+//! which case they set every parameter they [match](matches) among the bound ones and the ones
+//! already set under the prefix.
+//!
+//! The first numeric argument of a message is its value: integers, floats, doubles and booleans
+//! are accepted; other messages are ignored. Time tags of bundles are ignored too: their messages
+//! are routed as soon as they are received.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::runner::Params;
+use crate::time::Time;
+
+/// Prefix of the addresses routed to parameters by default.
+pub const DEFAULT_PREFIX: &str = "/spectra/param/";
+
+/// Time tag meaning “immediately”.
+pub const IMMEDIATELY: u64 = 1;
+
+/// Maximum size of a UDP datagram.
+const MAX_PACKET_SIZE: usize = 65_536;
+
+/// Maximum nesting of arrays in a message, and of bundles in a bundle.
+const MAX_NESTING: usize = 16;
+
+/// Maximum length of an address pattern; longer patterns match nothing.
+const MAX_PATTERN_LEN: usize = 1024;
+
+/// Possible decoding errors.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+  /// The packet ends before the end of an element.
+  Truncated,
+  /// The size of an element is not a multiple of 4.
+  Misaligned,
+  /// A string is not null-terminated or not UTF-8.
+  InvalidString,
+  /// A packet is neither a message nor a bundle.
+  UnknownPacket,
+  /// Unsupported type tag.
+  UnknownTag(char),
+  /// An array is opened and not closed, or closed and not opened.
+  UnbalancedArray,
+  /// Arrays or bundles are nested too deeply.
+  TooDeep
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Error::Truncated => f.write_str("truncated packet"),
+      Error::Misaligned => f.write_str("size not a multiple of 4"),
+      Error::InvalidString => f.write_str("invalid string"),
+      Error::UnknownPacket => f.write_str("neither a message nor a bundle"),
+      Error::UnknownTag(tag) => write!(f, "unknown type tag “{}”", tag),
+      Error::UnbalancedArray => f.write_str("unbalanced array"),
+      Error::TooDeep => write!(f, "arrays or bundles nested more than {} levels deep", MAX_NESTING)
+    }
+  }
+}
+
+/// An OSC packet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Packet {
+  Message(Message),
+  Bundle(Bundle)
+}
+
+impl Packet {
+  /// Decode a packet.
+  pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+    Packet::decode_nested(bytes, 0)
+  }
+
+  /// Decode a packet found `depth` bundles deep.
+  fn decode_nested(bytes: &[u8], depth: usize) -> Result<Self, Error> {
+    if padded(bytes.len()) != bytes.len() {
+      return Err(Error::Misaligned);
+    }
+
+    match bytes.first() {
+      Some(b'/') => Message::decode(bytes).map(Packet::Message),
+      Some(b'#') if depth == MAX_NESTING => Err(Error::TooDeep),
+      Some(b'#') => Bundle::decode(bytes, depth).map(Packet::Bundle),
+      _ => Err(Error::UnknownPacket)
+    }
+  }
+
+  /// Encode a packet.
+  pub fn encode(&self) -> Vec<u8> {
+    match *self {
+      Packet::Message(ref msg) => msg.encode(),
+      Packet::Bundle(ref bundle) => bundle.encode()
+    }
+  }
+
+  /// All the messages of the packet, bundles flattened.
+  pub fn messages(&self) -> Vec<&Message> {
+    match *self {
+      Packet::Message(ref msg) => vec![msg],
+      Packet::Bundle(ref bundle) => bundle.packets.iter().flat_map(Packet::messages).collect()
+    }
+  }
+}
+
+/// An OSC message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+  /// Address pattern.
+  pub address: String,
+  pub args: Vec<Arg>
+}
+
+impl Message {
+  pub fn new<A>(address: A, args: Vec<Arg>) -> Self where A: Into<String> {
+    Message { address: address.into(), args }
+  }
+
+  /// First numeric argument, as a parameter value.
+  pub fn value(&self) -> Option<f32> {
+    self.args.iter().filter_map(Arg::as_f32).next()
+  }
+
+  fn decode(bytes: &[u8]) -> Result<Self, Error> {
+    let mut reader = Reader::new(bytes);
+    let address = reader.string()?;
+
+    // type tags can be omitted by old implementations
+    if reader.is_empty() {
+      return Ok(Message { address, args: Vec::new() });
+    }
+
+    let tags = reader.string()?;
+    let mut tags = tags.strip_prefix(',').ok_or(Error::UnknownPacket)?.chars();
+    let args = reader.args(&mut tags, 0)?;
+
+    Ok(Message { address, args })
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut tags = String::from(",");
+    let mut data = Vec::new();
+
+    for arg in &self.args {
+      arg.encode(&mut tags, &mut data);
+    }
+
+    let mut bytes = Vec::new();
+    write_string(&mut bytes, &self.address);
+    write_string(&mut bytes, &tags);
+    bytes.extend(data);
+    bytes
+  }
+}
+
+/// An OSC bundle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bundle {
+  /// NTP time tag; [`IMMEDIATELY`] for immediate dispatch.
+  pub time_tag: u64,
+  pub packets: Vec<Packet>
+}
+
+impl Bundle {
+  pub fn new(time_tag: u64, packets: Vec<Packet>) -> Self {
+    Bundle { time_tag, packets }
+  }
+
+  fn decode(bytes: &[u8], depth: usize) -> Result<Self, Error> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.string()? != "#bundle" {
+      return Err(Error::UnknownPacket);
+    }
+
+    let time_tag = reader.u64()?;
+    let mut packets = Vec::new();
+
+    while !reader.is_empty() {
+      let size = reader.i32()?;
+
+      if size < 0 {
+        return Err(Error::Truncated);
+      }
+
+      packets.push(Packet::decode_nested(reader.take(size as usize)?, depth + 1)?);
+    }
+
+    Ok(Bundle { time_tag, packets })
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_string(&mut bytes, "#bundle");
+    bytes.extend(&self.time_tag.to_be_bytes());
+
+    for packet in &self.packets {
+      let packet = packet.encode();
+      bytes.extend(&(packet.len() as i32).to_be_bytes());
+      bytes.extend(packet);
+    }
+
+    bytes
+  }
+}
+
+/// Argument of a message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+  Int(i32),
+  Float(f32),
+  String(String),
+  Blob(Vec<u8>),
+  Long(i64),
+  Double(f64),
+  TimeTag(u64),
+  Symbol(String),
+  Char(char),
+  Color([u8; 4]),
+  Midi([u8; 4]),
+  Bool(bool),
+  Nil,
+  Impulse,
+  Array(Vec<Arg>)
+}
+
+impl Arg {
+  /// Numeric value of the argument, if any; booleans are 0 or 1.
+  pub fn as_f32(&self) -> Option<f32> {
+    match *self {
+      Arg::Int(i) => Some(i as f32),
+      Arg::Float(x) => Some(x),
+      Arg::Long(i) => Some(i as f32),
+      Arg::Double(x) => Some(x as f32),
+      Arg::Bool(b) => Some(if b { 1. } else { 0. }),
+      _ => None
+    }
+  }
+
+  fn encode(&self, tags: &mut String, data: &mut Vec<u8>) {
+    match *self {
+      Arg::Int(i) => {
+        tags.push('i');
+        data.extend(&i.to_be_bytes());
+      }
+
+      Arg::Float(x) => {
+        tags.push('f');
+        data.extend(&x.to_bits().to_be_bytes());
+      }
+
+      Arg::String(ref s) => {
+        tags.push('s');
+        write_string(data, s);
+      }
+
+      Arg::Blob(ref blob) => {
+        tags.push('b');
+        data.extend(&(blob.len() as i32).to_be_bytes());
+        data.extend(blob);
+        pad(data);
+      }
+
+      Arg::Long(i) => {
+        tags.push('h');
+        data.extend(&i.to_be_bytes());
+      }
+
+      Arg::Double(x) => {
+        tags.push('d');
+        data.extend(&x.to_bits().to_be_bytes());
+      }
+
+      Arg::TimeTag(t) => {
+        tags.push('t');
+        data.extend(&t.to_be_bytes());
+      }
+
+      Arg::Symbol(ref s) => {
+        tags.push('S');
+        write_string(data, s);
+      }
+
+      Arg::Char(c) => {
+        tags.push('c');
+        data.extend(&(c as u32).to_be_bytes());
+      }
+
+      Arg::Color(rgba) => {
+        tags.push('r');
+        data.extend(&rgba);
+      }
+
+      Arg::Midi(midi) => {
+        tags.push('m');
+        data.extend(&midi);
+      }
+
+      Arg::Bool(b) => tags.push(if b { 'T' } else { 'F' }),
+      Arg::Nil => tags.push('N'),
+      Arg::Impulse => tags.push('I'),
+
+      Arg::Array(ref args) => {
+        tags.push('[');
+
+        for arg in args {
+          arg.encode(tags, data);
+        }
+
+        tags.push(']');
+      }
+    }
+  }
+}
+
+/// Does an OSC address pattern match an address?
+///
+/// Patterns are matched part by part – wildcards never match a `/`:
+///
+///   - `?` matches any character.
+///   - `*` matches any sequence of characters, including an empty one.
+///   - `[abc]` matches any of the listed characters, which can be ranges as in `[a-z0-9]`; `[!…]`
+///     matches any character not listed.
+///   - `{foo,bar}` matches any of the listed strings.
+///
+/// Patterns longer than 1024 bytes match nothing.
+pub fn matches(pattern: &str, address: &str) -> bool {
+  pattern.len() <= MAX_PATTERN_LEN && match_bytes(pattern.as_bytes(), address.as_bytes())
+}
+
+/// Match a pattern by going through it once, keeping track of all the positions in the address
+/// reachable so far – which takes a time proportional to the product of their lengths, instead of
+/// backtracking.
+fn match_bytes(mut pattern: &[u8], address: &[u8]) -> bool {
+  let mut reachable = vec![false; address.len() + 1];
+  let mut next = reachable.clone();
+  reachable[0] = true;
+
+  // characters matched by the single-character elements
+  let single = |next: &mut [bool], reachable: &[bool], f: &dyn Fn(u8) -> bool| {
+    for (i, &c) in address.iter().enumerate() {
+      next[i + 1] = reachable[i] && c != b'/' && f(c);
+    }
+  };
+
+  while let Some(&first) = pattern.first() {
+    next[0] = false;
+
+    match first {
+      b'*' => {
+        // a run of stars matches like a single one: up to the next /
+        pattern = &pattern[pattern.iter().take_while(|&&c| c == b'*').count()..];
+        next[0] = reachable[0];
+
+        for i in 1..=address.len() {
+          next[i] = reachable[i] || (next[i - 1] && address[i - 1] != b'/');
+        }
+      }
+
+      b'?' => {
+        pattern = &pattern[1..];
+        single(&mut next, &reachable, &|_| true);
+      }
+
+      b'[' => match pattern.iter().position(|&c| c == b']') {
+        Some(end) => {
+          let class = &pattern[1..end];
+          pattern = &pattern[end + 1..];
+          single(&mut next, &reachable, &|c| match_class(class, c));
+        }
+
+        None => return false
+      },
+
+      b'{' => match pattern.iter().position(|&c| c == b'}') {
+        Some(end) => {
+          next.iter_mut().for_each(|n| *n = false);
+
+          for alt in pattern[1..end].split(|&c| c == b',') {
+            for i in (0..=address.len()).filter(|&i| reachable[i] && address[i..].starts_with(alt)) {
+              next[i + alt.len()] = true;
+            }
+          }
+
+          pattern = &pattern[end + 1..];
+        }
+
+        None => return false
+      },
+
+      c => {
+        pattern = &pattern[1..];
+
+        for (i, &a) in address.iter().enumerate() {
+          next[i + 1] = reachable[i] && a == c;
+        }
+      }
+    }
+
+    std::mem::swap(&mut reachable, &mut next);
+
+    if !reachable.contains(&true) {
+      return false;
+    }
+  }
+
+  reachable[address.len()]
+}
+
+/// Match a character against the inside of a `[…]` class.
+fn match_class(class: &[u8], c: u8) -> bool {
+  let (negated, class) = match class.first() {
+    Some(b'!') => (true, &class[1..]),
+    _ => (false, class)
+  };
+
+  let mut found = false;
+  let mut i = 0;
+
+  while i < class.len() {
+    if i + 2 < class.len() && class[i + 1] == b'-' {
+      found |= class[i] <= c && c <= class[i + 2];
+      i += 3;
+    } else {
+      found |= class[i] == c;
+      i += 1;
+    }
+  }
+
+  found != negated
+}
+
+/// Does an address contain pattern characters?
+fn is_pattern(address: &str) -> bool {
+  address.contains(|c| "*?[]{}".contains(c))
+}
+
+/// How parameter changes are smoothed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Smoothing {
+  /// Changes apply immediately.
+  #[default]
+  None,
+  /// Values move towards their targets exponentially, with a given time constant: after that
+  /// time, about 63% of the change is done.
+  Exponential(Time),
+  /// Values move linearly towards their targets, reaching them after a given time.
+  Linear(Time)
+}
+
+/// A mapping of an OSC address onto a parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+  address: String,
+  param: String,
+  smoothing: Smoothing
+}
+
+impl Binding {
+  /// Map an address onto a parameter, without smoothing.
+  pub fn new<A, P>(address: A, param: P) -> Self where A: Into<String>, P: Into<String> {
+    Binding { address: address.into(), param: param.into(), smoothing: Smoothing::None }
+  }
+
+  /// Smooth the changes of the parameter.
+  pub fn smoothing(self, smoothing: Smoothing) -> Self {
+    Binding { smoothing, ..self }
+  }
+}
+
+/// A smoothed parameter value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Smoothed {
+  current: f32,
+  target: f32,
+  /// Speed of linear smoothing, in units per second.
+  rate: f32,
+  smoothing: Smoothing
+}
+
+impl Smoothed {
+  fn new(value: f32, smoothing: Smoothing) -> Self {
+    Smoothed { current: value, target: value, rate: 0., smoothing }
+  }
+
+  fn set(&mut self, target: f32) {
+    self.target = target;
+
+    if let Smoothing::Linear(duration) = self.smoothing {
+      self.rate = (target - self.current).abs() / duration.as_secs().max(1e-6) as f32;
+    }
+  }
+
+  fn advance(&mut self, dt: Time) {
+    let dt = dt.as_secs() as f32;
+    let delta = self.target - self.current;
+
+    self.current = match self.smoothing {
+      Smoothing::None => self.target,
+
+      Smoothing::Exponential(tau) if tau.as_secs() > 0. => {
+        self.current + delta * (1. - (-dt / tau.as_secs() as f32).exp())
+      }
+
+      Smoothing::Linear(_) if delta.abs() > self.rate * dt => self.current + self.rate * dt * delta.signum(),
+
+      _ => self.target
+    };
+  }
+}
+
+/// Routes OSC messages to parameters.
+#[derive(Clone, Debug)]
+pub struct Router {
+  prefix: Option<String>,
+  smoothing: Smoothing,
+  bindings: Vec<Binding>,
+  values: BTreeMap<String, Smoothed>
+}
+
+impl Default for Router {
+  fn default() -> Self {
+    Router {
+      prefix: Some(DEFAULT_PREFIX.to_owned()),
+      smoothing: Smoothing::None,
+      bindings: Vec::new(),
+      values: BTreeMap::new()
+    }
+  }
+}
+
+impl Router {
+  /// Create a router with the default prefix and no smoothing.
+  pub fn new() -> Self {
+    Router::default()
+  }
+
+  /// Route the messages under a prefix to the parameters named after the rest of their addresses;
+  /// `None` only routes the bound addresses.
+  pub fn prefix<P>(self, prefix: Option<P>) -> Self where P: Into<String> {
+    Router { prefix: prefix.map(Into::into), ..self }
+  }
+
+  /// Smoothing of the parameters set under the prefix.
+  pub fn smoothing(self, smoothing: Smoothing) -> Self {
+    Router { smoothing, ..self }
+  }
+
+  /// Add a binding.
+  pub fn bind(mut self, binding: Binding) -> Self {
+    self.bindings.push(binding);
+    self
+  }
+
+  /// Route all the messages of a packet.
+  pub fn route_packet(&mut self, packet: &Packet) {
+    for msg in packet.messages() {
+      self.route(msg);
+    }
+  }
+
+  /// Route a message; returns the number of parameters it targets.
+  pub fn route(&mut self, msg: &Message) -> usize {
+    let value = match msg.value() {
+      Some(value) => value,
+      None => return 0
+    };
+
+    let mut targets: Vec<(String, Smoothing)> = self.bindings
+      .iter()
+      .filter(|binding| matches(&msg.address, &binding.address) || matches(&binding.address, &msg.address))
+      .map(|binding| (binding.param.clone(), binding.smoothing))
+      .collect();
+
+    if let Some(ref prefix) = self.prefix {
+      if let Some(name) = msg.address.strip_prefix(prefix.as_str()) {
+        if !is_pattern(name) && !name.is_empty() && !name.contains('/') {
+          targets.push((name.to_owned(), self.smoothing));
+        } else {
+          let bound: Vec<_> = self.bindings.iter().map(|binding| binding.param.as_str()).collect();
+          let known = self.values.keys().filter(|name| !bound.contains(&name.as_str()));
+
+          for name in known {
+            if matches(&msg.address, &format!("{}{}", prefix, name)) {
+              targets.push((name.clone(), self.smoothing));
+            }
+          }
+        }
+      }
+    }
+
+    for &(ref name, smoothing) in &targets {
+      self.values
+        .entry(name.clone())
+        .or_insert_with(|| Smoothed::new(value, smoothing))
+        .set(value);
+    }
+
+    targets.len()
+  }
+
+  /// Move the values towards their targets by some time, and write them to parameters.
+  pub fn advance(&mut self, dt: Time, params: &mut Params) {
+    for (name, value) in &mut self.values {
+      value.advance(dt);
+      params.set(name.as_str(), value.current);
+    }
+  }
+}
+
+/// OSC listener.
+///
+/// Packets are received and decoded in a background thread; get them with [`Listener::poll`].
+#[derive(Debug)]
+pub struct Listener {
+  address: SocketAddr,
+  packets: Receiver<Result<Packet, Error>>,
+  closed: Arc<AtomicBool>
+}
+
+impl Listener {
+  /// Listen on a UDP address; the port can be 0 to pick any free port.
+  pub fn bind(address: &str) -> io::Result<Self> {
+    let socket = UdpSocket::bind(address)?;
+    let address = socket.local_addr()?;
+    let (sender, packets) = mpsc::channel();
+    let closed = Arc::new(AtomicBool::new(false));
+
+    // wake up regularly to notice when the listener is dropped
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    let thread_closed = closed.clone();
+    thread::spawn(move || {
+      let mut buffer = vec![0; MAX_PACKET_SIZE];
+
+      while !thread_closed.load(Ordering::Relaxed) {
+        match socket.recv(&mut buffer) {
+          Ok(size) => {
+            if sender.send(Packet::decode(&buffer[..size])).is_err() {
+              return;
+            }
+          }
+
+          Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => (),
+          Err(_) => return
+        }
+      }
+    });
+
+    Ok(Listener { address, packets, closed })
+  }
+
+  /// Address the listener is bound to.
+  pub fn address(&self) -> SocketAddr {
+    self.address
+  }
+
+  /// Get the packets received since the last call, or the reason why they couldn’t be decoded.
+  pub fn poll(&self) -> Vec<Result<Packet, Error>> {
+    self.packets.try_iter().collect()
+  }
+}
+
+impl Drop for Listener {
+  fn drop(&mut self) {
+    self.closed.store(true, Ordering::Relaxed);
+  }
+}
+
+/// Read elements of a packet.
+struct Reader<'a> {
+  bytes: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+  fn new(bytes: &'a [u8]) -> Self {
+    Reader { bytes }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.bytes.is_empty()
+  }
+
+  fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+    if n > self.bytes.len() {
+      return Err(Error::Truncated);
+    }
+
+    let (taken, rest) = self.bytes.split_at(n);
+    self.bytes = rest;
+    Ok(taken)
+  }
+
+  fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+    let mut array = [0; N];
+    array.copy_from_slice(self.take(N)?);
+    Ok(array)
+  }
+
+  fn i32(&mut self) -> Result<i32, Error> {
+    self.array().map(i32::from_be_bytes)
+  }
+
+  fn u64(&mut self) -> Result<u64, Error> {
+    self.array().map(u64::from_be_bytes)
+  }
+
+  fn string(&mut self) -> Result<String, Error> {
+    let len = self.bytes.iter().position(|&b| b == 0).ok_or(Error::InvalidString)?;
+    let bytes = self.take(padded(len + 1))?;
+
+    String::from_utf8(bytes[..len].to_vec()).map_err(|_| Error::InvalidString)
+  }
+
+  fn blob(&mut self) -> Result<Vec<u8>, Error> {
+    let len = self.i32()?;
+
+    if len < 0 {
+      return Err(Error::Truncated);
+    }
+
+    let bytes = self.take(padded(len as usize))?;
+    Ok(bytes[..len as usize].to_vec())
+  }
+
+  /// Read the arguments described by some type tags, up to the end of the array if `depth` arrays
+  /// deep.
+  fn args<I>(&mut self, tags: &mut I, depth: usize) -> Result<Vec<Arg>, Error> where I: Iterator<Item = char> {
+    let mut args = Vec::new();
+
+    while let Some(tag) = tags.next() {
+      let arg = match tag {
+        'i' => Arg::Int(self.i32()?),
+        'f' => Arg::Float(f32::from_bits(self.i32()? as u32)),
+        's' => Arg::String(self.string()?),
+        'b' => Arg::Blob(self.blob()?),
+        'h' => Arg::Long(self.u64()? as i64),
+        'd' => Arg::Double(f64::from_bits(self.u64()?)),
+        't' => Arg::TimeTag(self.u64()?),
+        'S' => Arg::Symbol(self.string()?),
+        'c' => Arg::Char(std::char::from_u32(self.i32()? as u32).ok_or(Error::InvalidString)?),
+        'r' => Arg::Color(self.array()?),
+        'm' => Arg::Midi(self.array()?),
+        'T' => Arg::Bool(true),
+        'F' => Arg::Bool(false),
+        'N' => Arg::Nil,
+        'I' => Arg::Impulse,
+        '[' if depth == MAX_NESTING => return Err(Error::TooDeep),
+        '[' => Arg::Array(self.args(tags, depth + 1)?),
+        ']' if depth > 0 => return Ok(args),
+        ']' => return Err(Error::UnbalancedArray),
+        _ => return Err(Error::UnknownTag(tag))
+      };
+
+      args.push(arg);
+    }
+
+    if depth > 0 {
+      Err(Error::UnbalancedArray)
+    } else {
+      Ok(args)
+    }
+  }
+}
+
+/// Size of an element padded to a multiple of 4.
+fn padded(len: usize) -> usize {
+  (len + 3) & !3
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+  bytes.resize(padded(bytes.len()), 0);
+}
+
+/// Write a null-terminated, padded string.
+fn write_string(bytes: &mut Vec<u8>, s: &str) {
+  bytes.extend(s.as_bytes());
+  bytes.push(0);
+  pad(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+
+  /// “/oscillator/4/frequency” with the float 440, from the OSC 1.0 specification.
+  const FREQUENCY: &[u8] = b"/oscillator/4/frequency\0,f\0\0\x43\xdc\0\0";
+
+  fn secs(t: f64) -> Time {
+    Time::from_secs(t)
+  }
+
+  #[test]
+  fn decode_message() {
+    let msg = Message::new("/oscillator/4/frequency", vec![Arg::Float(440.)]);
+    assert_eq!(Packet::decode(FREQUENCY), Ok(Packet::Message(msg)));
+
+    // “/foo” with 1000, -1, “hello”, 1.234 and 5.678, from the OSC 1.0 specification
+    let bytes = b"/foo\0\0\0\0,iisff\0\0\0\0\x03\xe8\xff\xff\xff\xffhello\0\0\0\x3f\x9d\xf3\xb6\x40\xb5\xb2\x2d";
+    let msg = Message::new("/foo", vec![
+      Arg::Int(1000),
+      Arg::Int(-1),
+      Arg::String("hello".to_owned()),
+      Arg::Float(1.234),
+      Arg::Float(5.678)
+    ]);
+
+    assert_eq!(Packet::decode(bytes), Ok(Packet::Message(msg)));
+    assert_eq!(Packet::decode(b"/foo\0\0\0\0"), Ok(Packet::Message(Message::new("/foo", Vec::new()))));
+  }
+
+  #[test]
+  fn decode_errors() {
+    assert_eq!(Packet::decode(b""), Err(Error::UnknownPacket));
+    assert_eq!(Packet::decode(b"foo\0"), Err(Error::UnknownPacket));
+    assert_eq!(Packet::decode(b"/foo\0"), Err(Error::Misaligned));
+    assert_eq!(Packet::decode(b"/foo"), Err(Error::InvalidString));
+    assert_eq!(Packet::decode(b"/foo\0\0\0\0,i\0\0"), Err(Error::Truncated));
+    assert_eq!(Packet::decode(b"/foo\0\0\0\0,x\0\0"), Err(Error::UnknownTag('x')));
+    assert_eq!(Packet::decode(b"/foo\0\0\0\0,[i\0\0\0\0\0"), Err(Error::UnbalancedArray));
+    assert_eq!(Packet::decode(b"/foo\0\0\0\0,]\0\0"), Err(Error::UnbalancedArray));
+    assert_eq!(Packet::decode(b"#bundle\0\0\0\0\0\0\0\0\x01\0\0\0\x0c/foo\0\0\0\0"), Err(Error::Truncated));
+  }
+
+  #[test]
+  fn nesting_limit() {
+    // a whole datagram of opening arrays
+    let mut deep = Vec::new();
+    write_string(&mut deep, "/foo");
+    write_string(&mut deep, &format!(",{}", "[".repeat(60_000)));
+    assert_eq!(Packet::decode(&deep), Err(Error::TooDeep));
+
+    let mut array = Arg::Nil;
+    let mut bundle = Packet::Message(Message::new("/foo", Vec::new()));
+
+    for _ in 0..MAX_NESTING {
+      array = Arg::Array(vec![array]);
+      bundle = Packet::Bundle(Bundle::new(IMMEDIATELY, vec![bundle]));
+    }
+
+    let msg = Packet::Message(Message::new("/foo", vec![array.clone()]));
+    assert_eq!(Packet::decode(&msg.encode()), Ok(msg));
+    assert_eq!(Packet::decode(&bundle.encode()), Ok(bundle.clone()));
+
+    let msg = Packet::Message(Message::new("/foo", vec![Arg::Array(vec![array])]));
+    let bundle = Packet::Bundle(Bundle::new(IMMEDIATELY, vec![bundle]));
+    assert_eq!(Packet::decode(&msg.encode()), Err(Error::TooDeep));
+    assert_eq!(Packet::decode(&bundle.encode()), Err(Error::TooDeep));
+  }
+
+  #[test]
+  fn round_trip() {
+    let msg = Message::new("/all/the/types", vec![
+      Arg::Int(-42),
+      Arg::Float(0.5),
+      Arg::String("four".to_owned()),
+      Arg::Blob(vec![1, 2, 3, 4, 5]),
+      Arg::Long(-1 << 40),
+      Arg::Double(0.1),
+      Arg::TimeTag(IMMEDIATELY),
+      Arg::Symbol("sym".to_owned()),
+      Arg::Char('λ'),
+      Arg::Color([255, 128, 0, 255]),
+      Arg::Midi([0, 0x90, 60, 127]),
+      Arg::Array(vec![Arg::Bool(true), Arg::Nil, Arg::Array(vec![Arg::Impulse])]),
+      Arg::Bool(false)
+    ]);
+
+    let bundle = Packet::Bundle(Bundle::new(IMMEDIATELY, vec![
+      Packet::Message(msg.clone()),
+      Packet::Bundle(Bundle::new(42, vec![Packet::Message(Message::new("/empty", Vec::new()))]))
+    ]));
+
+    let encoded = bundle.encode();
+    assert_eq!(encoded.len() % 4, 0);
+    assert_eq!(Packet::decode(&encoded), Ok(bundle.clone()));
+    assert_eq!(Packet::Message(Message::new("/oscillator/4/frequency", vec![Arg::Float(440.)])).encode(), FREQUENCY);
+
+    let addresses: Vec<_> = bundle.messages().iter().map(|msg| msg.address.as_str()).collect();
+    assert_eq!(addresses, vec!["/all/the/types", "/empty"]);
+  }
+
+  #[test]
+  fn values() {
+    assert_eq!(Message::new("/a", vec![Arg::String("x".to_owned()), Arg::Int(3)]).value(), Some(3.));
+    assert_eq!(Message::new("/a", vec![Arg::Double(0.25)]).value(), Some(0.25));
+    assert_eq!(Message::new("/a", vec![Arg::Bool(true)]).value(), Some(1.));
+    assert_eq!(Message::new("/a", vec![Arg::Nil]).value(), None);
+  }
+
+  #[test]
+  fn patterns() {
+    assert!(matches("/spectra/param/glow", "/spectra/param/glow"));
+    assert!(!matches("/spectra/param/glow", "/spectra/param/glo"));
+    assert!(!matches("/spectra/param/glo", "/spectra/param/glow"));
+
+    assert!(matches("/spectra/param/gl?w", "/spectra/param/glow"));
+    assert!(!matches("/spectra/param?glow", "/spectra/param/glow"));
+
+    assert!(matches("/spectra/param/*", "/spectra/param/glow"));
+    assert!(matches("/spectra/*/glow", "/spectra/param/glow"));
+    assert!(matches("/spectra/param/g*w", "/spectra/param/gw"));
+    assert!(matches("/*/*/*", "/spectra/param/glow"));
+    assert!(!matches("/spectra/*", "/spectra/param/glow"));
+    assert!(!matches("/spectra/param/*a", "/spectra/param/glow"));
+
+    assert!(matches("/fader/[1-4]", "/fader/3"));
+    assert!(!matches("/fader/[1-4]", "/fader/5"));
+    assert!(matches("/fader/[!1-4]", "/fader/5"));
+    assert!(matches("/fader/[abc-]", "/fader/-"));
+    assert!(!matches("/fader/[1-4", "/fader/1"));
+
+    assert!(matches("/spectra/param/{glow,bloom}", "/spectra/param/bloom"));
+    assert!(matches("/spectra/param/{glow,bloom}/x", "/spectra/param/glow/x"));
+    assert!(!matches("/spectra/param/{glow,bloom}", "/spectra/param/fog"));
+    assert!(matches("/{a,ab}c", "/abc"));
+  }
+
+  #[test]
+  fn pathological_patterns() {
+    let address = format!("/{}", "a".repeat(1000));
+    let start = Instant::now();
+
+    assert!(!matches(&format!("/{}b", "*a".repeat(500)), &address));
+    assert!(!matches(&format!("/{}b", "{a,aa}".repeat(150)), &address));
+    assert!(matches(&format!("/{}", "*".repeat(1000)), &address));
+    assert!(matches("/**a**", &address));
+
+    // too long to be matched
+    assert!(!matches(&format!("/{}", "*".repeat(MAX_PATTERN_LEN)), &address));
+
+    assert!(start.elapsed() < Duration::from_secs(1));
+  }
+
+  #[test]
+  fn prefix_routing() {
+    let mut router = Router::new();
+    let mut params = Params::new();
+
+    assert_eq!(router.route(&Message::new("/spectra/param/glow", vec![Arg::Float(0.5)])), 1);
+    assert_eq!(router.route(&Message::new("/spectra/param/fog", vec![Arg::Int(2)])), 1);
+    assert_eq!(router.route(&Message::new("/spectra/param/bloom", vec![Arg::Nil])), 0);
+    assert_eq!(router.route(&Message::new("/spectra/other", vec![Arg::Float(1.)])), 0);
+    assert_eq!(router.route(&Message::new("/spectra/param/a/b", vec![Arg::Float(1.)])), 0);
+
+    router.advance(secs(0.), &mut params);
+    assert_eq!(params.iter().collect::<Vec<_>>(), vec![("fog", 2.), ("glow", 0.5)]);
+
+    // patterns set the known parameters
+    assert_eq!(router.route(&Message::new("/spectra/param/*", vec![Arg::Float(0.)])), 2);
+    assert_eq!(router.route(&Message::new("/spectra/param/{glow,bloom}", vec![Arg::Float(1.)])), 1);
+
+    router.advance(secs(0.), &mut params);
+    assert_eq!(params.iter().collect::<Vec<_>>(), vec![("fog", 0.), ("glow", 1.)]);
+  }
+
+  #[test]
+  fn bindings() {
+    let mut router = Router::new()
+      .prefix(None::<String>)
+      .bind(Binding::new("/1/fader[1-2]", "exposure"))
+      .bind(Binding::new("/1/fader2", "fov"));
+    let mut params = Params::new();
+
+    assert_eq!(router.route(&Message::new("/1/fader1", vec![Arg::Float(0.25)])), 1);
+    assert_eq!(router.route(&Message::new("/1/fader2", vec![Arg::Float(0.75)])), 2);
+    assert_eq!(router.route(&Message::new("/spectra/param/glow", vec![Arg::Float(1.)])), 0);
+
+    router.advance(secs(0.), &mut params);
+    assert_eq!(params.iter().collect::<Vec<_>>(), vec![("exposure", 0.75), ("fov", 0.75)]);
+  }
+
+  #[test]
+  fn smoothing() {
+    let mut router = Router::new()
+      .smoothing(Smoothing::Linear(secs(1.)))
+      .bind(Binding::new("/exp", "exp").smoothing(Smoothing::Exponential(secs(0.5))));
+    let mut params = Params::new();
+
+    // first values apply immediately
+    router.route(&Message::new("/spectra/param/lin", vec![Arg::Float(0.)]));
+    router.route(&Message::new("/exp", vec![Arg::Float(0.)]));
+    router.advance(secs(0.1), &mut params);
+    assert_eq!((params.get("lin"), params.get("exp")), (Some(0.), Some(0.)));
+
+    router.route(&Message::new("/spectra/param/lin", vec![Arg::Float(2.)]));
+    router.route(&Message::new("/exp", vec![Arg::Float(1.)]));
+
+    router.advance(secs(0.25), &mut params);
+    assert!((params.get("lin").unwrap() - 0.5).abs() < 1e-6);
+    assert!((params.get("exp").unwrap() - (1. - (-0.5f32).exp())).abs() < 1e-6);
+
+    router.advance(secs(0.5), &mut params);
+    assert!((params.get("lin").unwrap() - 1.5).abs() < 1e-6);
+    assert!((params.get("exp").unwrap() - (1. - (-1.5f32).exp())).abs() < 1e-6);
+
+    // linear smoothing stops at the target
+    router.advance(secs(1.), &mut params);
+    assert_eq!(params.get("lin"), Some(2.));
+
+    router.advance(secs(10.), &mut params);
+    assert!((params.get("exp").unwrap() - 1.).abs() < 1e-6);
+  }
+
+  #[test]
+  fn udp_loopback() {
+    let listener = Listener::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let bundle = Packet::Bundle(Bundle::new(IMMEDIATELY, vec![
+      Packet::Message(Message::new("/spectra/param/glow", vec![Arg::Float(0.5)])),
+      Packet::Message(Message::new("/spectra/param/fog", vec![Arg::Int(3)]))
+    ]));
+
+    socket.send_to(&bundle.encode(), listener.address()).unwrap();
+    socket.send_to(b"garbage", listener.address()).unwrap();
+
+    let mut received = Vec::new();
+    let start = Instant::now();
+
+    while received.len() < 2 && start.elapsed() < Duration::from_secs(5) {
+      received.extend(listener.poll());
+      thread::yield_now();
+    }
+
+    assert_eq!(received, vec![Ok(bundle.clone()), Err(Error::Misaligned)]);
+
+    let mut router = Router::new();
+    let mut params = Params::new();
+    router.route_packet(&bundle);
+    router.advance(secs(0.), &mut params);
+
+    assert_eq!(params.iter().collect::<Vec<_>>(), vec![("fog", 3.), ("glow", 0.5)]);
+  }
+}
@@ -0,0 +1,356 @@
+//! Frame pacing.
+//!
+//! A [`FrameLimiter`] holds the main loop back so that frames are issued at a target frame rate.
+//! Sleeping is cheap but coarse – the OS scheduler might wake us up late – so the limiter sleeps
+//! until shortly before the deadline and spins for the remaining time.
+
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+use crate::time::{Monotonic, Time};
+
+/// Vertical synchronization mode.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Vsync {
+  /// Wait for the vertical blank before swapping buffers.
+  On,
+  /// Swap buffers as soon as possible.
+  Off,
+  /// Wait for the vertical blank, unless the frame is late.
+  Adaptive
+}
+
+impl fmt::Display for Vsync {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Vsync::On => f.write_str("on"),
+      Vsync::Off => f.write_str("off"),
+      Vsync::Adaptive => f.write_str("adaptive")
+    }
+  }
+}
+
+impl FromStr for Vsync {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "on" => Ok(Vsync::On),
+      "off" => Ok(Vsync::Off),
+      "adaptive" => Ok(Vsync::Adaptive),
+      _ => Err(format!("invalid vsync mode “{}”; expected on, off or adaptive", s))
+    }
+  }
+}
+
+/// Source of time the frame limiter waits with.
+pub trait Timer {
+  /// Current time.
+  fn now(&mut self) -> Time;
+
+  /// Sleep for a given amount of time – or a bit more.
+  fn sleep(&mut self, t: Time);
+}
+
+/// Timer following the real time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RealTimer {
+  start: Monotonic
+}
+
+impl RealTimer {
+  pub fn new() -> Self {
+    RealTimer { start: Monotonic::now() }
+  }
+}
+
+impl Default for RealTimer {
+  fn default() -> Self {
+    RealTimer::new()
+  }
+}
+
+impl Timer for RealTimer {
+  fn now(&mut self) -> Time {
+    self.start.elapsed_secs()
+  }
+
+  fn sleep(&mut self, t: Time) {
+    if let Some(d) = t.to_duration() {
+      thread::sleep(d);
+    }
+  }
+}
+
+/// Frame pacing statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PacingStats {
+  frames: u64,
+  late_frames: u64,
+  total_error: Time,
+  max_error: Time,
+  slept: Time,
+  spun: Time
+}
+
+impl PacingStats {
+  /// Number of paced frames.
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  /// Number of frames released after the deadline plus the tolerance.
+  pub fn late_frames(&self) -> u64 {
+    self.late_frames
+  }
+
+  /// Average difference between the deadlines and the actual release times.
+  pub fn avg_error(&self) -> Time {
+    if self.frames == 0 {
+      Time::default()
+    } else {
+      self.total_error / self.frames as f64
+    }
+  }
+
+  /// Maximum difference between a deadline and the actual release time.
+  pub fn max_error(&self) -> Time {
+    self.max_error
+  }
+
+  /// Time spent sleeping.
+  pub fn slept(&self) -> Time {
+    self.slept
+  }
+
+  /// Time spent spinning.
+  pub fn spun(&self) -> Time {
+    self.spun
+  }
+}
+
+impl fmt::Display for PacingStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(
+      f,
+      "{} frames ({} late), error avg {:.3}ms max {:.3}ms, slept {:.1}ms, spun {:.1}ms",
+      self.frames,
+      self.late_frames,
+      self.avg_error().as_millis(),
+      self.max_error.as_millis(),
+      self.slept.as_millis(),
+      self.spun.as_millis()
+    )
+  }
+}
+
+/// Frame rate limiter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameLimiter {
+  period: Time,
+  spin: Time,
+  tolerance: Time,
+  deadline: Option<Time>,
+  stats: PacingStats
+}
+
+impl FrameLimiter {
+  /// Default time spent spinning before a deadline.
+  pub const DEFAULT_SPIN: f64 = 2e-3;
+
+  /// Default lateness after which a frame is considered late.
+  pub const DEFAULT_TOLERANCE: f64 = 1e-3;
+
+  /// Create a limiter releasing `fps` frames per second.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `fps` is 0.
+  pub fn new(fps: u32) -> Self {
+    assert!(fps > 0, "a frame limiter needs a non-zero frame rate");
+
+    FrameLimiter {
+      period: Time::from_secs(1. / fps as f64),
+      spin: Time::from_secs(Self::DEFAULT_SPIN),
+      tolerance: Time::from_secs(Self::DEFAULT_TOLERANCE),
+      deadline: None,
+      stats: PacingStats::default()
+    }
+  }
+
+  /// Change the time spent spinning before a deadline.
+  pub fn spin(self, spin: Time) -> Self {
+    FrameLimiter { spin, ..self }
+  }
+
+  /// Change the lateness after which a frame is considered late.
+  pub fn tolerance(self, tolerance: Time) -> Self {
+    FrameLimiter { tolerance, ..self }
+  }
+
+  /// Duration of a frame.
+  pub fn period(&self) -> Time {
+    self.period
+  }
+
+  /// Statistics gathered so far.
+  pub fn stats(&self) -> &PacingStats {
+    &self.stats
+  }
+
+  /// Reset the statistics.
+  pub fn reset_stats(&mut self) {
+    self.stats = PacingStats::default();
+  }
+
+  /// Wait until the next frame is due.
+  ///
+  /// The first call only sets the first deadline up. If a deadline is missed by more than a frame,
+  /// the limiter doesn’t try to catch up and schedules the next frame one period from now.
+  pub fn wait<T>(&mut self, timer: &mut T) where T: Timer {
+    let now = timer.now();
+
+    let deadline = match self.deadline {
+      Some(deadline) => deadline,
+      None => {
+        self.deadline = Some(now + self.period);
+        return;
+      }
+    };
+
+    // sleep until shortly before the deadline
+    let remaining = deadline - now;
+
+    if remaining > self.spin {
+      timer.sleep(remaining - self.spin);
+    }
+
+    // spin for the remaining time
+    let spin_start = timer.now();
+    self.stats.slept += spin_start - now;
+
+    let mut released = spin_start;
+    while released < deadline {
+      released = timer.now();
+    }
+
+    self.stats.spun += released - spin_start;
+
+    // account for the frame
+    let error = released - deadline;
+
+    self.stats.frames += 1;
+    self.stats.total_error += error;
+    self.stats.max_error = if error > self.stats.max_error { error } else { self.stats.max_error };
+
+    if error > self.tolerance {
+      self.stats.late_frames += 1;
+    }
+
+    self.deadline = Some(if error > self.period { released + self.period } else { deadline + self.period });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Fake timer whose sleeps overshoot and whose reads take time.
+  struct FakeTimer {
+    t: Time,
+    overshoot: Time,
+    read_cost: Time
+  }
+
+  impl FakeTimer {
+    fn new(overshoot_ms: f64, read_cost_ms: f64) -> Self {
+      FakeTimer {
+        t: Time::default(),
+        overshoot: Time::from_millis(overshoot_ms),
+        read_cost: Time::from_millis(read_cost_ms)
+      }
+    }
+
+    /// Simulate some work.
+    fn work(&mut self, ms: f64) {
+      self.t += Time::from_millis(ms);
+    }
+  }
+
+  impl Timer for FakeTimer {
+    fn now(&mut self) -> Time {
+      self.t += self.read_cost;
+      self.t
+    }
+
+    fn sleep(&mut self, t: Time) {
+      self.t += t + self.overshoot;
+    }
+  }
+
+  #[test]
+  fn parse_vsync() {
+    assert_eq!("on".parse(), Ok(Vsync::On));
+    assert_eq!("off".parse(), Ok(Vsync::Off));
+    assert_eq!("adaptive".parse(), Ok(Vsync::Adaptive));
+    assert!("maybe".parse::<Vsync>().is_err());
+    assert_eq!(Vsync::Adaptive.to_string(), "adaptive");
+  }
+
+  #[test]
+  fn hits_target_within_tolerance() {
+    let mut timer = FakeTimer::new(1., 0.01);
+    let mut limiter = FrameLimiter::new(60);
+
+    limiter.wait(&mut timer);
+    let start = timer.t;
+
+    for _ in 0..600 {
+      timer.work(5.);
+      limiter.wait(&mut timer);
+    }
+
+    let elapsed = (timer.t - start).as_secs();
+    let stats = *limiter.stats();
+
+    assert!((elapsed - 10.).abs() < 1e-3, "elapsed {}", elapsed);
+    assert_eq!(stats.frames(), 600);
+    assert_eq!(stats.late_frames(), 0);
+    assert!(stats.max_error() < Time::from_millis(0.02));
+    assert!(stats.slept() > stats.spun());
+  }
+
+  #[test]
+  fn spins_when_sleeping_is_too_coarse() {
+    let mut timer = FakeTimer::new(0., 0.01);
+    let mut limiter = FrameLimiter::new(100).spin(Time::from_millis(20.));
+
+    limiter.wait(&mut timer);
+    timer.work(1.);
+    limiter.wait(&mut timer);
+
+    assert!(limiter.stats().slept() < Time::from_millis(0.02));
+    assert!(limiter.stats().spun() > Time::from_millis(8.9));
+  }
+
+  #[test]
+  fn late_frames_dont_catch_up() {
+    let mut timer = FakeTimer::new(0., 0.01);
+    let mut limiter = FrameLimiter::new(100);
+
+    limiter.wait(&mut timer);
+    timer.work(50.);
+    limiter.wait(&mut timer);
+
+    assert_eq!(limiter.stats().late_frames(), 1);
+
+    // the next frame is paced normally instead of being released right away
+    let before = timer.t;
+    timer.work(1.);
+    limiter.wait(&mut timer);
+
+    assert!((timer.t - before).as_millis() > 9.9);
+    assert_eq!(limiter.stats().late_frames(), 1);
+  }
+}
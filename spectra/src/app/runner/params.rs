@@ -0,0 +1,59 @@
+//! Named parameters.
+//!
+//! Parameters are values set from outside of a demo while it runs – for instance through the
+//! [remote control](crate::app::runner::remote) of the debug runner or [OSC](crate::app::runner::osc)
+//! messages – and read by the demo through
+//! [`Runner::params`](crate::app::runner::Runner::params), typically to tweak it live.
+
+use std::collections::BTreeMap;
+
+/// A set of named parameters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+  values: BTreeMap<String, f32>
+}
+
+impl Params {
+  pub fn new() -> Self {
+    Params::default()
+  }
+
+  /// Value of a parameter, if set.
+  pub fn get(&self, name: &str) -> Option<f32> {
+    self.values.get(name).cloned()
+  }
+
+  /// Value of a parameter, or a default value if it’s not set.
+  pub fn get_or(&self, name: &str, default: f32) -> f32 {
+    self.get(name).unwrap_or(default)
+  }
+
+  /// Set the value of a parameter.
+  pub fn set<N>(&mut self, name: N, value: f32) where N: Into<String> {
+    self.values.insert(name.into(), value);
+  }
+
+  /// All the parameters, sorted by name.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+    self.values.iter().map(|(name, &value)| (name.as_str(), value))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn params() {
+    let mut params = Params::new();
+    assert_eq!(params.get("fov"), None);
+    assert_eq!(params.get_or("fov", 90.), 90.);
+
+    params.set("fov", 60.);
+    params.set("exposure", 1.5);
+    params.set("fov", 75.);
+
+    assert_eq!(params.get("fov"), Some(75.));
+    assert_eq!(params.iter().collect::<Vec<_>>(), vec![("exposure", 1.5), ("fov", 75.)]);
+  }
+}
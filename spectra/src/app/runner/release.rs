@@ -0,0 +1,436 @@
+//! The release runner.
+//!
+//! This runner plays a demo once, from 0 to a given end time, and then exits. It doesn’t parse any
+//! command line option: everything is set through a [`Config`] and, optionally, a setup prompt
+//! letting the user pick a resolution and whether to run in fullscreen.
+
+use luminance_glfw::surface::{WindowDim, WindowOpt};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use warmy::{Store, StoreOpt};
+
+use crate::app::demo::Demo;
+use crate::app::event::{Action, Event, Key as EventKey};
+use crate::app::runner::{self, ClockState, FrameStats, Params, RunnerState};
+use crate::app::runner::surface::{self, GlfwWindow, Surface};
+use crate::app::runner::update::Stepper;
+use crate::logger::Logger;
+use crate::resource::key::Key;
+use crate::resource::timing::{self, SLOWEST_COUNT};
+use crate::time::Time;
+use crate::time::clock::Clock;
+use crate::time::marker::CueCursor;
+
+/// Resolutions offered by the setup prompt.
+const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1920, 1080), (2560, 1440), (3840, 2160)];
+
+/// Release runner.
+///
+/// This runner shall be used for the final executable of a demo.
+pub struct Runner {
+  state: RunnerState,
+  /// Whether the demo was told that captures are not supported.
+  capture_warned: bool
+}
+
+impl runner::Runner for Runner {
+  fn window_size(&self) -> [u32; 2] {
+    self.state.size
+  }
+
+  fn frame_stats(&self) -> &FrameStats {
+    &self.state.stats
+  }
+
+  fn clock(&self) -> &ClockState {
+    &self.state.clock
+  }
+
+  fn request_exit(&mut self) {
+    self.state.exit_requested = true;
+  }
+
+  fn request_screenshot(&mut self) {
+    self.request_capture(1);
+  }
+
+  fn request_capture(&mut self, _: u32) {
+    // captures are a debugging feature
+    if !self.capture_warned {
+      warn!(self.state.logger, "captures are not supported by the release runner; ignoring the requests");
+      self.capture_warned = true;
+    }
+  }
+
+  fn logger(&mut self) -> &mut dyn Logger {
+    &mut self.state.logger
+  }
+
+  fn params(&self) -> &Params {
+    &self.state.params
+  }
+}
+
+/// Release runner configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+  title: String,
+  width: u32,
+  height: u32,
+  fullscreen: bool,
+  end_at: Time,
+  setup_prompt: bool,
+  store_root: PathBuf
+}
+
+impl Config {
+  /// Create a configuration playing a demo windowed, with no setup prompt, until `end_at`.
+  pub fn new<T>(title: T, width: u32, height: u32, end_at: Time) -> Self where T: Into<String> {
+    Config {
+      title: title.into(),
+      width,
+      height,
+      fullscreen: false,
+      end_at,
+      setup_prompt: false,
+      store_root: PathBuf::from("data")
+    }
+  }
+
+  /// Run in fullscreen.
+  pub fn fullscreen(self, fullscreen: bool) -> Self {
+    Config { fullscreen, ..self }
+  }
+
+  /// Ask the user for the resolution and fullscreen mode on the standard input before starting.
+  pub fn setup_prompt(self, setup_prompt: bool) -> Self {
+    Config { setup_prompt, ..self }
+  }
+
+  /// Change the directory resources are loaded from. Defaults to `data`.
+  pub fn store_root<P>(self, store_root: P) -> Self where P: Into<PathBuf> {
+    Config { store_root: store_root.into(), ..self }
+  }
+}
+
+impl Runner {
+  /// Run a demo.
+  ///
+  /// The demo is initialized – loading all of its resources – before `clock` starts ticking. The
+  /// demo then plays until the clock reaches the end time of the configuration or
+  /// [finishes](Clock::is_finished), or until the user quits with escape.
+  ///
+  /// Any error is fatal: it’s logged in a box through the context before being returned.
+  pub fn run<D, C>(
+    config: Config,
+    clock: C,
+    context: &mut D::Context
+  ) -> Result<(), runner::Error>
+  where D: Demo<Self>,
+        C: Clock {
+    let result = Self::run_demo::<D, C>(config, clock, context);
+
+    if let Err(ref e) = result {
+      error!(context, "\n{}", (error_box(&e.to_string())));
+    }
+
+    result
+  }
+
+  fn run_demo<D, C>(
+    mut config: Config,
+    mut clock: C,
+    context: &mut D::Context
+  ) -> Result<(), runner::Error>
+  where D: Demo<Self>,
+        C: Clock {
+    info!(context, "starting « {} »", (config.title));
+
+    if config.setup_prompt {
+      let stdin = io::stdin();
+      let stdout = io::stdout();
+
+      config = setup_prompt(&mut stdin.lock(), &mut stdout.lock(), config)
+        .map_err(|e| runner::Error::setup_failure(format!("{}", e)))?;
+    }
+
+    let win_dim = if config.fullscreen {
+      info!(context, "window mode: fullscreen restricted ({}×{})", (config.width), (config.height));
+      WindowDim::FullscreenRestricted(config.width, config.height)
+    } else {
+      info!(context, "window mode: windowed ({}×{})", (config.width), (config.height));
+      WindowDim::Windowed(config.width, config.height)
+    };
+
+    let win_opt = WindowOpt::default().hide_cursor(true);
+
+    // create the rendering surface
+    let mut surface = GlfwWindow::new(win_dim, &config.title, win_opt)?;
+
+    // create the store
+    let store_opt = StoreOpt::default().set_root(&config.store_root);
+    let mut store: Store<D::Context, Key> =
+      Store::new(store_opt)
+        .map_err(|e| runner::Error::cannot_create_store(format!("{}", e)))?;
+
+    let mut runner = Runner { state: RunnerState::new(surface.size()), capture_warned: false };
+
+    // initialize the demo, which preloads all of its resources
+    timing::start_recording();
+    let init = D::init(&mut runner, &mut store, context);
+    runner.state.logger.flush(context);
+    timing::log_slowest(context, SLOWEST_COUNT);
+    let mut demo =
+      init.map_err(|e| runner::Error::demo_initialization_failure(format!("{:?}", e)))?;
+
+    surface.initialized();
+
+    let markers = demo.markers();
+    let mut cue_cursor = CueCursor::new();
+    let mut stepper = Stepper::new(demo.update_rate());
+    let end_at = config.end_at;
+
+    info!(context, "initialized; running until {}…", end_at);
+
+    'run: loop {
+      for event in surface.poll_events() {
+        let consumed = demo.event(&mut runner, context, &event);
+
+        match event {
+          Event::Key(EventKey::Escape, Action::Release) if consumed => (),
+
+          Event::Close | Event::Key(EventKey::Escape, Action::Release) => {
+            info!(context, "interrupted");
+            break 'run;
+          }
+
+          Event::Resize(w, h) => {
+            let size = [w, h];
+
+            surface.resize(size);
+            runner.state.size = size;
+            demo.resize(&mut runner, context, w, h);
+          }
+
+          _ => ()
+        }
+      }
+
+      let t = clock.tick();
+
+      // a playback stops advancing once finished, before reaching the end time if it’s too short
+      if t >= end_at || clock.is_finished() {
+        break;
+      }
+
+      runner.state.clock.update(t, false, 1.);
+
+      for cue in cue_cursor.advance(&markers, t) {
+        demo.cue(&mut runner, context, cue, t);
+      }
+
+      let steps = stepper.advance(t);
+      let interpolation = steps.interpolation();
+
+      for (ut, dt) in steps {
+        demo.update(&mut runner, context, ut, dt);
+      }
+
+      let (render, swap, _) = surface::render_timed(&mut surface, t, false, |target| {
+        demo.render(&mut runner, context, t, interpolation, target);
+      });
+      runner.state.end_frame(render, swap);
+      runner.state.logger.flush(context);
+
+      if runner.state.exit_requested {
+        break;
+      }
+    }
+
+    demo.shutdown(&mut runner, context);
+    runner.state.logger.flush(context);
+
+    info!(context, "finished");
+
+    Ok(())
+  }
+}
+
+/// Ask the user for a resolution and the fullscreen mode.
+///
+/// Invalid answers are asked again; empty answers keep the values of the configuration.
+fn setup_prompt<R, W>(input: &mut R, output: &mut W, config: Config) -> io::Result<Config>
+where R: BufRead,
+      W: Write {
+  let mut resolutions = RESOLUTIONS.to_vec();
+  let current = (config.width, config.height);
+
+  if !resolutions.contains(&current) {
+    resolutions.push(current);
+    resolutions.sort();
+  }
+
+  let default = resolutions.iter().position(|&r| r == current).unwrap();
+
+  writeln!(output, "« {} » setup", config.title)?;
+
+  for (i, &(w, h)) in resolutions.iter().enumerate() {
+    writeln!(output, "  {}) {}×{}", i + 1, w, h)?;
+  }
+
+  let choice = ask(input, output, &format!("resolution [{}]: ", default + 1), |answer| {
+    parse_choice(answer, resolutions.len(), default)
+  })?;
+
+  let default_fullscreen = if config.fullscreen { "Y/n" } else { "y/N" };
+  let fullscreen = ask(input, output, &format!("fullscreen? [{}]: ", default_fullscreen), |answer| {
+    parse_yes_no(answer, config.fullscreen)
+  })?;
+
+  let (width, height) = resolutions[choice];
+
+  Ok(Config { width, height, fullscreen, ..config })
+}
+
+/// Ask a question until the answer is valid.
+fn ask<R, W, F, A>(input: &mut R, output: &mut W, question: &str, parse: F) -> io::Result<A>
+where R: BufRead,
+      W: Write,
+      F: Fn(&str) -> Option<A> {
+  loop {
+    write!(output, "{}", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+
+    if input.read_line(&mut answer)? == 0 {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no answer to the setup prompt"));
+    }
+
+    if let Some(a) = parse(answer.trim()) {
+      return Ok(a);
+    }
+  }
+}
+
+/// Parse a 1-based choice among `count` items; an empty answer yields the 0-based `default`.
+fn parse_choice(answer: &str, count: usize, default: usize) -> Option<usize> {
+  if answer.is_empty() {
+    return Some(default);
+  }
+
+  answer.parse::<usize>().ok().filter(|&i| i >= 1 && i <= count).map(|i| i - 1)
+}
+
+/// Parse a yes / no answer; an empty answer yields `default`.
+fn parse_yes_no(answer: &str, default: bool) -> Option<bool> {
+  match answer.to_lowercase().as_str() {
+    "" => Some(default),
+    "y" | "yes" => Some(true),
+    "n" | "no" => Some(false),
+    _ => None
+  }
+}
+
+/// Surround a message with a box, one line of the message per line of the box.
+fn error_box(msg: &str) -> String {
+  let title = "fatal error";
+  let lines: Vec<_> = msg.lines().collect();
+  let width = lines.iter().map(|l| l.chars().count()).chain(Some(title.len())).max().unwrap();
+  let rule = "─".repeat(width + 2);
+  let mut boxed = format!("┌{}┐\n│ {:<w$} │\n├{}┤\n", rule, title, rule, w = width);
+
+  for line in lines {
+    boxed += &format!("│ {:<w$} │\n", line, w = width);
+  }
+
+  boxed + &format!("└{}┘", rule)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use crate::logger::Buffer;
+
+  use super::*;
+
+  #[test]
+  fn choice() {
+    assert_eq!(parse_choice("", 4, 1), Some(1));
+    assert_eq!(parse_choice("1", 4, 1), Some(0));
+    assert_eq!(parse_choice("4", 4, 1), Some(3));
+    assert_eq!(parse_choice("0", 4, 1), None);
+    assert_eq!(parse_choice("5", 4, 1), None);
+    assert_eq!(parse_choice("foo", 4, 1), None);
+  }
+
+  #[test]
+  fn yes_no() {
+    assert_eq!(parse_yes_no("", true), Some(true));
+    assert_eq!(parse_yes_no("Y", false), Some(true));
+    assert_eq!(parse_yes_no("no", true), Some(false));
+    assert_eq!(parse_yes_no("maybe", true), None);
+  }
+
+  #[test]
+  fn prompt() {
+    let config = Config::new("demo", 1920, 1080, Time::from_secs(60.));
+    let mut input = Cursor::new("7\n3\nwhat\ny\n");
+    let mut output = Vec::new();
+    let config = setup_prompt(&mut input, &mut output, config).unwrap();
+
+    assert_eq!((config.width, config.height, config.fullscreen), (2560, 1440, true));
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("  2) 1920×1080\n"));
+    assert!(output.contains("resolution [2]: "));
+    assert!(output.contains("fullscreen? [y/N]: "));
+  }
+
+  #[test]
+  fn prompt_defaults_to_config() {
+    let config = Config::new("demo", 1024, 768, Time::from_secs(60.)).fullscreen(true);
+    let mut input = Cursor::new("\n\n");
+    let mut output = Vec::new();
+
+    assert_eq!(setup_prompt(&mut input, &mut output, config.clone()).unwrap(), config);
+    assert!(String::from_utf8(output).unwrap().contains("  1) 1024×768\n"));
+  }
+
+  #[test]
+  fn prompt_eof() {
+    let config = Config::new("demo", 1920, 1080, Time::from_secs(60.));
+    let mut input = Cursor::new("");
+
+    assert!(setup_prompt(&mut input, &mut Vec::new(), config).is_err());
+  }
+
+  #[test]
+  fn boxed_error() {
+    let expected = "\
+┌───────────────┐
+│ fatal error   │
+├───────────────┤
+│ cannot load   │
+│ data/mesh.obj │
+└───────────────┘";
+
+    assert_eq!(error_box("cannot load\ndata/mesh.obj"), expected);
+  }
+
+  #[test]
+  fn captures_are_ignored() {
+    use crate::app::runner::Runner as _;
+
+    let mut runner = Runner { state: RunnerState::new([4, 2]), capture_warned: false };
+    runner.request_screenshot();
+    runner.request_capture(60);
+
+    let mut expected = Buffer::new();
+    warn!(expected, "captures are not supported by the release runner; ignoring the requests");
+
+    assert!(!runner.state.capture.is_pending());
+    assert_eq!(runner.state.logger, expected);
+  }
+}
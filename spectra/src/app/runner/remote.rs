@@ -0,0 +1,527 @@
+//! Remote control.
+//!
+//! The debug runner can listen on a local socket – TCP, or a Unix socket with a `unix:` address –
+//! so that a running demo can be controlled from another terminal, with the `spectra-remote`
+//! client for instance.
+//!
+//! The protocol is line-based: clients send one command per line and get one JSON object per line
+//! back. The commands are:
+//!
+//!   - `seek <time or marker>`: move the playback position; times use the same syntax as the
+//!     `--start-at` option.
+//!   - `pause` / `play`: pause or resume the playback.
+//!   - `speed <factor>`: set the playback speed – to the closest available one.
+//!   - `reload`: reload the resources that changed on the file system.
+//!   - `set <name> <value>`: set a named [parameter](crate::app::runner::Params).
+//!   - `time`: get the playback state.
+//!   - `stats`: get the frame statistics.
+//!   - `quit`: stop the runner.
+//!
+//! Responses have a `type` field: `ok`, `state` – after `time` and the playback commands –,
+//! `stats`, or `error` along with a `message`.
+
+use serde_derive::Serialize;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::runner::StartAt;
+use crate::app::runner::stats::Summary;
+use crate::time::Time;
+
+/// Address the debug runner listens on when none is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:6660";
+
+/// Prefix of Unix socket addresses.
+const UNIX_PREFIX: &str = "unix:";
+
+/// Interval at which the accepting threads check for new connections and for the server being
+/// dropped.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A command sent by a client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+  Seek(StartAt),
+  Pause,
+  Play,
+  Speed(f64),
+  Reload,
+  Set(String, f32),
+  Time,
+  Stats,
+  Quit
+}
+
+impl fmt::Display for Request {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Request::Seek(ref at) => write!(f, "seek {}", at),
+      Request::Pause => f.write_str("pause"),
+      Request::Play => f.write_str("play"),
+      Request::Speed(speed) => write!(f, "speed {}", speed),
+      Request::Reload => f.write_str("reload"),
+      Request::Set(ref name, value) => write!(f, "set {} {}", name, value),
+      Request::Time => f.write_str("time"),
+      Request::Stats => f.write_str("stats"),
+      Request::Quit => f.write_str("quit")
+    }
+  }
+}
+
+impl FromStr for Request {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut words = s.split_whitespace();
+    let command = words.next().ok_or_else(|| "empty command".to_owned())?;
+    let args: Vec<_> = words.collect();
+
+    let arity = |n: usize| {
+      if args.len() == n {
+        Ok(())
+      } else {
+        Err(format!("{} expects {} argument(s), got {}", command, n, args.len()))
+      }
+    };
+
+    match command {
+      "seek" => {
+        arity(1)?;
+        args[0].parse().map(Request::Seek).map_err(|e| format!("invalid position: {}", e))
+      }
+
+      "pause" => arity(0).map(|_| Request::Pause),
+      "play" => arity(0).map(|_| Request::Play),
+
+      "speed" => {
+        arity(1)?;
+        match args[0].parse() {
+          Ok(speed) if speed > 0. => Ok(Request::Speed(speed)),
+          _ => Err(format!("invalid speed “{}”", args[0]))
+        }
+      }
+
+      "reload" => arity(0).map(|_| Request::Reload),
+
+      "set" => {
+        arity(2)?;
+        let value = args[1].parse().map_err(|_| format!("invalid value “{}”", args[1]))?;
+        Ok(Request::Set(args[0].to_owned(), value))
+      }
+
+      "time" => arity(0).map(|_| Request::Time),
+      "stats" => arity(0).map(|_| Request::Stats),
+      "quit" => arity(0).map(|_| Request::Quit),
+      _ => Err(format!("unknown command “{}”", command))
+    }
+  }
+}
+
+/// Response sent back to a client.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+  Ok,
+  State {
+    time: Time,
+    paused: bool,
+    speed: f64
+  },
+  Stats {
+    frames: u64,
+    fps: f64,
+    frame: Summary,
+    render: Summary,
+    swap: Summary
+  },
+  Error {
+    message: String
+  }
+}
+
+impl Response {
+  pub fn error<M>(message: M) -> Self where M: Into<String> {
+    Response::Error { message: message.into() }
+  }
+
+  /// JSON version of the response, on a single line.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).expect("responses are always serializable")
+  }
+}
+
+/// A request waiting for a response.
+#[derive(Debug)]
+pub struct Pending {
+  request: Request,
+  reply: Sender<Response>
+}
+
+impl Pending {
+  pub fn request(&self) -> &Request {
+    &self.request
+  }
+
+  /// Send the response back to the client.
+  pub fn reply(self, response: Response) {
+    // the client might be gone already
+    let _ = self.reply.send(response);
+  }
+}
+
+/// Remote control server.
+///
+/// Connections are accepted and read in background threads; requests are handed to the runner
+/// loop with [`Server::poll`], and the connections wait for the responses. Dropping the server stops
+/// accepting connections; the open ones end with their clients, or at their next request.
+#[derive(Debug)]
+pub struct Server {
+  address: String,
+  requests: Receiver<Pending>,
+  socket_path: Option<PathBuf>,
+  closed: Arc<AtomicBool>
+}
+
+impl Server {
+  /// Listen on an address: `host:port` for TCP – the port can be 0 to pick any free port –, or
+  /// `unix:path` for a Unix socket.
+  pub fn bind(address: &str) -> io::Result<Self> {
+    let (sender, requests) = mpsc::channel();
+
+    if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+      return Server::bind_unix(PathBuf::from(path), sender, requests);
+    }
+
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?.to_string();
+
+    listener.set_nonblocking(true)?;
+    let closed = accept(move || listener.accept().map(|(stream, _)| stream), sender);
+
+    Ok(Server { address, requests, socket_path: None, closed })
+  }
+
+  #[cfg(unix)]
+  fn bind_unix(path: PathBuf, sender: Sender<Pending>, requests: Receiver<Pending>) -> io::Result<Self> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // remove a socket left behind by a previous run, but nothing else
+    match std::fs::symlink_metadata(&path) {
+      Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(&path)?,
+
+      Ok(_) => {
+        let message = format!("{} already exists and is not a socket", path.display());
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+      }
+
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+      Err(e) => return Err(e)
+    }
+
+    let listener = UnixListener::bind(&path)?;
+
+    listener.set_nonblocking(true)?;
+    let closed = accept(move || listener.accept().map(|(stream, _)| stream), sender);
+
+    let address = format!("{}{}", UNIX_PREFIX, path.display());
+    Ok(Server { address, requests, socket_path: Some(path), closed })
+  }
+
+  #[cfg(not(unix))]
+  fn bind_unix(_: PathBuf, _: Sender<Pending>, _: Receiver<Pending>) -> io::Result<Self> {
+    Err(io::Error::new(io::ErrorKind::Other, "Unix sockets are not supported on this platform"))
+  }
+
+  /// Address the server listens on.
+  pub fn address(&self) -> &str {
+    &self.address
+  }
+
+  /// Get the requests received since the last call.
+  pub fn poll(&self) -> Vec<Pending> {
+    self.requests.try_iter().collect()
+  }
+}
+
+impl Drop for Server {
+  fn drop(&mut self) {
+    self.closed.store(true, Ordering::Relaxed);
+
+    if let Some(ref path) = self.socket_path {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+/// A connection accepted by a server.
+trait Stream: Read + Write + Send + Sized + 'static {
+  fn try_clone(&self) -> io::Result<Self>;
+
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Stream for TcpStream {
+  fn try_clone(&self) -> io::Result<Self> {
+    TcpStream::try_clone(self)
+  }
+
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+    TcpStream::set_nonblocking(self, nonblocking)
+  }
+}
+
+#[cfg(unix)]
+impl Stream for std::os::unix::net::UnixStream {
+  fn try_clone(&self) -> io::Result<Self> {
+    std::os::unix::net::UnixStream::try_clone(self)
+  }
+
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+    std::os::unix::net::UnixStream::set_nonblocking(self, nonblocking)
+  }
+}
+
+/// Accept connections in a background thread, with a non-blocking `accept` function, and serve
+/// them until the returned flag is set.
+fn accept<A, S>(mut accept: A, requests: Sender<Pending>) -> Arc<AtomicBool>
+where A: FnMut() -> io::Result<S> + Send + 'static,
+      S: Stream {
+  let closed = Arc::new(AtomicBool::new(false));
+  let thread_closed = closed.clone();
+
+  thread::spawn(move || {
+    while !thread_closed.load(Ordering::Relaxed) {
+      match accept() {
+        Ok(stream) => {
+          // accepted streams might inherit the non-blocking mode of the listener
+          let reader = stream.set_nonblocking(false).and_then(|_| stream.try_clone());
+
+          if let Ok(reader) = reader {
+            let requests = requests.clone();
+            thread::spawn(move || serve(reader, stream, requests));
+          }
+        }
+
+        // nothing to accept yet, or a failure that might be transient
+        Err(_) => thread::sleep(ACCEPT_INTERVAL)
+      }
+    }
+  });
+
+  closed
+}
+
+/// Serve a connection until it’s closed or the server is gone.
+fn serve<R, W>(reader: R, mut writer: W, requests: Sender<Pending>) where R: Read, W: Write {
+  for line in BufReader::new(reader).lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(_) => return
+    };
+
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let response = match line.parse() {
+      Ok(request) => {
+        let (reply, response) = mpsc::channel();
+
+        if requests.send(Pending { request, reply }).is_err() {
+          return;
+        }
+
+        match response.recv() {
+          Ok(response) => response,
+          Err(_) => return
+        }
+      }
+
+      Err(e) => Response::error(e)
+    };
+
+    if writeln!(writer, "{}", response.to_json()).and_then(|_| writer.flush()).is_err() {
+      return;
+    }
+  }
+}
+
+/// Remote control client.
+pub struct Client {
+  reader: BufReader<Box<dyn Read + Send>>,
+  writer: Box<dyn Write + Send>
+}
+
+impl Client {
+  /// Connect to a server, with the same address syntax as [`Server::bind`].
+  pub fn connect(address: &str) -> io::Result<Self> {
+    let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) =
+      match address.strip_prefix(UNIX_PREFIX) {
+        Some(path) => Client::connect_unix(path)?,
+
+        None => {
+          let stream = TcpStream::connect(address)?;
+          (Box::new(stream.try_clone()?), Box::new(stream))
+        }
+      };
+
+    Ok(Client { reader: BufReader::new(reader), writer })
+  }
+
+  #[cfg(unix)]
+  fn connect_unix(path: &str) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+    Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+  }
+
+  #[cfg(not(unix))]
+  fn connect_unix(_: &str) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+    Err(io::Error::new(io::ErrorKind::Other, "Unix sockets are not supported on this platform"))
+  }
+
+  /// Send a command and wait for the response, as a line of JSON.
+  pub fn send(&mut self, command: &str) -> io::Result<String> {
+    writeln!(self.writer, "{}", command.trim())?;
+    self.writer.flush()?;
+
+    let mut response = String::new();
+
+    if self.reader.read_line(&mut response)? == 0 {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by the server"));
+    }
+
+    Ok(response.trim_end().to_owned())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::Value;
+
+  use crate::time::DurationSpec;
+
+  use super::*;
+
+  #[test]
+  fn parse_requests() {
+    assert_eq!("seek 1m30s".parse(), Ok(Request::Seek(StartAt::Duration(DurationSpec::new(90, 0)))));
+    assert_eq!("seek drop".parse(), Ok(Request::Seek(StartAt::Marker("drop".to_owned()))));
+    assert_eq!("  pause ".parse(), Ok(Request::Pause));
+    assert_eq!("speed 0.5".parse(), Ok(Request::Speed(0.5)));
+    assert_eq!("set exposure 1.25".parse(), Ok(Request::Set("exposure".to_owned(), 1.25)));
+
+    assert!("".parse::<Request>().is_err());
+    assert!("jump 10s".parse::<Request>().is_err());
+    assert!("pause now".parse::<Request>().is_err());
+    assert!("speed -1".parse::<Request>().is_err());
+    assert!("set exposure".parse::<Request>().is_err());
+    assert!("seek 1m75s".parse::<Request>().is_err());
+  }
+
+  #[test]
+  fn request_round_trip() {
+    let requests = vec![
+      Request::Seek(StartAt::Marker("drop".to_owned())),
+      Request::Speed(2.),
+      Request::Set("fov".to_owned(), 60.5),
+      Request::Stats,
+      Request::Quit
+    ];
+
+    for request in requests {
+      assert_eq!(request.to_string().parse(), Ok(request));
+    }
+  }
+
+  #[test]
+  fn responses() {
+    assert_eq!(Response::Ok.to_json(), r#"{"type":"ok"}"#);
+    assert_eq!(Response::error("nope").to_json(), r#"{"type":"error","message":"nope"}"#);
+
+    let state = Response::State { time: Time::from_secs(1.5), paused: true, speed: 2. };
+    assert_eq!(state.to_json(), r#"{"type":"state","time":1.5,"paused":true,"speed":2.0}"#);
+  }
+
+  #[test]
+  fn tcp_loopback() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.address().to_owned();
+
+    let client = thread::spawn(move || {
+      let mut client = Client::connect(&address).unwrap();
+      (client.send("bogus").unwrap(), client.send("time").unwrap())
+    });
+
+    // answer the only valid request
+    let pending = loop {
+      if let Some(pending) = server.poll().pop() {
+        break pending;
+      }
+
+      thread::yield_now();
+    };
+
+    assert_eq!(pending.request(), &Request::Time);
+    pending.reply(Response::State { time: Time::from_secs(2.), paused: false, speed: 1. });
+
+    let (error, state) = client.join().unwrap();
+    let error: Value = serde_json::from_str(&error).unwrap();
+    let state: Value = serde_json::from_str(&state).unwrap();
+
+    assert_eq!(error["type"], "error");
+    assert_eq!(state["type"], "state");
+    assert_eq!(state["time"], 2.);
+  }
+
+  #[test]
+  fn stops_accepting_when_dropped() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.address().to_owned();
+
+    assert!(TcpStream::connect(&address).is_ok());
+    drop(server);
+
+    // the listener is closed once the accepting thread notices
+    let refused = (0..100).any(|_| {
+      thread::sleep(ACCEPT_INTERVAL);
+      TcpStream::connect(&address).is_err()
+    });
+
+    assert!(refused);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn unix_socket_replaces_only_sockets() {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::process;
+
+    let path = env::temp_dir().join(format!("spectra-remote-{}.sock", process::id()));
+    let address = format!("unix:{}", path.display());
+
+    // a regular file is left alone
+    fs::write(&path, "precious").unwrap();
+    assert_eq!(Server::bind(&address).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "precious");
+    fs::remove_file(&path).unwrap();
+
+    // a socket left behind by a previous run is replaced
+    drop(UnixListener::bind(&path).unwrap());
+    let server = Server::bind(&address).unwrap();
+    assert!(Client::connect(&address).is_ok());
+
+    drop(server);
+    assert!(!path.exists());
+  }
+}
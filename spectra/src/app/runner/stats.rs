@@ -0,0 +1,362 @@
+//! Frame timing statistics.
+//!
+//! Runners measure, for every frame, the CPU time spent in [`Demo::render`], the time spent
+//! swapping buffers and the total frame time. The last samples are kept in rolling windows, from
+//! which [`Summary`]s – minimum, average, maximum and percentiles – are computed.
+//!
+//! All the samples can also be recorded to be exported as CSV or JSON, so that runs can be compared.
+//!
+//! [`Demo::render`]: crate::app::demo::Demo::render
+
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::time::Time;
+
+/// Timings of a single frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct FrameSample {
+  /// Time spent rendering.
+  pub render: Time,
+  /// Time spent swapping buffers.
+  pub swap: Time,
+  /// Total time of the frame.
+  pub frame: Time
+}
+
+/// Summary of a set of timings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+  pub min: Time,
+  pub avg: Time,
+  pub max: Time,
+  pub p50: Time,
+  pub p95: Time,
+  pub p99: Time
+}
+
+impl Summary {
+  /// Summarize some timings; an empty set of timings yields an all-zero summary.
+  pub fn new<I>(timings: I) -> Self where I: IntoIterator<Item = Time> {
+    let mut secs: Vec<f64> = timings.into_iter().map(Time::as_secs).collect();
+
+    if secs.is_empty() {
+      return Summary::default();
+    }
+
+    secs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let sum: f64 = secs.iter().sum();
+
+    Summary {
+      min: Time::from_secs(secs[0]),
+      avg: Time::from_secs(sum / secs.len() as f64),
+      max: Time::from_secs(secs[secs.len() - 1]),
+      p50: Time::from_secs(percentile(&secs, 50.)),
+      p95: Time::from_secs(percentile(&secs, 95.)),
+      p99: Time::from_secs(percentile(&secs, 99.))
+    }
+  }
+}
+
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(
+      f,
+      "{:.2}/{:.2}/{:.2} p50/95/99 {:.2}/{:.2}/{:.2}",
+      self.min.as_millis(),
+      self.avg.as_millis(),
+      self.max.as_millis(),
+      self.p50.as_millis(),
+      self.p95.as_millis(),
+      self.p99.as_millis()
+    )
+  }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+  let rank = (p / 100. * sorted.len() as f64).ceil() as usize;
+  sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Frame statistics.
+///
+/// The [`Display`](fmt::Display) implementation gives a compact, one-line report of the rolling
+/// windows, in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameStats {
+  frames: u64,
+  last: FrameSample,
+  avg_frame_time: Time,
+  window: VecDeque<FrameSample>,
+  capacity: usize,
+  history: Option<Vec<FrameSample>>
+}
+
+impl FrameStats {
+  /// Default number of frames kept in the rolling window.
+  pub const DEFAULT_WINDOW: usize = 300;
+
+  /// Smoothing factor of the average frame time.
+  const SMOOTHING: f64 = 0.05;
+
+  /// Create statistics keeping the `window` last frames.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `window` is 0.
+  pub fn new(window: usize) -> Self {
+    assert!(window > 0, "frame statistics need a non-empty window");
+
+    FrameStats {
+      frames: 0,
+      last: FrameSample::default(),
+      avg_frame_time: Time::default(),
+      window: VecDeque::with_capacity(window),
+      capacity: window,
+      history: None
+    }
+  }
+
+  /// Record all the samples, in addition to the rolling window, so that they can be exported.
+  pub fn record_history(self) -> Self {
+    FrameStats { history: Some(Vec::new()), ..self }
+  }
+
+  /// Number of frames rendered so far.
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  /// Timings of the last frame.
+  pub fn last(&self) -> &FrameSample {
+    &self.last
+  }
+
+  /// Duration of the last frame.
+  pub fn last_frame_time(&self) -> Time {
+    self.last.frame
+  }
+
+  /// Exponential moving average of the frame duration.
+  pub fn avg_frame_time(&self) -> Time {
+    self.avg_frame_time
+  }
+
+  /// Number of frames per second, computed from the average frame duration.
+  pub fn fps(&self) -> f64 {
+    let secs = self.avg_frame_time.as_secs();
+
+    if secs > 0. { 1. / secs } else { 0. }
+  }
+
+  /// Samples of the rolling window, from the oldest to the newest.
+  pub fn window(&self) -> impl Iterator<Item = &FrameSample> {
+    self.window.iter()
+  }
+
+  /// Summary of the render times of the rolling window.
+  pub fn render(&self) -> Summary {
+    Summary::new(self.window.iter().map(|s| s.render))
+  }
+
+  /// Summary of the swap times of the rolling window.
+  pub fn swap(&self) -> Summary {
+    Summary::new(self.window.iter().map(|s| s.swap))
+  }
+
+  /// Summary of the frame times of the rolling window.
+  pub fn frame(&self) -> Summary {
+    Summary::new(self.window.iter().map(|s| s.frame))
+  }
+
+  /// All the recorded samples, if recording was enabled with [`FrameStats::record_history`].
+  pub fn history(&self) -> Option<&[FrameSample]> {
+    self.history.as_deref()
+  }
+
+  /// Account for a new frame.
+  pub(crate) fn record(&mut self, sample: FrameSample) {
+    self.avg_frame_time = if self.frames == 0 {
+      sample.frame
+    } else {
+      self.avg_frame_time + (sample.frame - self.avg_frame_time) * Self::SMOOTHING
+    };
+
+    if self.window.len() == self.capacity {
+      self.window.pop_front();
+    }
+
+    self.window.push_back(sample);
+
+    if let Some(ref mut history) = self.history {
+      history.push(sample);
+    }
+
+    self.last = sample;
+    self.frames += 1;
+  }
+
+  /// Write the recorded samples as CSV – one line per frame, in milliseconds.
+  pub fn write_csv<W>(&self, mut w: W) -> io::Result<()> where W: Write {
+    writeln!(w, "frame,render_ms,swap_ms,frame_ms")?;
+
+    for (i, s) in self.history().unwrap_or(&[]).iter().enumerate() {
+      writeln!(w, "{},{},{},{}", i, s.render.as_millis(), s.swap.as_millis(), s.frame.as_millis())?;
+    }
+
+    Ok(())
+  }
+
+  /// Write the summaries of the recorded samples and the samples themselves as JSON, in seconds.
+  pub fn write_json<W>(&self, w: W) -> io::Result<()> where W: Write {
+    #[derive(Serialize)]
+    struct Export<'a> {
+      frames: usize,
+      render: Summary,
+      swap: Summary,
+      frame: Summary,
+      samples: &'a [FrameSample]
+    }
+
+    let samples = self.history().unwrap_or(&[]);
+    let export = Export {
+      frames: samples.len(),
+      render: Summary::new(samples.iter().map(|s| s.render)),
+      swap: Summary::new(samples.iter().map(|s| s.swap)),
+      frame: Summary::new(samples.iter().map(|s| s.frame)),
+      samples
+    };
+
+    serde_json::to_writer_pretty(w, &export).map_err(io::Error::from)
+  }
+
+  /// Export the recorded samples to a file, as JSON if its extension is `json` and as CSV
+  /// otherwise.
+  pub fn export<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+    let path = path.as_ref();
+    let mut w = BufWriter::new(File::create(path)?);
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+      self.write_json(&mut w)?;
+    } else {
+      self.write_csv(&mut w)?;
+    }
+
+    w.flush()
+  }
+}
+
+impl Default for FrameStats {
+  fn default() -> Self {
+    FrameStats::new(Self::DEFAULT_WINDOW)
+  }
+}
+
+impl fmt::Display for FrameStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(
+      f,
+      "{:.1} FPS | frame {} | render {} | swap {} (ms)",
+      self.fps(),
+      self.frame(),
+      self.render(),
+      self.swap()
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(millis: f64) -> Time {
+    Time::from_millis(millis)
+  }
+
+  fn sample(render: f64, swap: f64, frame: f64) -> FrameSample {
+    FrameSample { render: ms(render), swap: ms(swap), frame: ms(frame) }
+  }
+
+  #[test]
+  fn summary() {
+    let summary = Summary::new((1..=100).map(|i| ms(i as f64)));
+
+    assert_eq!(summary.min, ms(1.));
+    assert_eq!(summary.max, ms(100.));
+    assert!((summary.avg.as_millis() - 50.5).abs() < 1e-9);
+    assert_eq!(summary.p50, ms(50.));
+    assert_eq!(summary.p95, ms(95.));
+    assert_eq!(summary.p99, ms(99.));
+
+    let single = Summary::new(vec![ms(4.)]);
+    assert_eq!((single.min, single.p50, single.p99), (ms(4.), ms(4.), ms(4.)));
+
+    assert_eq!(Summary::new(Vec::new()), Summary::default());
+  }
+
+  #[test]
+  fn rolling_window() {
+    let mut stats = FrameStats::new(3);
+
+    for i in 1..=5 {
+      stats.record(sample(i as f64, 1., 10. * i as f64));
+    }
+
+    assert_eq!(stats.frames(), 5);
+    assert_eq!(stats.window().count(), 3);
+    assert_eq!(stats.render().min, ms(3.));
+    assert_eq!(stats.frame().max, ms(50.));
+    assert_eq!(stats.last_frame_time(), ms(50.));
+    assert_eq!(stats.history(), None);
+  }
+
+  #[test]
+  fn fps() {
+    let mut stats = FrameStats::default();
+    assert_eq!(stats.fps(), 0.);
+
+    stats.record(sample(5., 1., 20.));
+    assert_eq!(stats.avg_frame_time(), ms(20.));
+    assert!((stats.fps() - 50.).abs() < 1e-9);
+
+    for _ in 0..1000 {
+      stats.record(sample(5., 1., 10.));
+    }
+
+    assert!((stats.fps() - 100.).abs() < 1e-6);
+  }
+
+  #[test]
+  fn export_csv() {
+    let mut stats = FrameStats::new(1).record_history();
+    stats.record(sample(1., 0.5, 16.));
+    stats.record(sample(2., 0.25, 17.));
+
+    let mut csv = Vec::new();
+    stats.write_csv(&mut csv).unwrap();
+
+    assert_eq!(String::from_utf8(csv).unwrap(), "frame,render_ms,swap_ms,frame_ms\n0,1,0.5,16\n1,2,0.25,17\n");
+  }
+
+  #[test]
+  fn export_json() {
+    let mut stats = FrameStats::new(1).record_history();
+    stats.record(sample(1., 0.5, 16.));
+    stats.record(sample(3., 0.5, 18.));
+
+    let mut json = Vec::new();
+    stats.write_json(&mut json).unwrap();
+
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["frames"], 2);
+    assert!((value["frame"]["max"].as_f64().unwrap() - 0.018).abs() < 1e-12);
+    assert!((value["render"]["avg"].as_f64().unwrap() - 0.002).abs() < 1e-12);
+    assert_eq!(value["samples"].as_array().map(Vec::len), Some(2));
+  }
+}
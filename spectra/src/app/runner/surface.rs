@@ -0,0 +1,273 @@
+//! Rendering surfaces.
+//!
+//! A [`Surface`] is what a runner loop renders to and gets its events from. [`GlfwWindow`] is the
+//! regular, windowed surface; [`Headless`](crate::app::runner::headless::Headless) is a mock
+//! surface used to run demos without a display.
+
+use gl::types::GLsizei;
+use glfw::SwapInterval;
+use luminance::context::GraphicsContext;
+use luminance::framebuffer::Framebuffer;
+use luminance_glfw::surface::{
+  Action as GlfwAction, GlfwSurface, Key as GlfwKey, MouseButton as GlfwMouseButton, Surface as _,
+  WindowDim, WindowEvent, WindowOpt
+};
+use std::os::raw::c_void;
+
+use crate::app::demo::{Backbuffer, Target};
+use crate::app::event::{Action, Event, Key, MouseButton};
+use crate::app::runner;
+use crate::app::runner::capture::Image;
+use crate::app::runner::pacing::Vsync;
+use crate::time::{Monotonic, Time};
+
+/// Something a runner can render to.
+pub trait Surface {
+  /// Size of the framebuffer, in pixels.
+  fn size(&self) -> [u32; 2];
+
+  /// Get the events that occurred since the last call.
+  fn poll_events(&mut self) -> Vec<Event>;
+
+  /// Adapt the surface to a new framebuffer size.
+  ///
+  /// Runners call this method when they treat an [`Event::Resize`].
+  fn resize(&mut self, size: [u32; 2]);
+
+  /// Render a frame at a given time.
+  ///
+  /// `f` is given the target to render to. Surfaces without graphics context give it a
+  /// [headless target](Target::has_graphics).
+  fn render<F>(&mut self, t: Time, f: F) where F: FnOnce(Target);
+
+  /// Render a frame at a given time, like [`Surface::render`], and read the rendered frame back.
+  ///
+  /// The default implementation renders without reading anything back.
+  fn render_captured<F>(&mut self, t: Time, f: F) -> Option<Image> where F: FnOnce(Target) {
+    self.render(t, f);
+    None
+  }
+
+  /// Called once the demo is initialized, right before the first frame.
+  fn initialized(&mut self) {}
+}
+
+/// Render a frame on a surface – and capture it if asked to –, returning the time spent in `f`, the
+/// remaining time spent by the surface – mostly swapping buffers – and the captured frame.
+pub(crate) fn render_timed<S, F>(surface: &mut S, t: Time, capture: bool, f: F) -> (Time, Time, Option<Image>)
+where S: Surface,
+      F: FnOnce(Target) {
+  let start = Monotonic::now();
+  let mut render = Time::default();
+
+  let timed = |target: Target| {
+    let render_start = Monotonic::now();
+    f(target);
+    render = render_start.elapsed_secs();
+  };
+
+  let image = if capture {
+    surface.render_captured(t, timed)
+  } else {
+    surface.render(t, timed);
+    None
+  };
+
+  (render, start.elapsed_secs() - render, image)
+}
+
+/// A GLFW window.
+pub struct GlfwWindow {
+  surface: GlfwSurface,
+  back_buffer: Backbuffer
+}
+
+impl GlfwWindow {
+  /// Open a window.
+  pub fn new(dim: WindowDim, title: &str, opt: WindowOpt) -> Result<Self, runner::Error> {
+    let surface =
+      GlfwSurface::new(dim, title, opt)
+        .map_err(|e| runner::Error::cannot_create_window(format!("{}", e)))?;
+    let back_buffer = Framebuffer::back_buffer(surface.size());
+
+    Ok(GlfwWindow { surface, back_buffer })
+  }
+
+  /// Change the vertical synchronization mode.
+  pub fn set_vsync(&mut self, vsync: Vsync) {
+    let interval = match vsync {
+      Vsync::On => SwapInterval::Sync(1),
+      Vsync::Off => SwapInterval::None,
+      Vsync::Adaptive => SwapInterval::Adaptive
+    };
+
+    self.surface.window.glfw.set_swap_interval(interval);
+  }
+}
+
+impl Surface for GlfwWindow {
+  fn size(&self) -> [u32; 2] {
+    self.surface.size()
+  }
+
+  fn poll_events(&mut self) -> Vec<Event> {
+    self.surface.poll_events().filter_map(event).collect()
+  }
+
+  fn resize(&mut self, size: [u32; 2]) {
+    self.back_buffer = Framebuffer::back_buffer(size);
+  }
+
+  fn render<F>(&mut self, _: Time, f: F) where F: FnOnce(Target) {
+    let builder = self.surface.pipeline_builder();
+
+    f(Target::new(&self.back_buffer, builder));
+    self.surface.swap_buffers();
+  }
+
+  fn render_captured<F>(&mut self, _: Time, f: F) -> Option<Image> where F: FnOnce(Target) {
+    let builder = self.surface.pipeline_builder();
+
+    f(Target::new(&self.back_buffer, builder));
+    let image = read_back(self.size());
+    self.surface.swap_buffers();
+
+    Some(image)
+  }
+}
+
+/// Read the pixels of the back buffer.
+fn read_back([width, height]: [u32; 2]) -> Image {
+  let mut rgba = vec![0u8; width as usize * height as usize * 4];
+
+  unsafe {
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+    gl::ReadBuffer(gl::BACK);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::ReadPixels(
+      0,
+      0,
+      width as GLsizei,
+      height as GLsizei,
+      gl::RGBA,
+      gl::UNSIGNED_BYTE,
+      rgba.as_mut_ptr() as *mut c_void
+    );
+  }
+
+  Image::from_gl_rgba(width, height, &rgba)
+}
+
+/// Translate a GLFW event.
+fn event(event: WindowEvent) -> Option<Event> {
+  match event {
+    WindowEvent::Close => Some(Event::Close),
+    WindowEvent::FramebufferSize(w, h) => Some(Event::Resize(w as u32, h as u32)),
+    WindowEvent::Key(k, _, a, _) => Some(Event::Key(key(k), action(a))),
+    WindowEvent::CursorPos(x, y) => Some(Event::MouseMove(x, y)),
+    WindowEvent::MouseButton(b, a, _) => Some(Event::MouseButton(mouse_button(b), action(a))),
+    WindowEvent::Scroll(x, y) => Some(Event::Scroll(x, y)),
+    WindowEvent::Focus(focused) => Some(Event::Focus(focused)),
+    WindowEvent::FileDrop(paths) => Some(Event::FileDrop(paths)),
+    _ => None
+  }
+}
+
+/// Translate a GLFW mouse button.
+fn mouse_button(button: GlfwMouseButton) -> MouseButton {
+  match button {
+    GlfwMouseButton::Button1 => MouseButton::Left,
+    GlfwMouseButton::Button2 => MouseButton::Right,
+    GlfwMouseButton::Button3 => MouseButton::Middle,
+    GlfwMouseButton::Button4 => MouseButton::Other(3),
+    GlfwMouseButton::Button5 => MouseButton::Other(4),
+    GlfwMouseButton::Button6 => MouseButton::Other(5),
+    GlfwMouseButton::Button7 => MouseButton::Other(6),
+    GlfwMouseButton::Button8 => MouseButton::Other(7)
+  }
+}
+
+/// Translate a GLFW action.
+fn action(action: GlfwAction) -> Action {
+  match action {
+    GlfwAction::Press => Action::Press,
+    GlfwAction::Repeat => Action::Repeat,
+    GlfwAction::Release => Action::Release
+  }
+}
+
+/// Translate a GLFW key.
+fn key(key: GlfwKey) -> Key {
+  match key {
+    GlfwKey::A => Key::A,
+    GlfwKey::B => Key::B,
+    GlfwKey::C => Key::C,
+    GlfwKey::D => Key::D,
+    GlfwKey::E => Key::E,
+    GlfwKey::F => Key::F,
+    GlfwKey::G => Key::G,
+    GlfwKey::H => Key::H,
+    GlfwKey::I => Key::I,
+    GlfwKey::J => Key::J,
+    GlfwKey::K => Key::K,
+    GlfwKey::L => Key::L,
+    GlfwKey::M => Key::M,
+    GlfwKey::N => Key::N,
+    GlfwKey::O => Key::O,
+    GlfwKey::P => Key::P,
+    GlfwKey::Q => Key::Q,
+    GlfwKey::R => Key::R,
+    GlfwKey::S => Key::S,
+    GlfwKey::T => Key::T,
+    GlfwKey::U => Key::U,
+    GlfwKey::V => Key::V,
+    GlfwKey::W => Key::W,
+    GlfwKey::X => Key::X,
+    GlfwKey::Y => Key::Y,
+    GlfwKey::Z => Key::Z,
+    GlfwKey::Num0 => Key::Num0,
+    GlfwKey::Num1 => Key::Num1,
+    GlfwKey::Num2 => Key::Num2,
+    GlfwKey::Num3 => Key::Num3,
+    GlfwKey::Num4 => Key::Num4,
+    GlfwKey::Num5 => Key::Num5,
+    GlfwKey::Num6 => Key::Num6,
+    GlfwKey::Num7 => Key::Num7,
+    GlfwKey::Num8 => Key::Num8,
+    GlfwKey::Num9 => Key::Num9,
+    GlfwKey::F1 => Key::F1,
+    GlfwKey::F2 => Key::F2,
+    GlfwKey::F3 => Key::F3,
+    GlfwKey::F4 => Key::F4,
+    GlfwKey::F5 => Key::F5,
+    GlfwKey::F6 => Key::F6,
+    GlfwKey::F7 => Key::F7,
+    GlfwKey::F8 => Key::F8,
+    GlfwKey::F9 => Key::F9,
+    GlfwKey::F10 => Key::F10,
+    GlfwKey::F11 => Key::F11,
+    GlfwKey::F12 => Key::F12,
+    GlfwKey::Space => Key::Space,
+    GlfwKey::Escape => Key::Escape,
+    GlfwKey::Enter => Key::Enter,
+    GlfwKey::Tab => Key::Tab,
+    GlfwKey::Backspace => Key::Backspace,
+    GlfwKey::Insert => Key::Insert,
+    GlfwKey::Delete => Key::Delete,
+    GlfwKey::Left => Key::Left,
+    GlfwKey::Right => Key::Right,
+    GlfwKey::Up => Key::Up,
+    GlfwKey::Down => Key::Down,
+    GlfwKey::PageUp => Key::PageUp,
+    GlfwKey::PageDown => Key::PageDown,
+    GlfwKey::Home => Key::Home,
+    GlfwKey::End => Key::End,
+    GlfwKey::LeftShift => Key::LeftShift,
+    GlfwKey::RightShift => Key::RightShift,
+    GlfwKey::LeftControl => Key::LeftControl,
+    GlfwKey::RightControl => Key::RightControl,
+    GlfwKey::LeftAlt => Key::LeftAlt,
+    GlfwKey::RightAlt => Key::RightAlt,
+    _ => Key::Unknown
+  }
+}
@@ -0,0 +1,704 @@
+//! Playback transport.
+//!
+//! The transport owns the playback position of a runner and lets the user pause, seek, change the
+//! playback speed, jump between timeline markers and loop over a region. It doesn’t depend on any
+//! rendering surface: runners translate their own key events into [`TransportKey`].
+//!
+//! A transport is a [`Clock`] adapter: it advances its playback position by the time elapsed on
+//! the clock it wraps. If that clock is a [playback](Clock::is_playback) – such as a soundtrack –,
+//! it is authoritative instead: the transport takes its position from it, and makes it follow the
+//! seeks, pauses and speed changes.
+
+use crate::time::Time;
+use crate::time::clock::Clock;
+
+/// Available playback speeds.
+const SPEEDS: [f64; 5] = [0.25, 0.5, 1., 2., 4.];
+
+/// Index of the normal speed in `SPEEDS`.
+const NORMAL_SPEED: usize = 2;
+
+/// Keys the transport reacts to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TransportKey {
+  Space,
+  Left,
+  Right,
+  Up,
+  Down,
+  PageUp,
+  PageDown,
+  Backspace,
+  A,
+  B,
+  C
+}
+
+/// Commands understood by the transport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+  /// Pause if playing, resume if paused.
+  TogglePause,
+  /// Move the playback position by a given amount of seconds.
+  Seek(f64),
+  /// Jump to the next marker.
+  NextMarker,
+  /// Jump to the previous marker.
+  PreviousMarker,
+  /// Double the playback speed, up to ×4.
+  SpeedUp,
+  /// Halve the playback speed, down to ×0.25.
+  SpeedDown,
+  /// Go back to the normal playback speed.
+  ResetSpeed,
+  /// Set the start of the loop region at the current position.
+  SetLoopStart,
+  /// Set the end of the loop region at the current position.
+  SetLoopEnd,
+  /// Remove the loop region.
+  ClearLoop
+}
+
+impl Command {
+  /// Map a key to a command.
+  ///
+  /// The default bindings are:
+  ///
+  ///   - *space*: pause / resume.
+  ///   - *left* / *right*: seek backward / forward by 1s; 10s if *shift* is held.
+  ///   - *page up* / *page down*: jump to the previous / next marker.
+  ///   - *up* / *down*: speed up / slow down.
+  ///   - *backspace*: reset the speed.
+  ///   - *A* / *B*: set the start / end of the loop region.
+  ///   - *C*: clear the loop region.
+  pub fn from_key(key: TransportKey, shift: bool) -> Option<Self> {
+    let seek = if shift { 10. } else { 1. };
+
+    match key {
+      TransportKey::Space => Some(Command::TogglePause),
+      TransportKey::Left => Some(Command::Seek(-seek)),
+      TransportKey::Right => Some(Command::Seek(seek)),
+      TransportKey::Up => Some(Command::SpeedUp),
+      TransportKey::Down => Some(Command::SpeedDown),
+      TransportKey::PageUp => Some(Command::PreviousMarker),
+      TransportKey::PageDown => Some(Command::NextMarker),
+      TransportKey::Backspace => Some(Command::ResetSpeed),
+      TransportKey::A => Some(Command::SetLoopStart),
+      TransportKey::B => Some(Command::SetLoopEnd),
+      TransportKey::C => Some(Command::ClearLoop),
+    }
+  }
+}
+
+/// Discontinuity of the playback position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Jump {
+  /// The position was moved by hand – seeks and marker navigation.
+  Seek,
+  /// The position reached `end` and went on from `start` – the loop region or the wrap-around.
+  Loop {
+    end: Time,
+    start: Time
+  }
+}
+
+/// Playback transport.
+#[derive(Clone, Debug)]
+pub struct Transport<C> {
+  /// Clock driving the transport.
+  clock: C,
+  /// Last time yielded by the clock.
+  last: Option<Time>,
+  /// Current playback position, in seconds. Starts at the first time yielded by the clock, unless
+  /// seeked before.
+  position: Option<f64>,
+  /// Whether the playback is paused.
+  paused: bool,
+  /// Index of the current speed in `SPEEDS`.
+  speed: usize,
+  /// Sorted marker positions, in seconds.
+  markers: Vec<f64>,
+  /// Start of the loop region.
+  loop_start: Option<f64>,
+  /// End of the loop region.
+  loop_end: Option<f64>,
+  /// Duration after which the position wraps around to 0, in seconds.
+  wrap_at: Option<f64>,
+  /// Discontinuity since the last call to `take_jump`.
+  jump: Option<Jump>
+}
+
+impl<C> Transport<C> where C: Clock {
+  /// Create a transport driven by a given clock.
+  pub fn new(clock: C) -> Self {
+    Transport {
+      clock,
+      last: None,
+      position: None,
+      paused: false,
+      speed: NORMAL_SPEED,
+      markers: Vec::new(),
+      loop_start: None,
+      loop_end: None,
+      wrap_at: None,
+      jump: None
+    }
+  }
+
+  /// Make the position wrap around to 0 whenever it reaches a given duration.
+  ///
+  /// A playback clock is sought back when the position wraps, so that it stays in sync.
+  pub fn wrap_at(mut self, wrap_at: Option<Time>) -> Self {
+    self.wrap_at = wrap_at.map(Time::as_secs).filter(|&w| w > 0.);
+
+    if let Some(position) = self.position {
+      self.seek(Time::from_secs(position));
+    }
+
+    self
+  }
+
+  /// Get a reference on the clock driving the transport.
+  pub fn get_ref(&self) -> &C {
+    &self.clock
+  }
+
+  /// Get a mutable reference on the clock driving the transport.
+  pub fn get_mut(&mut self) -> &mut C {
+    &mut self.clock
+  }
+
+  /// Replace the markers used by the marker navigation commands.
+  ///
+  /// Markers at non-finite times – NaN, for instance – can’t be jumped to and are ignored.
+  pub fn set_markers<I>(&mut self, markers: I) where I: IntoIterator<Item = Time> {
+    self.markers = markers.into_iter().map(Time::as_secs).filter(|t| t.is_finite()).collect();
+    self.markers.sort_by(f64::total_cmp);
+  }
+
+  /// Current playback position.
+  pub fn position(&self) -> Time {
+    Time::from_secs(self.position.unwrap_or(0.))
+  }
+
+  /// Whether the playback is paused.
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  /// Current playback speed factor.
+  pub fn speed(&self) -> f64 {
+    SPEEDS[self.speed]
+  }
+
+  /// Take the discontinuity of the playback position since the last call, if any.
+  ///
+  /// Runners check it after every tick, to know which cues were crossed. If the position was both
+  /// sought and looped, the seek wins.
+  pub fn take_jump(&mut self) -> Option<Jump> {
+    self.jump.take()
+  }
+
+  /// Current loop region, if both its start and end are set.
+  pub fn loop_region(&self) -> Option<(Time, Time)> {
+    self.active_loop().map(|(a, b)| (Time::from_secs(a), Time::from_secs(b)))
+  }
+
+  /// Handle a key press.
+  ///
+  /// Returns the command that was applied, if any.
+  pub fn key(&mut self, key: TransportKey, shift: bool) -> Option<Command> {
+    let command = Command::from_key(key, shift)?;
+    self.command(command);
+    Some(command)
+  }
+
+  /// Apply a command.
+  pub fn command(&mut self, command: Command) {
+    match command {
+      Command::TogglePause => self.paused = !self.paused,
+
+      Command::Seek(delta) => {
+        let position = self.position().as_secs();
+        self.seek(Time::from_secs(position + delta));
+      }
+
+      Command::NextMarker => {
+        let position = self.position().as_secs();
+
+        if let Some(&marker) = self.markers.iter().find(|&&m| m > position) {
+          self.seek(Time::from_secs(marker));
+        }
+      }
+
+      Command::PreviousMarker => {
+        let position = self.position().as_secs();
+
+        if let Some(&marker) = self.markers.iter().rev().find(|&&m| m < position) {
+          self.seek(Time::from_secs(marker));
+        }
+      }
+
+      Command::SpeedUp => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+
+      Command::SpeedDown => self.speed = self.speed.saturating_sub(1),
+
+      Command::ResetSpeed => self.speed = NORMAL_SPEED,
+
+      Command::SetLoopStart => {
+        self.loop_start = Some(self.position().as_secs());
+        self.order_loop();
+      }
+
+      Command::SetLoopEnd => {
+        self.loop_end = Some(self.position().as_secs());
+        self.order_loop();
+      }
+
+      Command::ClearLoop => {
+        self.loop_start = None;
+        self.loop_end = None;
+      }
+    }
+
+    self.clock.set_paused(self.paused);
+    self.clock.set_speed(self.speed());
+  }
+
+  /// Pause or resume the playback.
+  pub fn set_paused(&mut self, paused: bool) {
+    self.paused = paused;
+    self.clock.set_paused(paused);
+  }
+
+  /// Set the playback speed to the closest available one.
+  pub fn set_speed(&mut self, speed: f64) {
+    let distance = |i: usize| (SPEEDS[i] - speed).abs();
+    self.speed = (0..SPEEDS.len()).min_by(|&a, &b| distance(a).partial_cmp(&distance(b)).unwrap()).unwrap();
+    self.clock.set_speed(self.speed());
+  }
+
+  /// Move the playback position to a given time.
+  ///
+  /// Negative times are clamped to 0.
+  pub fn seek(&mut self, t: Time) {
+    let t = self.wrap(t.as_secs().max(0.));
+
+    self.position = Some(t);
+    self.jump = Some(Jump::Seek);
+    self.clock.seek(Time::from_secs(t));
+  }
+
+  /// Advance the playback position by a given amount of clock time.
+  ///
+  /// The amount of time is scaled by the playback speed and ignored when paused.
+  fn advance(&mut self, dt: f64) {
+    let current = self.position().as_secs();
+
+    if !self.paused {
+      let position = self.loop_around(current, (current + dt * self.speed()).max(0.));
+      self.position = Some(position);
+    }
+  }
+
+  /// Follow the position of a playback clock, looping over the loop region and wrapping around.
+  fn follow(&mut self, now: Time) {
+    let current = self.position().as_secs();
+    let now = now.as_secs().max(0.);
+    let position = self.loop_around(current, now);
+
+    if position != now {
+      self.clock.seek(Time::from_secs(position));
+    }
+
+    self.position = Some(position);
+  }
+
+  /// Move from `current` to `position`, looping over the loop region and wrapping around.
+  fn loop_around(&mut self, current: f64, mut position: f64) -> f64 {
+    if let Some((a, b)) = self.active_loop() {
+      if current < b && position >= b {
+        position = a + (position - b) % (b - a);
+        self.looped(b, a);
+      }
+    }
+
+    if let Some(wrap_at) = self.wrap_at {
+      if position >= wrap_at {
+        position %= wrap_at;
+        self.looped(wrap_at, 0.);
+      }
+    }
+
+    position
+  }
+
+  fn looped(&mut self, end: f64, start: f64) {
+    if self.jump.is_none() {
+      self.jump = Some(Jump::Loop { end: Time::from_secs(end), start: Time::from_secs(start) });
+    }
+  }
+
+  fn wrap(&self, position: f64) -> f64 {
+    match self.wrap_at {
+      Some(wrap_at) => position % wrap_at,
+      None => position
+    }
+  }
+
+  fn active_loop(&self) -> Option<(f64, f64)> {
+    match (self.loop_start, self.loop_end) {
+      (Some(a), Some(b)) if a < b => Some((a, b)),
+      _ => None
+    }
+  }
+
+  fn order_loop(&mut self) {
+    if let (Some(a), Some(b)) = (self.loop_start, self.loop_end) {
+      if b < a {
+        self.loop_start = Some(b);
+        self.loop_end = Some(a);
+      }
+    }
+  }
+}
+
+impl<C> Clock for Transport<C> where C: Clock {
+  fn tick(&mut self) -> Time {
+    let now = self.clock.tick();
+
+    if self.clock.is_playback() {
+      self.follow(now);
+      return self.position();
+    }
+
+    match self.last.replace(now) {
+      Some(last) => self.advance((now - last).as_secs()),
+      None => {
+        if self.position.is_none() {
+          self.position = Some(self.wrap(now.as_secs().max(0.)));
+        }
+      }
+    }
+
+    self.position()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::time::clock::ManualClock;
+  use crate::time::marker::{Cue, CueCursor, MarkerTrack};
+
+  use super::*;
+
+  fn secs(t: f64) -> Time {
+    Time::from_secs(t)
+  }
+
+  fn transport(start_at: f64) -> Transport<ManualClock> {
+    let mut transport = Transport::new(ManualClock::new(secs(start_at)));
+    transport.tick();
+    transport
+  }
+
+  fn advance(transport: &mut Transport<ManualClock>, dt: f64) -> Time {
+    transport.get_mut().step(secs(dt));
+    transport.tick()
+  }
+
+  #[test]
+  fn advance_and_pause() {
+    let mut transport = transport(2.);
+
+    assert_eq!(transport.position(), secs(2.));
+    assert_eq!(advance(&mut transport, 0.5), secs(2.5));
+
+    transport.key(TransportKey::Space, false);
+    assert!(transport.is_paused());
+    assert_eq!(advance(&mut transport, 1.), secs(2.5));
+
+    transport.key(TransportKey::Space, false);
+    assert_eq!(advance(&mut transport, 1.), secs(3.5));
+
+    transport.set_paused(true);
+    assert_eq!(advance(&mut transport, 1.), secs(3.5));
+  }
+
+  #[test]
+  fn seek() {
+    let mut transport = transport(5.);
+
+    transport.key(TransportKey::Right, false);
+    assert_eq!(transport.position(), secs(6.));
+    transport.key(TransportKey::Right, true);
+    assert_eq!(transport.position(), secs(16.));
+    transport.key(TransportKey::Left, true);
+    transport.key(TransportKey::Left, true);
+    assert_eq!(transport.position(), secs(0.));
+  }
+
+  #[test]
+  fn seek_before_first_tick() {
+    let mut transport = Transport::new(ManualClock::new(secs(3.)));
+
+    transport.seek(secs(10.));
+    assert_eq!(transport.tick(), secs(10.));
+    assert_eq!(advance(&mut transport, 1.), secs(11.));
+  }
+
+  #[test]
+  fn speed() {
+    let mut transport = transport(0.);
+
+    for _ in 0..10 {
+      transport.key(TransportKey::Up, false);
+    }
+
+    assert_eq!(transport.speed(), 4.);
+    assert_eq!(advance(&mut transport, 1.), secs(4.));
+
+    for _ in 0..10 {
+      transport.key(TransportKey::Down, false);
+    }
+
+    assert_eq!(transport.speed(), 0.25);
+    assert_eq!(advance(&mut transport, 1.), secs(4.25));
+
+    transport.key(TransportKey::Backspace, false);
+    assert_eq!(transport.speed(), 1.);
+
+    transport.set_speed(3.5);
+    assert_eq!(transport.speed(), 4.);
+    transport.set_speed(0.3);
+    assert_eq!(transport.speed(), 0.25);
+  }
+
+  #[test]
+  fn markers() {
+    let mut transport = transport(5.);
+    transport.set_markers(vec![secs(12.5), secs(2.), secs(8.)]);
+
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(8.));
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(12.5));
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(12.5));
+    transport.key(TransportKey::PageUp, false);
+    transport.key(TransportKey::PageUp, false);
+    assert_eq!(transport.position(), secs(2.));
+    transport.key(TransportKey::PageUp, false);
+    assert_eq!(transport.position(), secs(2.));
+  }
+
+  #[test]
+  fn non_finite_markers_are_ignored() {
+    let mut transport = transport(5.);
+    transport.set_markers(vec![secs(8.), secs(f64::NAN), secs(2.), secs(f64::INFINITY)]);
+
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(8.));
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(8.));
+    transport.key(TransportKey::PageUp, false);
+    assert_eq!(transport.position(), secs(2.));
+  }
+
+  #[test]
+  fn loop_region() {
+    let mut transport = transport(4.);
+
+    transport.key(TransportKey::B, false);
+    transport.seek(secs(2.));
+    transport.key(TransportKey::A, false);
+    assert_eq!(transport.loop_region(), Some((secs(2.), secs(4.))));
+
+    transport.take_jump();
+    assert_eq!(advance(&mut transport, 1.5), secs(3.5));
+    assert_eq!(transport.take_jump(), None);
+    assert_eq!(advance(&mut transport, 1.), secs(2.5));
+    assert_eq!(transport.take_jump(), Some(Jump::Loop { end: secs(4.), start: secs(2.) }));
+
+    // seeking past the loop region leaves it
+    transport.seek(secs(10.));
+    assert_eq!(advance(&mut transport, 1.), secs(11.));
+    assert_eq!(transport.take_jump(), Some(Jump::Seek));
+
+    transport.key(TransportKey::C, false);
+    assert_eq!(transport.loop_region(), None);
+  }
+
+  #[test]
+  fn wrap_at() {
+    let mut transport = Transport::new(ManualClock::new(secs(1.))).wrap_at(Some(secs(10.)));
+    transport.tick();
+    transport.set_markers(vec![secs(2.), secs(8.)]);
+
+    transport.take_jump();
+    assert_eq!(advance(&mut transport, 9.5), secs(0.5));
+    assert_eq!(transport.take_jump(), Some(Jump::Loop { end: secs(10.), start: secs(0.) }));
+
+    // navigation works on the wrapped position
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(2.));
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(8.));
+    assert_eq!(advance(&mut transport, 3.), secs(1.));
+    transport.key(TransportKey::PageUp, false);
+    assert_eq!(transport.position(), secs(1.));
+    transport.key(TransportKey::PageDown, false);
+    assert_eq!(transport.position(), secs(2.));
+
+    transport.seek(secs(12.));
+    assert_eq!(transport.position(), secs(2.));
+
+    // so do loop points
+    transport.key(TransportKey::A, false);
+    transport.seek(secs(4.));
+    transport.key(TransportKey::B, false);
+    assert_eq!(transport.loop_region(), Some((secs(2.), secs(4.))));
+    transport.seek(secs(3.));
+    assert_eq!(advance(&mut transport, 1.5), secs(2.5));
+  }
+
+  /// Play a transport for a number of 1s steps, firing the cues crossed on the way.
+  fn play(transport: &mut Transport<ManualClock>, track: &MarkerTrack<()>, steps: usize) -> Vec<String> {
+    let mut cursor = CueCursor::new();
+    let mut fired = Vec::new();
+
+    for _ in 0..steps {
+      let t = advance(transport, 1.);
+
+      match transport.take_jump() {
+        Some(Jump::Seek) => cursor.reset(),
+        Some(Jump::Loop { end, start }) => cursor.loop_back(end, start),
+        None => ()
+      }
+
+      fired.extend(cursor.advance(track, t).map(|cue| cue.name.clone()));
+    }
+
+    fired
+  }
+
+  #[test]
+  fn cues_in_loop() {
+    let track: MarkerTrack<()> =
+      vec![("intro", 0.5), ("a", 2.5), ("b", 3.5), ("outro", 8.5)]
+        .into_iter()
+        .map(|(name, t)| Cue::new(name, secs(t), ()))
+        .collect();
+
+    // every pass over the loop region fires its cues, and only them; playing starts past “a”
+    for &wrap_at in &[None, Some(secs(10.))] {
+      let mut transport = transport(0.).wrap_at(wrap_at);
+      transport.seek(secs(2.));
+      transport.key(TransportKey::A, false);
+      transport.seek(secs(4.));
+      transport.key(TransportKey::B, false);
+      transport.seek(secs(2.));
+
+      assert_eq!(play(&mut transport, &track, 7), vec!["b", "a", "b", "a", "b", "a"]);
+    }
+
+    // wrapping around fires the cues up to the end and from the start
+    let mut transport = transport(7.).wrap_at(Some(secs(10.)));
+    assert_eq!(play(&mut transport, &track, 6), vec!["outro", "intro", "a"]);
+  }
+
+  #[test]
+  fn loop_points_are_ordered() {
+    let mut transport = transport(6.);
+
+    transport.key(TransportKey::A, false);
+    transport.seek(secs(3.));
+    transport.key(TransportKey::B, false);
+
+    assert_eq!(transport.loop_region(), Some((secs(3.), secs(6.))));
+  }
+
+  /// Playback clock, moved by hand.
+  #[derive(Debug, Default)]
+  struct Tape {
+    t: f64,
+    paused: bool,
+    speed: f64
+  }
+
+  impl Clock for Tape {
+    fn tick(&mut self) -> Time {
+      secs(self.t)
+    }
+
+    fn is_playback(&self) -> bool {
+      true
+    }
+
+    fn seek(&mut self, t: Time) {
+      self.t = t.as_secs();
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+      self.paused = paused;
+    }
+
+    fn set_speed(&mut self, speed: f64) {
+      self.speed = speed;
+    }
+  }
+
+  #[test]
+  fn playback_clock() {
+    let mut transport = Transport::new(Tape::default());
+
+    transport.seek(secs(3.));
+    assert_eq!(transport.get_ref().t, 3.);
+    assert_eq!(transport.tick(), secs(3.));
+
+    // the position comes from the clock, already scaled by its speed
+    transport.key(TransportKey::Up, false);
+    assert_eq!(transport.get_ref().speed, 2.);
+    transport.get_mut().t = 5.;
+    assert_eq!(transport.tick(), secs(5.));
+
+    transport.key(TransportKey::Space, false);
+    assert!(transport.get_ref().paused);
+    transport.set_paused(false);
+    assert!(!transport.get_ref().paused);
+
+    transport.key(TransportKey::Left, true);
+    assert_eq!(transport.get_ref().t, 0.);
+
+    // the loop region seeks the clock back
+    transport.seek(secs(1.));
+    transport.key(TransportKey::A, false);
+    transport.seek(secs(2.));
+    transport.key(TransportKey::B, false);
+    transport.seek(secs(1.5));
+    transport.tick();
+
+    transport.get_mut().t = 2.25;
+    assert_eq!(transport.tick(), secs(1.25));
+    assert_eq!(transport.get_ref().t, 1.25);
+  }
+
+  #[test]
+  fn playback_clock_wrap_at() {
+    let mut transport = Transport::new(Tape::default()).wrap_at(Some(secs(10.)));
+
+    transport.get_mut().t = 9.5;
+    assert_eq!(transport.tick(), secs(9.5));
+    transport.take_jump();
+
+    // the clock is sought back along with the position
+    transport.get_mut().t = 10.5;
+    assert_eq!(transport.tick(), secs(0.5));
+    assert_eq!(transport.get_ref().t, 0.5);
+    assert_eq!(transport.take_jump(), Some(Jump::Loop { end: secs(10.), start: secs(0.) }));
+
+    transport.seek(secs(12.));
+    assert_eq!(transport.get_ref().t, 2.);
+    assert_eq!(transport.tick(), secs(2.));
+  }
+}
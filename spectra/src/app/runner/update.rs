@@ -0,0 +1,226 @@
+//! Update scheduling.
+//!
+//! Runners call [`Demo::update`] before rendering each frame. By default, the demo is updated once
+//! per frame with the time elapsed since the previous frame. A demo can also ask for a fixed update
+//! rate – for determinism of simulations – in which case it’s updated as many times as needed to
+//! catch up with the frame time, and the rendering gets an interpolation factor between the last
+//! two updates.
+//!
+//! [`Demo::update`]: crate::app::demo::Demo::update
+
+use crate::time::Time;
+
+/// Updates to perform for a frame.
+///
+/// This is an iterator over the time and time delta of each update, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Steps {
+  /// Update to perform before the regular ones – the only one with a variable update rate.
+  first: Option<(Time, Time)>,
+  /// Time of the previous regular update.
+  last: Time,
+  step: Time,
+  /// Number of regular updates left.
+  remaining: u32,
+  interpolation: f64
+}
+
+impl Steps {
+  /// Progress, in [0; 1], of the frame time between the last update and the next one. Always 0
+  /// with a variable update rate.
+  pub fn interpolation(&self) -> f64 {
+    self.interpolation
+  }
+}
+
+impl Iterator for Steps {
+  type Item = (Time, Time);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(first) = self.first.take() {
+      return Some(first);
+    }
+
+    if self.remaining == 0 {
+      return None;
+    }
+
+    self.remaining -= 1;
+    self.last += self.step;
+    Some((self.last, self.step))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let len = self.first.iter().count() + self.remaining as usize;
+    (len, Some(len))
+  }
+}
+
+impl ExactSizeIterator for Steps {}
+
+/// Schedule updates for frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stepper {
+  /// Duration of an update, with a fixed update rate.
+  step: Option<Time>,
+  /// Time of the last update.
+  last: Option<Time>
+}
+
+impl Stepper {
+  /// Maximum number of updates per frame with a fixed update rate. If more updates are needed, the
+  /// simulation drops the extra time instead of trying to catch up.
+  pub const MAX_STEPS: u32 = 16;
+
+  /// One update per frame.
+  pub fn variable() -> Self {
+    Stepper { step: None, last: None }
+  }
+
+  /// `rate` updates per second.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `rate` is 0.
+  pub fn fixed(rate: u32) -> Self {
+    assert!(rate > 0, "a fixed update rate must be non-zero");
+    Stepper { step: Some(Time::from_secs(1. / rate as f64)), last: None }
+  }
+
+  /// Create a stepper with an optional fixed update rate.
+  pub fn new(rate: Option<u32>) -> Self {
+    rate.map_or_else(Stepper::variable, Stepper::fixed)
+  }
+
+  /// Get the updates to perform to reach `t`.
+  ///
+  /// With a fixed update rate, the first frame gets exactly one update at `t`, with a time delta of
+  /// one step. So does any frame going back in time – after a seek, a wrap-around or while
+  /// rewinding – but with the negative time delta since the last update.
+  pub fn advance(&mut self, t: Time) -> Steps {
+    let step = match self.step {
+      Some(step) => step,
+
+      None => {
+        let dt = self.last.replace(t).map_or(Time::default(), |last| t - last);
+        let first = Some((t, dt));
+        return Steps { first, last: t, step: Time::default(), remaining: 0, interpolation: 0. };
+      }
+    };
+
+    let (start, first) = match self.last {
+      Some(last) if last <= t => (last, None),
+      Some(last) => (t, Some((t, t - last))),
+      None => (t, Some((t, step)))
+    };
+    let mut last = start;
+    let mut updates = u32::from(first.is_some());
+
+    // tolerance absorbing the rounding errors accumulated by the updates
+    let epsilon = Time::from_secs(1e-9);
+
+    // the updates are only counted here; the iterator accumulates the steps the same way
+    while last + step <= t + epsilon {
+      if updates == Self::MAX_STEPS {
+        // drop the extra time
+        last = t;
+        break;
+      }
+
+      last += step;
+      updates += 1;
+    }
+
+    self.last = Some(last);
+
+    Steps {
+      first,
+      last: start,
+      step,
+      remaining: updates - u32::from(first.is_some()),
+      interpolation: ((t - last) / step).clamp(0., 1.)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(millis: f64) -> Time {
+    Time::from_millis(millis)
+  }
+
+  fn update_millis(steps: &Steps) -> Vec<(f64, f64)> {
+    steps.clone().map(|(t, dt)| (t.as_millis().round(), dt.as_millis().round())).collect()
+  }
+
+  #[test]
+  fn variable() {
+    let mut stepper = Stepper::variable();
+
+    let steps = stepper.advance(ms(100.));
+    assert_eq!(steps.interpolation(), 0.);
+    assert_eq!(steps.collect::<Vec<_>>(), vec![(ms(100.), ms(0.))]);
+    assert_eq!(update_millis(&stepper.advance(ms(116.))), vec![(116., 16.)]);
+    assert_eq!(update_millis(&stepper.advance(ms(116.))), vec![(116., 0.)]);
+    assert_eq!(update_millis(&stepper.advance(ms(50.))), vec![(50., -66.)]);
+  }
+
+  #[test]
+  fn fixed() {
+    let mut stepper = Stepper::fixed(100);
+
+    let steps = stepper.advance(ms(0.));
+    assert_eq!(update_millis(&steps), vec![(0., 10.)]);
+    assert_eq!(steps.interpolation(), 0.);
+
+    let steps = stepper.advance(ms(25.));
+    assert_eq!(update_millis(&steps), vec![(10., 10.), (20., 10.)]);
+    assert!((steps.interpolation() - 0.5).abs() < 1e-9);
+
+    let steps = stepper.advance(ms(28.));
+    assert_eq!(steps.len(), 0);
+    assert!((steps.interpolation() - 0.8).abs() < 1e-9);
+
+    let steps = stepper.advance(ms(31.));
+    assert_eq!(update_millis(&steps), vec![(30., 10.)]);
+  }
+
+  #[test]
+  fn fixed_going_backward() {
+    let mut stepper = Stepper::fixed(100);
+
+    stepper.advance(ms(500.));
+    let steps = stepper.advance(ms(100.));
+
+    assert_eq!(update_millis(&steps), vec![(100., -400.)]);
+    assert_eq!(update_millis(&stepper.advance(ms(110.))), vec![(110., 10.)]);
+  }
+
+  #[test]
+  fn fixed_rewinding() {
+    let mut stepper = Stepper::fixed(100);
+
+    stepper.advance(ms(100.));
+    assert_eq!(update_millis(&stepper.advance(ms(95.))), vec![(95., -5.)]);
+    assert_eq!(update_millis(&stepper.advance(ms(90.))), vec![(90., -5.)]);
+    assert_eq!(update_millis(&stepper.advance(ms(90.))), vec![]);
+
+    // playing forward again
+    assert_eq!(update_millis(&stepper.advance(ms(100.))), vec![(100., 10.)]);
+  }
+
+  #[test]
+  fn fixed_drops_extra_time() {
+    let mut stepper = Stepper::fixed(100);
+
+    stepper.advance(ms(0.));
+    let steps = stepper.advance(ms(1000.));
+
+    assert_eq!(steps.len() as u32, Stepper::MAX_STEPS);
+    assert_eq!(steps.interpolation(), 0.);
+    assert_eq!(steps.last().unwrap().0, ms(160.));
+    assert_eq!(update_millis(&stepper.advance(ms(1010.))), vec![(1010., 10.)]);
+  }
+}
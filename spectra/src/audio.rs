@@ -0,0 +1,69 @@
+//! Audio.
+//!
+//! A demo’s soundtrack is decoded from OGG Vorbis or WAV into a [`Soundtrack`], and played by a
+//! [`Player`] through an output [backend](backend::Backend). The player is a
+//! [`Clock`](crate::time::clock::Clock): its playback position is the time of the demo, so that the
+//! visuals stay locked to the music, and it follows the seeks, pauses and speed changes of the
+//! runners’ transports.
+//!
+//! The soundtrack can be [analyzed](analysis) at any time, for audio-reactive visuals, and its
+//! [beats and onsets](beat) can be detected ahead of time. Soundtracks can also be
+//! [synthesized](synth) from a song description instead of decoded.
+
+pub mod analysis;
+pub mod backend;
+pub mod beat;
+pub mod decode;
+pub mod player;
+pub mod synth;
+
+use std::fmt;
+
+pub use self::analysis::{Analysis, Analyzer, SpectrumTexture};
+pub use self::decode::Soundtrack;
+pub use self::player::Player;
+
+/// Possible audio errors.
+#[derive(Debug)]
+pub enum Error {
+  CannotOpen(String),
+  UnsupportedFormat(String),
+  CannotDecode(String),
+  CannotOpenOutput(String),
+  InvalidSong(String)
+}
+
+impl Error {
+  pub(crate) fn cannot_open<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotOpen(reason.into())
+  }
+
+  pub(crate) fn unsupported_format<R>(reason: R) -> Self where R: Into<String> {
+    Error::UnsupportedFormat(reason.into())
+  }
+
+  pub(crate) fn cannot_decode<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotDecode(reason.into())
+  }
+
+  #[cfg_attr(not(feature = "cpal"), allow(dead_code))]
+  pub(crate) fn cannot_open_output<R>(reason: R) -> Self where R: Into<String> {
+    Error::CannotOpenOutput(reason.into())
+  }
+
+  pub(crate) fn invalid_song<R>(reason: R) -> Self where R: Into<String> {
+    Error::InvalidSong(reason.into())
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Error::CannotOpen(ref reason) => write!(f, "cannot open soundtrack: {}", reason),
+      Error::UnsupportedFormat(ref reason) => write!(f, "unsupported soundtrack format: {}", reason),
+      Error::CannotDecode(ref reason) => write!(f, "cannot decode soundtrack: {}", reason),
+      Error::CannotOpenOutput(ref reason) => write!(f, "cannot open audio output: {}", reason),
+      Error::InvalidSong(ref reason) => write!(f, "invalid song: {}", reason),
+    }
+  }
+}
@@ -0,0 +1,417 @@
+//! Soundtrack analysis, for audio-reactive visuals.
+//!
+//! An [`Analyzer`] computes the spectrum, the loudness and the onset strength of a soundtrack at
+//! any given time. The analysis only depends on the decoded samples around that time: the same
+//! values are obtained offline – when exporting, for instance – and in real time from the
+//! [player](crate::audio::Player)’s position, whatever the seeks made in between.
+//!
+//! Results are exposed to render blocks as [built-ins](BuiltIn): the scalar ones through
+//! [`Analysis::built_in`], and the spectrum through a [`SpectrumTexture`].
+
+use luminance::context::GraphicsContext;
+use luminance::pixel::R32F;
+use luminance::texture::{Dim1, Flat, GenMipmaps, Sampler, Texture, TextureError};
+use std::f32::consts::PI;
+
+use crate::audio::Soundtrack;
+use crate::render::input::BuiltIn;
+use crate::time::Time;
+
+/// Default number of frames analyzed at once.
+pub const DEFAULT_WINDOW: usize = 1024;
+
+/// Default upper bound of the bass band, in Hz.
+pub const DEFAULT_BASS_CUTOFF: f32 = 250.;
+
+/// Default upper bound of the mid band, in Hz.
+pub const DEFAULT_MID_CUTOFF: f32 = 4000.;
+
+/// Soundtrack analyzer.
+///
+/// The window of analyzed frames ends at the requested time, so that the analysis reflects what
+/// has just been heard. Channels are mixed down to mono and silence is assumed before the start
+/// and after the end of the soundtrack.
+#[derive(Clone, Debug)]
+pub struct Analyzer {
+  /// Hann window.
+  window: Vec<f32>,
+  /// Sum of the squared window coefficients, to normalize energies.
+  window_energy: f32,
+  bass_cutoff: f32,
+  mid_cutoff: f32
+}
+
+impl Analyzer {
+  /// Analyze windows of a given number of frames.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the window size is not a power of two greater than 1.
+  pub fn new(window: usize) -> Self {
+    assert!(window > 1 && window.is_power_of_two(), "an analysis window of {} frames", window);
+
+    let window = (0..window).map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / window as f32).cos()).collect::<Vec<_>>();
+    let window_energy = window.iter().map(|w| w * w).sum();
+
+    Analyzer {
+      window,
+      window_energy,
+      bass_cutoff: DEFAULT_BASS_CUTOFF,
+      mid_cutoff: DEFAULT_MID_CUTOFF
+    }
+  }
+
+  /// Change the frequencies, in Hz, splitting the bass, mid and high bands.
+  pub fn cutoffs(self, bass: f32, mid: f32) -> Self {
+    Analyzer { bass_cutoff: bass, mid_cutoff: mid, ..self }
+  }
+
+  /// Number of frames analyzed at once.
+  pub fn window(&self) -> usize {
+    self.window.len()
+  }
+
+  /// Number of frames between the two windows compared to compute the onset strength.
+  pub fn hop(&self) -> usize {
+    self.window.len() / 4
+  }
+
+  /// Number of bins of the spectrum.
+  pub fn bins(&self) -> usize {
+    self.window.len() / 2
+  }
+
+  /// Center frequency, in Hz, of a bin of the spectrum for a given sample rate.
+  pub fn bin_frequency(&self, bin: usize, sample_rate: u32) -> f32 {
+    bin as f32 * sample_rate as f32 / self.window.len() as f32
+  }
+
+  /// Analyze a soundtrack at a given time.
+  pub fn analyze(&self, soundtrack: &Soundtrack, t: Time) -> Analysis {
+    let end = (t.as_secs() * soundtrack.sample_rate() as f64).round() as i64;
+    let samples = self.mono(soundtrack, end);
+    let (spectrum, powers) = self.spectrum(&samples);
+    let (previous, _) = self.spectrum(&self.mono(soundtrack, end - self.hop() as i64));
+
+    // energy of each band, normalized so that a band’s level is the RMS of its part of the signal
+    let scale = 2. / (self.window.len() as f32 * self.window_energy);
+    let band = |low: f32, high: f32| {
+      let energy = powers
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|&(bin, _)| {
+          let freq = self.bin_frequency(bin, soundtrack.sample_rate());
+          freq >= low && freq < high
+        })
+        .map(|(_, power)| power)
+        .sum::<f32>();
+
+      (energy * scale).sqrt()
+    };
+
+    let rms = (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+    let peak = samples.iter().fold(0., |peak: f32, x| peak.max(x.abs()));
+    let onset = spectrum.iter().zip(&previous).map(|(a, b)| (a - b).max(0.)).sum();
+
+    Analysis {
+      bass: band(0., self.bass_cutoff),
+      mid: band(self.bass_cutoff, self.mid_cutoff),
+      high: band(self.mid_cutoff, f32::INFINITY),
+      spectrum,
+      rms,
+      peak,
+      onset
+    }
+  }
+
+  /// Frames of the window ending at a given frame, mixed down to mono.
+  fn mono(&self, soundtrack: &Soundtrack, end: i64) -> Vec<f32> {
+    let channels = soundtrack.channels() as usize;
+    let frames = soundtrack.frames() as i64;
+    let start = end - self.window.len() as i64;
+
+    (start..end)
+      .map(|index| {
+        if index < 0 || index >= frames {
+          0.
+        } else {
+          soundtrack.frame(index as usize).iter().sum::<f32>() / channels as f32
+        }
+      })
+      .collect()
+  }
+
+  /// Amplitude spectrum and power of each bin of a window of samples.
+  ///
+  /// Amplitudes are normalized so that a sine of amplitude 1 centered on a bin gives 1.
+  fn spectrum(&self, samples: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    let mut re = samples.iter().zip(&self.window).map(|(x, w)| x * w).collect::<Vec<_>>();
+    let mut im = vec![0.; re.len()];
+
+    fft(&mut re, &mut im);
+
+    let powers = re.iter().zip(&im).take(self.bins()).map(|(re, im)| re * re + im * im).collect::<Vec<f32>>();
+    let scale = 2. / self.window.iter().sum::<f32>();
+    let amplitudes = powers.iter().map(|power| power.sqrt() * scale).collect();
+
+    (amplitudes, powers)
+  }
+}
+
+impl Default for Analyzer {
+  fn default() -> Self {
+    Analyzer::new(DEFAULT_WINDOW)
+  }
+}
+
+/// Analysis of a soundtrack at a given time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+  /// Amplitude of each frequency bin, from 0Hz up to half the sample rate.
+  pub spectrum: Vec<f32>,
+  /// RMS level of the bass band.
+  pub bass: f32,
+  /// RMS level of the mid band.
+  pub mid: f32,
+  /// RMS level of the high band.
+  pub high: f32,
+  /// RMS level of the signal.
+  pub rms: f32,
+  /// Peak level of the signal.
+  pub peak: f32,
+  /// Onset strength – the spectral flux: how much the spectrum grew since the previous hop.
+  pub onset: f32
+}
+
+impl Analysis {
+  /// Value of a scalar built-in.
+  ///
+  /// [`BuiltIn::Spectrum`] is a 1D texture of the [`spectrum`](Analysis::spectrum) – see
+  /// [`SpectrumTexture`]; `None` is returned for it, as well as for the built-ins not related to
+  /// audio.
+  pub fn built_in(&self, built_in: BuiltIn) -> Option<f32> {
+    match built_in {
+      BuiltIn::Bass => Some(self.bass),
+      BuiltIn::Mid => Some(self.mid),
+      BuiltIn::High => Some(self.high),
+      BuiltIn::Loudness => Some(self.rms),
+      BuiltIn::Peak => Some(self.peak),
+      BuiltIn::Onset => Some(self.onset),
+      _ => None
+    }
+  }
+}
+
+/// 1D texture of the spectrum, bound to [`BuiltIn::Spectrum`].
+///
+/// The texture has one `R32F` texel per frequency bin of the analyzer it’s created for, holding
+/// the bin’s amplitude.
+pub struct SpectrumTexture {
+  texture: Texture<Flat, Dim1, R32F>
+}
+
+impl SpectrumTexture {
+  /// Create a texture holding the spectra computed by an analyzer.
+  pub fn new<C>(context: &mut C, analyzer: &Analyzer) -> Result<Self, TextureError> where C: GraphicsContext {
+    let texture = Texture::new(context, analyzer.bins() as u32, 0, &Sampler::default())?;
+    Ok(SpectrumTexture { texture })
+  }
+
+  /// Upload the spectrum of an analysis.
+  ///
+  /// # Panics
+  ///
+  /// If the analysis doesn’t come from an analyzer with the same number of bins as the one the
+  /// texture was created for.
+  pub fn upload(&self, analysis: &Analysis) {
+    assert_eq!(
+      analysis.spectrum.len(),
+      self.texture.size() as usize,
+      "spectrum of {} bins uploaded to a texture of {} texels",
+      analysis.spectrum.len(),
+      self.texture.size()
+    );
+
+    self.texture.upload(GenMipmaps::No, &analysis.spectrum);
+  }
+
+  /// The texture, to bind in pipelines.
+  pub fn texture(&self) -> &Texture<Flat, Dim1, R32F> {
+    &self.texture
+  }
+}
+
+/// In-place radix-2 FFT.
+///
+/// The length of the buffers must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+  let n = re.len();
+
+  // bit-reversal permutation
+  let mut j = 0;
+  for i in 1..n {
+    let mut bit = n >> 1;
+
+    while j & bit != 0 {
+      j ^= bit;
+      bit >>= 1;
+    }
+
+    j |= bit;
+
+    if i < j {
+      re.swap(i, j);
+      im.swap(i, j);
+    }
+  }
+
+  // butterflies
+  let mut len = 2;
+  while len <= n {
+    let angle = -2. * std::f64::consts::PI / len as f64;
+
+    for start in (0..n).step_by(len) {
+      for k in 0..len / 2 {
+        let (sin, cos) = (angle * k as f64).sin_cos();
+        let (a, b) = (start + k, start + k + len / 2);
+        let tre = re[b] * cos as f32 - im[b] * sin as f32;
+        let tim = re[b] * sin as f32 + im[b] * cos as f32;
+
+        re[b] = re[a] - tre;
+        im[b] = im[a] - tim;
+        re[a] += tre;
+        im[a] += tim;
+      }
+    }
+
+    len <<= 1;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RATE: u32 = 16_000;
+
+  /// A mono sine of a given frequency and amplitude, lasting a second.
+  fn sine(freq: f32, amplitude: f32) -> Vec<f32> {
+    (0..RATE).map(|i| amplitude * (2. * PI * freq * i as f32 / RATE as f32).sin()).collect()
+  }
+
+  fn close(a: f32, b: f32, eps: f32) -> bool {
+    (a - b).abs() < eps
+  }
+
+  #[test]
+  fn fft_of_impulse_and_cosine() {
+    let mut re = vec![1., 0., 0., 0., 0., 0., 0., 0.];
+    let mut im = vec![0.; 8];
+    fft(&mut re, &mut im);
+    assert!(re.iter().all(|&x| close(x, 1., 1e-6)) && im.iter().all(|&x| close(x, 0., 1e-6)));
+
+    let mut re = (0..8).map(|i| (2. * PI * i as f32 / 8.).cos()).collect::<Vec<_>>();
+    let mut im = vec![0.; 8];
+    fft(&mut re, &mut im);
+    assert!(close(re[1], 4., 1e-5) && close(re[7], 4., 1e-5));
+    assert!(re[2..7].iter().chain(&im).all(|&x| close(x, 0., 1e-5)));
+  }
+
+  #[test]
+  fn sine_spectrum() {
+    // 500Hz is the center of the 32nd bin
+    let analyzer = Analyzer::new(1024);
+    let analysis = analyzer.analyze(&Soundtrack::new(RATE, 1, sine(500., 0.5)), Time::from_secs(0.5));
+    let (bin, amplitude) = analysis
+      .spectrum
+      .iter()
+      .enumerate()
+      .fold((0, 0.), |best, (bin, &amp)| if amp > best.1 { (bin, amp) } else { best });
+
+    assert_eq!(analysis.spectrum.len(), 512);
+    assert_eq!((bin, analyzer.bin_frequency(bin, RATE)), (32, 500.));
+    assert!(close(amplitude, 0.5, 1e-3), "amplitude: {}", amplitude);
+    assert!(analysis.spectrum[40..].iter().all(|&amp| amp < 1e-3));
+  }
+
+  #[test]
+  fn bands_and_loudness() {
+    let analyzer = Analyzer::default();
+    let rms = 0.8 / 2f32.sqrt();
+
+    for &(freq, band) in &[(100., 0), (1000., 1), (6000., 2)] {
+      let analysis = analyzer.analyze(&Soundtrack::new(RATE, 1, sine(freq, 0.8)), Time::from_secs(0.5));
+      let levels = [analysis.bass, analysis.mid, analysis.high];
+
+      assert!(close(levels[band], rms, 0.01), "{}Hz: {:?}", freq, levels);
+      assert!(levels.iter().enumerate().all(|(i, &l)| i == band || l < 0.01), "{}Hz: {:?}", freq, levels);
+      assert!(close(analysis.rms, rms, 0.01));
+      assert!(close(analysis.peak, 0.8, 0.01));
+    }
+
+    let high = Analyzer::default().cutoffs(250., 2000.);
+    let analysis = high.analyze(&Soundtrack::new(RATE, 1, sine(3000., 0.8)), Time::from_secs(0.5));
+    assert!(close(analysis.high, rms, 0.01) && analysis.mid < 0.01);
+  }
+
+  #[test]
+  fn stereo_is_mixed_down() {
+    let samples = sine(500., 0.5).into_iter().flat_map(|x| vec![x, x]).collect();
+    let stereo = Analyzer::default().analyze(&Soundtrack::new(RATE, 2, samples), Time::from_secs(0.25));
+    let mono = Analyzer::default().analyze(&Soundtrack::new(RATE, 1, sine(500., 0.5)), Time::from_secs(0.25));
+
+    assert_eq!(stereo, mono);
+  }
+
+  #[test]
+  fn silence_around_the_soundtrack() {
+    let analyzer = Analyzer::default();
+    let soundtrack = Soundtrack::new(RATE, 1, sine(500., 0.5));
+
+    for &t in &[-1., 0., 2.] {
+      let analysis = analyzer.analyze(&soundtrack, Time::from_secs(t));
+      assert_eq!((analysis.rms, analysis.peak, analysis.onset), (0., 0., 0.));
+      assert!(analysis.spectrum.iter().all(|&amp| amp == 0.));
+    }
+  }
+
+  #[test]
+  fn onset() {
+    // silence, then a sine from 0.5s
+    let mut samples = vec![0.; RATE as usize / 2];
+    samples.extend_from_slice(&sine(500., 0.5)[..RATE as usize / 2]);
+    let soundtrack = Soundtrack::new(RATE, 1, samples);
+    let analyzer = Analyzer::default();
+
+    let attack = analyzer.analyze(&soundtrack, Time::from_secs(0.5 + 128. / RATE as f64)).onset;
+    let steady = analyzer.analyze(&soundtrack, Time::from_secs(0.8)).onset;
+    let silent = analyzer.analyze(&soundtrack, Time::from_secs(0.4)).onset;
+
+    assert!(attack > 0.1, "attack: {}", attack);
+    assert!(steady < 0.01 * attack, "steady: {}", steady);
+    assert_eq!(silent, 0.);
+  }
+
+  #[test]
+  fn analysis_depends_only_on_time() {
+    let analyzer = Analyzer::default();
+    let soundtrack = Soundtrack::new(RATE, 1, sine(440., 0.5));
+    let t = Time::from_secs(0.3);
+
+    let first = analyzer.analyze(&soundtrack, t);
+    analyzer.analyze(&soundtrack, Time::from_secs(0.9));
+    assert_eq!(analyzer.analyze(&soundtrack, t), first);
+  }
+
+  #[test]
+  fn built_ins() {
+    let analysis = Analyzer::default().analyze(&Soundtrack::new(RATE, 1, sine(100., 1.)), Time::from_secs(0.5));
+
+    assert_eq!(analysis.built_in(BuiltIn::Bass), Some(analysis.bass));
+    assert_eq!(analysis.built_in(BuiltIn::Loudness), Some(analysis.rms));
+    assert_eq!(analysis.built_in(BuiltIn::Onset), Some(analysis.onset));
+    assert_eq!(analysis.built_in(BuiltIn::Spectrum), None);
+    assert_eq!(analysis.built_in(BuiltIn::Time), None);
+  }
+}
@@ -0,0 +1,221 @@
+//! Audio output backends.
+//!
+//! A [`Backend`] pulls audio from a [`Source`] – usually from an audio thread – and sends it to an
+//! output. Two backends are available:
+//!
+//!   - [`NullBackend`] pulls audio at the pace of the real time and throws it away. It’s meant for
+//!     machines without audio device, such as CI runners.
+//!   - `CpalBackend`, with the `cpal` feature, plays on the default output device.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio::Error;
+
+pub use crate::audio::player::Source;
+
+/// Format of an output.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Format {
+  /// Number of frames per second.
+  pub sample_rate: u32,
+  pub channels: u16
+}
+
+impl Default for Format {
+  fn default() -> Self {
+    Format { sample_rate: 48_000, channels: 2 }
+  }
+}
+
+/// Audio output.
+pub trait Backend {
+  /// Start pulling audio from a source, until the backend is dropped.
+  fn start(&mut self, source: Source) -> Result<(), Error>;
+}
+
+/// Backend without output.
+#[derive(Debug)]
+pub struct NullBackend {
+  format: Format,
+  block: Duration,
+  stopped: Arc<AtomicBool>
+}
+
+impl NullBackend {
+  /// Pull stereo audio at 48kHz, by blocks of 10ms.
+  pub fn new() -> Self {
+    NullBackend::with_format(Format::default(), Duration::from_millis(10))
+  }
+
+  /// Pull audio in a given format, by blocks of a given duration.
+  pub fn with_format(format: Format, block: Duration) -> Self {
+    NullBackend { format, block, stopped: Arc::new(AtomicBool::new(false)) }
+  }
+}
+
+impl Default for NullBackend {
+  fn default() -> Self {
+    NullBackend::new()
+  }
+}
+
+impl Backend for NullBackend {
+  fn start(&mut self, source: Source) -> Result<(), Error> {
+    let format = self.format;
+    let frames = (format.sample_rate as f64 * self.block.as_secs_f64()).ceil().max(1.) as usize;
+    let block = Duration::from_secs_f64(frames as f64 / format.sample_rate as f64);
+    let stopped = self.stopped.clone();
+
+    thread::spawn(move || {
+      let mut buffer = vec![0.; frames * format.channels as usize];
+      let mut deadline = Instant::now();
+
+      while !stopped.load(Ordering::Relaxed) {
+        source.render(&mut buffer, format);
+
+        deadline += block;
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+      }
+    });
+
+    Ok(())
+  }
+}
+
+impl Drop for NullBackend {
+  fn drop(&mut self) {
+    self.stopped.store(true, Ordering::Relaxed);
+  }
+}
+
+#[cfg(feature = "cpal")]
+pub use self::cpal_backend::CpalBackend;
+
+#[cfg(feature = "cpal")]
+mod cpal_backend {
+  use cpal::{Sample, StreamData, UnknownTypeOutputBuffer};
+  use cpal::traits::{DeviceTrait, EventLoopTrait, HostTrait};
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::mpsc;
+  use std::thread;
+
+  use super::{Backend, Format, Source};
+  use crate::audio::Error;
+
+  /// Backend playing on the default output device, in its default format.
+  ///
+  /// The audio runs in its own thread, which outputs silence once the backend is dropped.
+  #[derive(Debug, Default)]
+  pub struct CpalBackend {
+    stopped: Arc<AtomicBool>
+  }
+
+  impl CpalBackend {
+    pub fn new() -> Self {
+      CpalBackend::default()
+    }
+  }
+
+  impl Backend for CpalBackend {
+    fn start(&mut self, source: Source) -> Result<(), Error> {
+      let (started, result) = mpsc::channel();
+      let stopped = self.stopped.clone();
+
+      thread::spawn(move || {
+        let host = cpal::default_host();
+        let event_loop = host.event_loop();
+        let stream = host
+          .default_output_device()
+          .ok_or_else(|| Error::cannot_open_output("no output device"))
+          .and_then(|device| {
+            let format = device.default_output_format().map_err(|e| Error::cannot_open_output(e.to_string()))?;
+            let stream =
+              event_loop.build_output_stream(&device, &format).map_err(|e| Error::cannot_open_output(e.to_string()))?;
+            event_loop.play_stream(stream).map_err(|e| Error::cannot_open_output(e.to_string()))?;
+
+            Ok(Format { sample_rate: format.sample_rate.0, channels: format.channels })
+          });
+
+        let format = match stream {
+          Ok(format) => format,
+          Err(e) => {
+            let _ = started.send(Err(e));
+            return;
+          }
+        };
+
+        let _ = started.send(Ok(()));
+        let mut block = Vec::new();
+
+        event_loop.run(move |_, data| {
+          let mut buffer = match data {
+            Ok(StreamData::Output { buffer }) => buffer,
+            _ => return
+          };
+
+          let len = match buffer {
+            UnknownTypeOutputBuffer::U16(ref buffer) => buffer.len(),
+            UnknownTypeOutputBuffer::I16(ref buffer) => buffer.len(),
+            UnknownTypeOutputBuffer::F32(ref buffer) => buffer.len()
+          };
+
+          block.resize(len, 0.);
+
+          if stopped.load(Ordering::Relaxed) {
+            block.iter_mut().for_each(|sample| *sample = 0.);
+          } else {
+            source.render(&mut block, format);
+          }
+
+          match buffer {
+            UnknownTypeOutputBuffer::U16(ref mut buffer) => {
+              buffer.iter_mut().zip(&block).for_each(|(out, sample)| *out = sample.to_u16());
+            }
+
+            UnknownTypeOutputBuffer::I16(ref mut buffer) => {
+              buffer.iter_mut().zip(&block).for_each(|(out, sample)| *out = sample.to_i16());
+            }
+
+            UnknownTypeOutputBuffer::F32(ref mut buffer) => buffer.copy_from_slice(&block)
+          }
+        });
+      });
+
+      result.recv().map_err(|_| Error::cannot_open_output("the audio thread died"))?
+    }
+  }
+
+  impl Drop for CpalBackend {
+    fn drop(&mut self) {
+      self.stopped.store(true, Ordering::Relaxed);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::audio::{Player, Soundtrack};
+  use crate::time::Time;
+  use crate::time::clock::Clock;
+
+  use super::*;
+
+  #[test]
+  fn null_backend_plays_in_real_time() {
+    let soundtrack = Soundtrack::new(1000, 1, vec![0.; 10_000]);
+    let backend = NullBackend::with_format(Format { sample_rate: 1000, channels: 1 }, Duration::from_millis(5));
+    let mut player = Player::new(soundtrack, backend).unwrap();
+
+    assert_eq!(player.tick(), Time::from_secs(0.));
+
+    let start = Instant::now();
+    thread::sleep(Duration::from_millis(50));
+    let t = player.tick().as_secs();
+
+    assert!(t > 0. && t <= start.elapsed().as_secs_f64() + 0.01, "position: {}", t);
+  }
+}
@@ -0,0 +1,466 @@
+//! Offline beat and onset detection.
+//!
+//! A [`Detector`] goes through a whole soundtrack with an [`Analyzer`] and estimates:
+//!
+//!   - Its [`Tempo`]: the number of beats per minute and the time of the first beat.
+//!   - Its [`Onsets`] – the attacks, such as kicks, snares and hi-hats – in the bass, mid and high
+//!     bands.
+//!
+//! The detection is deterministic and the result can be turned into a [`MarkerTrack`] with
+//! [`Detection::to_markers`], so that cues don’t have to be placed by hand on every beat. Along
+//! with the tempo, it forms [`Beats`], the file written by the `spectra-beats` tool.
+
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::audio::{Analysis, Analyzer, Soundtrack};
+use crate::time::Time;
+use crate::time::marker::{Cue, MarkerTrack};
+
+/// Name of the cues placed on beats.
+pub const BEAT_CUE: &str = "beat";
+
+/// Name of the cues placed on onsets of the bass band.
+pub const BASS_ONSET_CUE: &str = "onset.bass";
+
+/// Name of the cues placed on onsets of the mid band.
+pub const MID_ONSET_CUE: &str = "onset.mid";
+
+/// Name of the cues placed on onsets of the high band.
+pub const HIGH_ONSET_CUE: &str = "onset.high";
+
+/// Number of hops on each side of an onset within which it must be the strongest.
+const PEAK_RADIUS: usize = 3;
+
+/// Number of hops on each side of an onset used to compute the local mean strength.
+const MEAN_RADIUS: usize = 16;
+
+/// Tempo of a soundtrack.
+///
+/// Tempos are deserialized only if their number of beats per minute is positive.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "RawTempo")]
+pub struct Tempo {
+  /// Beats per minute.
+  pub bpm: f64,
+  /// Time of the first beat, within the first beat period.
+  pub phase: Time
+}
+
+/// Unchecked [`Tempo`], as deserialized.
+#[derive(Deserialize)]
+struct RawTempo {
+  bpm: f64,
+  phase: Time
+}
+
+impl TryFrom<RawTempo> for Tempo {
+  type Error = String;
+
+  fn try_from(raw: RawTempo) -> Result<Self, Self::Error> {
+    if raw.bpm > 0. && raw.bpm.is_finite() {
+      Ok(Tempo { bpm: raw.bpm, phase: raw.phase })
+    } else {
+      Err(format!("invalid tempo: {} BPM", raw.bpm))
+    }
+  }
+}
+
+impl Tempo {
+  /// Duration of a beat.
+  pub fn period(&self) -> Time {
+    Time::from_secs(60. / self.bpm)
+  }
+
+  /// Times of the beats until a given time (excluded).
+  ///
+  /// There are none if the number of beats per minute is not positive.
+  pub fn beats(&self, until: Time) -> impl Iterator<Item = Time> {
+    let (phase, period) = (self.phase, self.period());
+    let valid = self.bpm > 0. && self.bpm.is_finite();
+
+    (0..).map(move |beat| phase + period * beat as f64).take_while(move |&t| valid && t < until)
+  }
+}
+
+/// Onsets of each band, sorted by time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Onsets {
+  pub bass: Vec<Time>,
+  pub mid: Vec<Time>,
+  pub high: Vec<Time>
+}
+
+/// Result of a detection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+  /// Estimated tempo; `None` if the soundtrack doesn’t have any.
+  pub tempo: Option<Tempo>,
+  /// Onsets per band.
+  pub onsets: Onsets,
+  /// Duration of the soundtrack.
+  pub duration: Time
+}
+
+impl Detection {
+  /// Turn the detection into a marker track.
+  ///
+  /// A [`BEAT_CUE`] is placed on every beat of the soundtrack and a [`BASS_ONSET_CUE`],
+  /// [`MID_ONSET_CUE`] or [`HIGH_ONSET_CUE`] on every onset.
+  pub fn to_markers(&self) -> MarkerTrack<()> {
+    let beats = self.tempo.iter().flat_map(|tempo| tempo.beats(self.duration)).map(|t| (BEAT_CUE, t));
+    let bands = [
+      (BASS_ONSET_CUE, &self.onsets.bass),
+      (MID_ONSET_CUE, &self.onsets.mid),
+      (HIGH_ONSET_CUE, &self.onsets.high)
+    ];
+    let onsets = bands.iter().flat_map(|&(name, onsets)| onsets.iter().map(move |&t| (name, t)));
+
+    beats.chain(onsets).map(|(name, t)| Cue::new(name, t, ())).collect()
+  }
+
+  /// Turn the detection into its tempo and [marker track](Detection::to_markers).
+  pub fn to_beats(&self) -> Beats {
+    Beats { tempo: self.tempo, markers: self.to_markers() }
+  }
+}
+
+/// Tempo and markers of a soundtrack, as (de)serialized.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Beats {
+  /// Estimated tempo; `None` if the soundtrack doesn’t have any.
+  pub tempo: Option<Tempo>,
+  /// Beats and onsets.
+  pub markers: MarkerTrack<()>
+}
+
+/// Beat and onset detector.
+#[derive(Clone, Debug)]
+pub struct Detector {
+  analyzer: Analyzer,
+  min_bpm: f64,
+  max_bpm: f64,
+  threshold: f32
+}
+
+impl Detector {
+  /// Detect tempos between 60 and 200 BPM with the default analyzer.
+  pub fn new() -> Self {
+    Detector {
+      analyzer: Analyzer::default(),
+      min_bpm: 60.,
+      max_bpm: 200.,
+      threshold: 1.5
+    }
+  }
+
+  /// Change the analyzer – its window size sets the time resolution of the detection.
+  pub fn analyzer(self, analyzer: Analyzer) -> Self {
+    Detector { analyzer, ..self }
+  }
+
+  /// Change the range of tempos to look for.
+  ///
+  /// Tempos are ambiguous up to a factor of two: a narrower range gives more control over the one
+  /// which is picked.
+  pub fn bpm_range(self, min: f64, max: f64) -> Self {
+    Detector { min_bpm: min, max_bpm: max, ..self }
+  }
+
+  /// Change how much stronger than its neighborhood an onset must be. Defaults to 1.5.
+  pub fn threshold(self, threshold: f32) -> Self {
+    Detector { threshold, ..self }
+  }
+
+  /// Detect the tempo and the onsets of a soundtrack.
+  pub fn detect(&self, soundtrack: &Soundtrack) -> Detection {
+    let rate = soundtrack.sample_rate() as f64;
+    let hop = self.analyzer.hop();
+    let hop_secs = hop as f64 / rate;
+    let hops = soundtrack.frames() / hop + 1;
+
+    // an attack is the most visible when it reaches the steepest part of the analysis window
+    let latency = (self.analyzer.window() / 4 + hop / 2) as f64 / rate;
+    let analyses = (0..hops)
+      .map(|i| self.analyzer.analyze(soundtrack, Time::from_secs(i as f64 * hop_secs + latency)))
+      .collect::<Vec<_>>();
+
+    let flux = analyses.iter().map(|analysis| analysis.onset).collect::<Vec<_>>();
+    let band = |level: fn(&Analysis) -> f32| {
+      (0..analyses.len())
+        .map(|i| if i == 0 { 0. } else { (level(&analyses[i]) - level(&analyses[i - 1])).max(0.) })
+        .collect::<Vec<_>>()
+    };
+    let bands = [band(|a| a.bass), band(|a| a.mid), band(|a| a.high)];
+
+    // quiet bands only get the attacks of the other ones: ignore what is weak relative to all bands
+    let floor = 0.1 * bands.iter().flatten().fold(0., |max: f32, &x| max.max(x));
+    let to_times = |envelope: &[f32], floor: f32| {
+      self.peaks(envelope, floor).into_iter().map(|i| Time::from_secs(i * hop_secs)).collect::<Vec<_>>()
+    };
+
+    let onsets = Onsets {
+      bass: to_times(&bands[0], floor),
+      mid: to_times(&bands[1], floor),
+      high: to_times(&bands[2], floor)
+    };
+
+    let flux_floor = 0.1 * flux.iter().fold(0., |max: f32, &x| max.max(x));
+    let tempo = self.tempo(&flux, &self.peaks(&flux, flux_floor), hop_secs);
+
+    Detection { tempo, onsets, duration: soundtrack.duration() }
+  }
+
+  /// Positions, in hops, of the onsets of an envelope, refined between hops.
+  fn peaks(&self, envelope: &[f32], floor: f32) -> Vec<f64> {
+    let len = envelope.len();
+
+    (1..len.saturating_sub(1))
+      .filter(|&i| {
+        let x = envelope[i];
+        let before = i.saturating_sub(PEAK_RADIUS)..i;
+        let after = i + 1..(i + PEAK_RADIUS + 1).min(len);
+        let around = &envelope[i.saturating_sub(MEAN_RADIUS)..(i + MEAN_RADIUS + 1).min(len)];
+        let mean = around.iter().sum::<f32>() / around.len() as f32;
+
+        x > floor
+          && x > self.threshold * mean
+          && before.into_iter().all(|j| envelope[j] < x)
+          && after.into_iter().all(|j| envelope[j] <= x)
+      })
+      .map(|i| i as f64 + parabolic_offset(envelope[i - 1], envelope[i], envelope[i + 1]))
+      .collect()
+  }
+
+  /// Estimate the tempo from the spectral flux and its peaks.
+  ///
+  /// The period is the lag maximizing the autocorrelation of the flux within the BPM range and the
+  /// phase is the offset of the beat grid catching most of the flux. Both are then refined by a
+  /// least squares fit of the grid on the peaks.
+  fn tempo(&self, flux: &[f32], peaks: &[f64], hop_secs: f64) -> Option<Tempo> {
+    let min_lag = (60. / self.max_bpm / hop_secs).floor().max(1.) as usize;
+    let max_lag = ((60. / self.min_bpm / hop_secs).ceil() as usize).min(flux.len().saturating_sub(2));
+
+    if min_lag + 1 >= max_lag {
+      return None;
+    }
+
+    // smoothing the flux gives a chance to periods falling between two hops
+    let smooth = (0..flux.len())
+      .map(|i| 0.5 * flux[i] + 0.25 * (flux[i.saturating_sub(1)] + flux[(i + 1).min(flux.len() - 1)]))
+      .collect::<Vec<_>>();
+    let autocorrelation = |lag: usize| smooth.iter().zip(&smooth[lag..]).map(|(a, b)| a * b).sum::<f32>();
+    let correlations = (min_lag - 1..=max_lag + 1).map(autocorrelation).collect::<Vec<_>>();
+    let argmax = |range: std::ops::Range<usize>| {
+      range.clone().fold(range.start, |best, i| if correlations[i] > correlations[best] { i } else { best })
+    };
+    let mut best = argmax(1..correlations.len() - 1);
+
+    if correlations[best] <= 0. {
+      return None;
+    }
+
+    // a multiple of the period correlates as well as the period itself: prefer the shortest one
+    let lag = |index: usize| min_lag + index - 1;
+    while lag(best) / 2 >= min_lag {
+      let half = lag(best) / 2 + 1 - min_lag;
+      let candidate = argmax(half.saturating_sub(1).max(1)..(half + 2).min(correlations.len() - 1));
+
+      if correlations[candidate] < 0.5 * correlations[best] {
+        break;
+      }
+
+      best = candidate;
+    }
+
+    let offset = parabolic_offset(correlations[best - 1], correlations[best], correlations[best + 1]);
+    let mut period = lag(best) as f64 + offset;
+
+    // coarse phase, in hops
+    let grid = |phase: f64| {
+      (0..)
+        .map(|beat| (phase + beat as f64 * period).round() as usize)
+        .take_while(|&i| i < flux.len())
+        .map(|i| flux[i])
+        .sum::<f32>()
+    };
+    let mut phase = (0..period.ceil() as usize)
+      .map(|phase| phase as f64)
+      .fold((0., -1.), |best, phase| {
+        let score = grid(phase);
+        if score > best.1 { (phase, score) } else { best }
+      })
+      .0;
+
+    // least squares fit of the peaks close to the grid: peak = phase + beat × period
+    let fitted = peaks
+      .iter()
+      .map(|&peak| (((peak - phase) / period).round(), peak))
+      .filter(|&(beat, peak)| (peak - (phase + beat * period)).abs() < period / 4.)
+      .collect::<Vec<_>>();
+
+    if fitted.len() >= 2 {
+      let n = fitted.len() as f64;
+      let (sx, sy) = fitted.iter().fold((0., 0.), |(sx, sy), &(x, y)| (sx + x, sy + y));
+      let (sxx, sxy) = fitted.iter().fold((0., 0.), |(sxx, sxy), &(x, y)| (sxx + x * x, sxy + x * y));
+      let det = n * sxx - sx * sx;
+
+      if det > 0. {
+        period = (n * sxy - sx * sy) / det;
+        phase = (sy - period * sx) / n;
+      }
+    }
+
+    Some(Tempo {
+      bpm: 60. / (period * hop_secs),
+      phase: Time::from_secs(phase.rem_euclid(period) * hop_secs)
+    })
+  }
+}
+
+impl Default for Detector {
+  fn default() -> Self {
+    Detector::new()
+  }
+}
+
+/// Offset, in [-0.5; 0.5], of the top of the parabola going through three equidistant points.
+fn parabolic_offset(a: f32, b: f32, c: f32) -> f64 {
+  let curvature = a - 2. * b + c;
+
+  if curvature == 0. {
+    0.
+  } else {
+    (0.5 * (a - c) / curvature).clamp(-0.5, 0.5) as f64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::f32::consts::PI;
+
+  use super::*;
+
+  const RATE: u32 = 16_000;
+
+  /// A mono click track: 30ms decaying sine bursts of a given frequency on each beat.
+  fn clicks(bpm: f64, phase: f64, secs: f64, freq: f32) -> Soundtrack {
+    let mut samples = vec![0.; (secs * RATE as f64) as usize];
+    let tempo = Tempo { bpm, phase: Time::from_secs(phase) };
+
+    for beat in tempo.beats(Time::from_secs(secs)) {
+      let start = (beat.as_secs() * RATE as f64).round() as usize;
+
+      for (i, sample) in samples[start..].iter_mut().take(RATE as usize * 3 / 100).enumerate() {
+        let t = i as f32 / RATE as f32;
+        *sample += 0.8 * (-t * 100.).exp() * (2. * PI * freq * t).sin();
+      }
+    }
+
+    Soundtrack::new(RATE, 1, samples)
+  }
+
+  fn assert_close(times: &[Time], expected: &[Time], eps: f64) {
+    assert_eq!(times.len(), expected.len(), "{:?}", times);
+
+    for (t, e) in times.iter().zip(expected) {
+      assert!((t.as_secs() - e.as_secs()).abs() < eps, "{} instead of {}", t, e);
+    }
+  }
+
+  #[test]
+  fn tempo_of_click_tracks() {
+    for &(bpm, phase, freq) in &[(120., 0.25, 100.), (87., 0.6, 1000.), (174., 0.1, 6000.)] {
+      let detection = Detector::new().detect(&clicks(bpm, phase, 12., freq));
+      let tempo = detection.tempo.expect("tempo");
+
+      assert!((tempo.bpm - bpm).abs() < 0.2, "{} BPM instead of {}", tempo.bpm, bpm);
+      assert!((tempo.phase.as_secs() - phase).abs() < 0.01, "phase {} instead of {}", tempo.phase, phase);
+    }
+  }
+
+  #[test]
+  fn onsets_per_band() {
+    let secs = 8.;
+    let expected = Tempo { bpm: 120., phase: Time::from_secs(0.25) }.beats(Time::from_secs(secs)).collect::<Vec<_>>();
+
+    let onsets = Detector::new().detect(&clicks(120., 0.25, secs, 100.)).onsets;
+    assert_close(&onsets.bass, &expected, 0.02);
+    assert!(onsets.mid.is_empty() && onsets.high.is_empty(), "{:?}", onsets);
+
+    let onsets = Detector::new().detect(&clicks(120., 0.25, secs, 6000.)).onsets;
+    assert_close(&onsets.high, &expected, 0.02);
+    assert!(onsets.bass.is_empty() && onsets.mid.is_empty(), "{:?}", onsets);
+  }
+
+  #[test]
+  fn silence() {
+    let detection = Detector::new().detect(&Soundtrack::new(RATE, 1, vec![0.; RATE as usize * 4]));
+
+    assert_eq!(detection.tempo, None);
+    assert_eq!(detection.onsets, Onsets::default());
+    assert!(detection.to_markers().cues().is_empty());
+  }
+
+  #[test]
+  fn deterministic() {
+    let soundtrack = clicks(133., 0.3, 6., 440.);
+
+    assert_eq!(Detector::new().detect(&soundtrack), Detector::new().detect(&soundtrack));
+  }
+
+  #[test]
+  fn bpm_range() {
+    let soundtrack = clicks(120., 0.25, 12., 100.);
+    let tempo = Detector::new().bpm_range(45., 90.).detect(&soundtrack).tempo.unwrap();
+
+    assert!((tempo.bpm - 60.).abs() < 0.2, "{} BPM", tempo.bpm);
+  }
+
+  #[test]
+  fn markers() {
+    let detection = Detection {
+      tempo: Some(Tempo { bpm: 60., phase: Time::from_secs(0.5) }),
+      onsets: Onsets { bass: vec![Time::from_secs(0.5)], mid: vec![], high: vec![Time::from_secs(1.)] },
+      duration: Time::from_secs(2.)
+    };
+    let markers = detection.to_markers();
+    let names = markers.cues().iter().map(|cue| (cue.name.as_str(), cue.time.as_secs())).collect::<Vec<_>>();
+
+    assert_eq!(names, vec![("beat", 0.5), ("onset.bass", 0.5), ("onset.high", 1.), ("beat", 1.5)]);
+
+    let json = serde_json::to_string(&markers).unwrap();
+    assert_eq!(serde_json::from_str::<MarkerTrack<()>>(&json).unwrap(), markers);
+  }
+
+  #[test]
+  fn beats_serde() {
+    let detection = Detection {
+      tempo: Some(Tempo { bpm: 120., phase: Time::from_secs(0.25) }),
+      onsets: Onsets { bass: vec![Time::from_secs(0.25)], mid: vec![], high: vec![] },
+      duration: Time::from_secs(1.)
+    };
+    let beats = detection.to_beats();
+    let json = serde_json::to_value(&beats).unwrap();
+
+    assert_eq!(json["tempo"], serde_json::json!({ "bpm": 120., "phase": 0.25 }));
+    assert_eq!(serde_json::from_value::<Beats>(json).unwrap(), beats);
+
+    let none = Detection { tempo: None, ..detection }.to_beats();
+    assert_eq!(serde_json::to_value(&none).unwrap()["tempo"], serde_json::Value::Null);
+  }
+
+  #[test]
+  fn invalid_tempos() {
+    for bpm in &["0", "-120", "1e400"] {
+      let json = format!(r#"{{ "bpm": {}, "phase": 0 }}"#, bpm);
+      assert!(serde_json::from_str::<Tempo>(&json).is_err(), "{}", json);
+    }
+
+    let until = Time::from_secs(10.);
+
+    for &bpm in &[0., -120., f64::NAN, f64::INFINITY] {
+      assert_eq!(Tempo { bpm, phase: Time::from_secs(0.) }.beats(until).count(), 0, "{}", bpm);
+    }
+  }
+}
@@ -0,0 +1,210 @@
+//! Soundtrack decoding.
+//!
+//! Soundtracks are fully decoded in memory when loaded, as 32-bit float samples, so that seeking
+//! is immediate and the audio thread never waits for the decoder.
+
+use lewton::inside_ogg::OggStreamReader;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use crate::audio::Error;
+use crate::time::Time;
+
+/// A decoded soundtrack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Soundtrack {
+  sample_rate: u32,
+  channels: u16,
+  /// Interleaved samples, in [-1; 1].
+  samples: Vec<f32>
+}
+
+impl Soundtrack {
+  /// Create a soundtrack from interleaved samples.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the sample rate or the number of channels is 0, or if the number of samples is not
+  /// a multiple of the number of channels.
+  pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Self {
+    assert!(sample_rate > 0 && channels > 0, "a soundtrack needs a sample rate and channels");
+    assert_eq!(samples.len() % channels as usize, 0, "samples of a {} channels soundtrack", channels);
+
+    Soundtrack { sample_rate, channels, samples }
+  }
+
+  /// Load a soundtrack, as OGG Vorbis if the file has the “ogg” extension and as WAV if it has the
+  /// “wav” one.
+  pub fn load<P>(path: P) -> Result<Self, Error> where P: AsRef<Path> {
+    let path = path.as_ref();
+    let ext = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+    let file = || {
+      File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| Error::cannot_open(format!("{}: {}", path.display(), e)))
+    };
+
+    let soundtrack = match ext.as_deref() {
+      Some("ogg") => Soundtrack::from_vorbis(file()?),
+      Some("wav") => Soundtrack::from_wav(file()?),
+      _ => return Err(Error::unsupported_format(format!("{}: expected an ogg or wav file", path.display())))
+    };
+
+    soundtrack.map_err(|e| match e {
+      Error::CannotDecode(reason) => Error::cannot_decode(format!("{}: {}", path.display(), reason)),
+      e => e
+    })
+  }
+
+  /// Decode an OGG Vorbis stream.
+  pub fn from_vorbis<R>(reader: R) -> Result<Self, Error> where R: Read + Seek {
+    let mut stream = OggStreamReader::new(reader).map_err(|e| Error::cannot_decode(format!("{:?}", e)))?;
+    let sample_rate = stream.ident_hdr.audio_sample_rate;
+    let channels = stream.ident_hdr.audio_channels as u16;
+    let mut samples = Vec::new();
+
+    while let Some(packet) = stream.read_dec_packet_itl().map_err(|e| Error::cannot_decode(format!("{:?}", e)))? {
+      samples.extend(packet.into_iter().map(|sample| sample as f32 / 32768.));
+    }
+
+    Ok(Soundtrack::new(sample_rate, channels, samples))
+  }
+
+  /// Decode a WAV stream of integer – 8 to 32 bits – or float samples.
+  pub fn from_wav<R>(reader: R) -> Result<Self, Error> where R: Read {
+    let mut reader = hound::WavReader::new(reader).map_err(|e| Error::cannot_decode(e.to_string()))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+      hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>(),
+
+      hound::SampleFormat::Int => {
+        let scale = 1. / (1u64 << (spec.bits_per_sample - 1)) as f32;
+        reader.samples::<i32>().map(|sample| sample.map(|sample| sample as f32 * scale)).collect()
+      }
+    };
+
+    let samples = samples.map_err(|e| Error::cannot_decode(e.to_string()))?;
+
+    Ok(Soundtrack::new(spec.sample_rate, spec.channels, samples))
+  }
+
+  /// Number of samples per second and per channel.
+  pub fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  pub fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  /// Interleaved samples, in [-1; 1].
+  pub fn samples(&self) -> &[f32] {
+    &self.samples
+  }
+
+  /// Number of frames – a sample per channel.
+  pub fn frames(&self) -> usize {
+    self.samples.len() / self.channels as usize
+  }
+
+  /// Samples of a frame, one per channel.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the frame is out of bounds.
+  pub fn frame(&self, index: usize) -> &[f32] {
+    let channels = self.channels as usize;
+    &self.samples[index * channels..(index + 1) * channels]
+  }
+
+  pub fn duration(&self) -> Time {
+    Time::from_secs(self.frames() as f64 / self.sample_rate as f64)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use crate::testing::ScratchDir;
+
+  use super::*;
+
+  fn wav<S, F>(spec: hound::WavSpec, samples: &[S], write: F) -> Vec<u8>
+  where S: Copy,
+        F: Fn(&mut hound::WavWriter<&mut Cursor<Vec<u8>>>, S) {
+    let mut bytes = Cursor::new(Vec::new());
+
+    {
+      let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+
+      for &sample in samples {
+        write(&mut writer, sample);
+      }
+
+      writer.finalize().unwrap();
+    }
+
+    bytes.into_inner()
+  }
+
+  #[test]
+  fn wav_int() {
+    let spec = hound::WavSpec {
+      channels: 2,
+      sample_rate: 44100,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int
+    };
+    let bytes = wav(spec, &[0i16, -32768, 16384, 32767], |w, s| w.write_sample(s).unwrap());
+    let soundtrack = Soundtrack::from_wav(Cursor::new(bytes)).unwrap();
+
+    assert_eq!((soundtrack.sample_rate(), soundtrack.channels(), soundtrack.frames()), (44100, 2, 2));
+    assert_eq!(soundtrack.frame(0), &[0., -1.]);
+    assert_eq!(soundtrack.frame(1)[0], 0.5);
+    assert!((soundtrack.frame(1)[1] - 1.).abs() < 1e-4);
+  }
+
+  #[test]
+  fn wav_float() {
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate: 8000,
+      bits_per_sample: 32,
+      sample_format: hound::SampleFormat::Float
+    };
+    let samples: Vec<f32> = (0..4000).map(|i| (i as f32 / 4000.) * 2. - 1.).collect();
+    let bytes = wav(spec, &samples, |w, s| w.write_sample(s).unwrap());
+    let soundtrack = Soundtrack::from_wav(Cursor::new(bytes)).unwrap();
+
+    assert_eq!(soundtrack.samples(), samples.as_slice());
+    assert_eq!(soundtrack.duration(), Time::from_secs(0.5));
+  }
+
+  #[test]
+  fn load() {
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate: 100,
+      bits_per_sample: 8,
+      sample_format: hound::SampleFormat::Int
+    };
+    let dir = ScratchDir::new("decode-files");
+    let path = dir.write("soundtrack.WAV", wav(spec, &[64i8, -64], |w, s| w.write_sample(s).unwrap()));
+
+    assert_eq!(Soundtrack::load(&path).unwrap().samples(), &[0.5, -0.5]);
+
+    // garbage
+    let path = dir.write("soundtrack.ogg", b"definitely not vorbis");
+
+    match Soundtrack::load(&path) {
+      Err(Error::CannotDecode(reason)) => assert!(reason.contains("soundtrack.ogg")),
+      r => panic!("unexpected result: {:?}", r)
+    }
+
+    assert!(matches!(Soundtrack::load("music.mp3"), Err(Error::UnsupportedFormat(_))));
+    assert!(matches!(Soundtrack::load("no/such/music.ogg"), Err(Error::CannotOpen(_))));
+  }
+}
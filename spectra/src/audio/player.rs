@@ -0,0 +1,335 @@
+//! Soundtrack playback.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::audio::{Analysis, Analyzer, Error, Soundtrack};
+use crate::audio::backend::{Backend, Format};
+use crate::time::Time;
+use crate::time::clock::Clock;
+
+/// Playback state, shared between a player and its source.
+#[derive(Debug)]
+struct State {
+  soundtrack: Arc<Soundtrack>,
+  /// Playback position, in frames of the soundtrack; fractional when resampling.
+  cursor: f64,
+  paused: bool,
+  speed: f64,
+  /// Last block rendered: when, from which position and for how long, in seconds.
+  block: Option<(Instant, f64, f64)>
+}
+
+impl State {
+  /// Position heard right now.
+  ///
+  /// The cursor moves a block at a time, ahead of what is heard: the position is interpolated
+  /// from the start of the last block with the time elapsed since it was rendered.
+  fn position(&self) -> Time {
+    let rate = self.soundtrack.sample_rate() as f64;
+
+    match self.block {
+      Some((rendered_at, start, duration)) if !self.paused => {
+        let elapsed = rendered_at.elapsed().as_secs_f64().min(duration);
+        Time::from_secs((start / rate + elapsed * self.speed).min(self.cursor / rate))
+      }
+
+      _ => Time::from_secs(self.cursor / rate)
+    }
+  }
+
+  /// Forget about the last block, after a change that makes the interpolation wrong.
+  fn settle(&mut self) {
+    if let Some((rendered_at, start, duration)) = self.block.take() {
+      if !self.paused {
+        let elapsed = rendered_at.elapsed().as_secs_f64().min(duration);
+        self.cursor = start + elapsed * self.speed * self.soundtrack.sample_rate() as f64;
+      }
+    }
+  }
+}
+
+/// Source of audio pulled by backends.
+#[derive(Clone, Debug)]
+pub struct Source {
+  state: Arc<Mutex<State>>
+}
+
+impl Source {
+  /// Render the next block of interleaved samples, in a given output format.
+  ///
+  /// The soundtrack is resampled to the output sample rate – and by the playback speed – and its
+  /// channels are mapped onto the output ones: mono is spread over all the channels, and everything
+  /// is mixed down to mono. Silence is rendered while paused and past the end of the soundtrack.
+  pub fn render(&self, out: &mut [f32], format: Format) {
+    let mut state = lock(&self.state);
+    let out_channels = format.channels as usize;
+    let frames = out.len() / out_channels;
+
+    if state.paused {
+      out.iter_mut().for_each(|sample| *sample = 0.);
+      return;
+    }
+
+    let soundtrack = state.soundtrack.clone();
+    let in_channels = soundtrack.channels() as usize;
+    let last = soundtrack.frames();
+    let step = state.speed * soundtrack.sample_rate() as f64 / format.sample_rate as f64;
+    let start = state.cursor;
+
+    for frame in out.chunks_mut(out_channels) {
+      let index = state.cursor as usize;
+
+      if index >= last {
+        frame.iter_mut().for_each(|sample| *sample = 0.);
+        continue;
+      }
+
+      // linear interpolation between two frames of the soundtrack
+      let a = soundtrack.frame(index);
+      let b = soundtrack.frame((index + 1).min(last - 1));
+      let x = (state.cursor - index as f64) as f32;
+      let sample = |channel: usize| a[channel] + (b[channel] - a[channel]) * x;
+
+      for (channel, out) in frame.iter_mut().enumerate() {
+        *out = if out_channels == 1 && in_channels > 1 {
+          (0..in_channels).map(sample).sum::<f32>() / in_channels as f32
+        } else {
+          sample(channel % in_channels)
+        };
+      }
+
+      state.cursor = (state.cursor + step).min(last as f64);
+    }
+
+    let duration = frames as f64 / format.sample_rate as f64;
+    state.block = Some((Instant::now(), start, duration));
+  }
+}
+
+/// Soundtrack player.
+///
+/// The player starts playing when it’s ticked for the first time, so that the time spent
+/// initializing a demo is not accounted for – unless it’s been paused before. It then plays until
+/// the end of the soundtrack, where its position stops.
+///
+/// As a [`Clock`], the player is a [playback](Clock::is_playback): runners’ transports make it
+/// seek, pause and change its speed. Speed changes are applied as a tape would: the pitch changes
+/// too.
+pub struct Player {
+  state: Arc<Mutex<State>>,
+  /// Whether the playback is yet to start.
+  pending_start: bool,
+  /// Kept alive for the audio to play.
+  _backend: Box<dyn Backend>
+}
+
+impl Player {
+  /// Play a soundtrack through a backend.
+  pub fn new<B>(soundtrack: Soundtrack, mut backend: B) -> Result<Self, Error> where B: 'static + Backend {
+    let state = Arc::new(Mutex::new(State {
+      soundtrack: Arc::new(soundtrack),
+      cursor: 0.,
+      paused: true,
+      speed: 1.,
+      block: None
+    }));
+
+    backend.start(Source { state: state.clone() })?;
+
+    Ok(Player { state, pending_start: true, _backend: Box::new(backend) })
+  }
+
+  /// Current playback position.
+  pub fn position(&self) -> Time {
+    lock(&self.state).position()
+  }
+
+  /// Duration of the soundtrack.
+  pub fn duration(&self) -> Time {
+    lock(&self.state).soundtrack.duration()
+  }
+
+  /// The soundtrack being played.
+  pub fn soundtrack(&self) -> Arc<Soundtrack> {
+    lock(&self.state).soundtrack.clone()
+  }
+
+  /// Analyze the soundtrack at the current playback position.
+  ///
+  /// This is the same as analyzing the soundtrack offline at that position.
+  pub fn analyze(&self, analyzer: &Analyzer) -> Analysis {
+    let state = lock(&self.state);
+    analyzer.analyze(&state.soundtrack, state.position())
+  }
+
+  pub fn is_paused(&self) -> bool {
+    lock(&self.state).paused
+  }
+
+  /// Whether the end of the soundtrack is reached.
+  pub fn is_finished(&self) -> bool {
+    let state = lock(&self.state);
+    state.cursor as usize >= state.soundtrack.frames()
+  }
+}
+
+impl Clock for Player {
+  fn tick(&mut self) -> Time {
+    if self.pending_start {
+      self.set_paused(false);
+    }
+
+    self.position()
+  }
+
+  fn is_playback(&self) -> bool {
+    true
+  }
+
+  fn is_finished(&self) -> bool {
+    Player::is_finished(self)
+  }
+
+  /// Move to a given time, clamped to the soundtrack.
+  fn seek(&mut self, t: Time) {
+    let mut state = lock(&self.state);
+    let frames = state.soundtrack.frames() as f64;
+
+    state.cursor = (t.as_secs() * state.soundtrack.sample_rate() as f64).max(0.).min(frames);
+    state.block = None;
+  }
+
+  fn set_paused(&mut self, paused: bool) {
+    let mut state = lock(&self.state);
+
+    self.pending_start = false;
+    state.settle();
+    state.paused = paused;
+  }
+
+  fn set_speed(&mut self, speed: f64) {
+    let mut state = lock(&self.state);
+
+    state.settle();
+    state.speed = speed.max(0.);
+  }
+}
+
+/// Lock the playback state, even if a thread panicked with it.
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+  state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Backend keeping the source, to render by hand.
+  struct Manual(Arc<Mutex<Option<Source>>>);
+
+  impl Backend for Manual {
+    fn start(&mut self, source: Source) -> Result<(), Error> {
+      *self.0.lock().unwrap() = Some(source);
+      Ok(())
+    }
+  }
+
+  /// A player of a stereo ramp sampled at 1Hz, and its source.
+  fn ramp(len: usize) -> (Player, Source) {
+    let samples = (0..len).flat_map(|i| vec![i as f32, -(i as f32)]).collect();
+    let source = Arc::new(Mutex::new(None));
+    let player = Player::new(Soundtrack::new(1, 2, samples), Manual(source.clone())).unwrap();
+    let source = source.lock().unwrap().take().unwrap();
+
+    (player, source)
+  }
+
+  fn render(source: &Source, frames: usize, sample_rate: u32, channels: u16) -> Vec<f32> {
+    let mut out = vec![f32::NAN; frames * channels as usize];
+    source.render(&mut out, Format { sample_rate, channels });
+    out
+  }
+
+  #[test]
+  fn starts_on_first_tick() {
+    let (mut player, source) = ramp(8);
+
+    assert!(player.is_paused());
+    assert_eq!(render(&source, 2, 1, 2), vec![0.; 4]);
+
+    assert_eq!(player.tick(), Time::from_secs(0.));
+    assert!(!player.is_paused());
+    assert_eq!(render(&source, 2, 1, 2), vec![0., 0., 1., -1.]);
+
+    // paused before the first tick
+    let (mut player, _) = ramp(8);
+    player.set_paused(true);
+    player.tick();
+    assert!(player.is_paused());
+  }
+
+  #[test]
+  fn resampling_and_channels() {
+    let (mut player, source) = ramp(8);
+    player.tick();
+
+    // twice the sample rate, interpolated
+    assert_eq!(render(&source, 3, 2, 2), vec![0., 0., 0.5, -0.5, 1., -1.]);
+
+    // mixed down to mono
+    assert_eq!(render(&source, 2, 2, 1), vec![0., 0.]);
+
+    // twice the speed
+    player.set_speed(2.);
+    player.seek(Time::from_secs(4.));
+    assert_eq!(render(&source, 2, 1, 3), vec![4., -4., 4., 6., -6., 6.]);
+  }
+
+  #[test]
+  fn end_of_soundtrack() {
+    let (mut player, source) = ramp(2);
+    player.tick();
+
+    assert!(!player.is_finished());
+    assert_eq!(render(&source, 4, 1, 2), vec![0., 0., 1., -1., 0., 0., 0., 0.]);
+    assert!(player.is_finished());
+    assert_eq!(player.duration(), Time::from_secs(2.));
+
+    // as a clock, through adapters
+    assert!(Box::new(player).offset(Time::from_secs(1.)).wrap_at(Time::from_secs(2.)).is_finished());
+  }
+
+  #[test]
+  fn position() {
+    let (mut player, source) = ramp(1000);
+    player.tick();
+
+    // the position is interpolated within the last block
+    render(&source, 100, 1, 2);
+    assert!(player.position() < Time::from_secs(1.));
+
+    player.set_paused(true);
+    let paused_at = player.position();
+    assert!(paused_at < Time::from_secs(1.));
+    assert_eq!(render(&source, 10, 1, 2), vec![0.; 20]);
+    assert_eq!(player.position(), paused_at);
+
+    player.seek(Time::from_secs(500.));
+    assert_eq!(player.tick(), Time::from_secs(500.));
+
+    player.seek(Time::from_secs(-1.));
+    assert_eq!(player.position(), Time::from_secs(0.));
+    player.seek(Time::from_secs(5000.));
+    assert_eq!(player.position(), Time::from_secs(1000.));
+  }
+
+  #[test]
+  fn analysis_at_position() {
+    let (mut player, _) = ramp(1000);
+    let analyzer = Analyzer::new(16);
+
+    player.seek(Time::from_secs(500.));
+    assert_eq!(player.analyze(&analyzer), analyzer.analyze(&player.soundtrack(), Time::from_secs(500.)));
+  }
+}
@@ -0,0 +1,399 @@
+//! Synthesizer units: oscillators, envelopes, filters and effects.
+//!
+//! Units are described by plain, serializable values. Those that need memory – filters, delays and
+//! reverbs – create a state, updated sample by sample.
+
+use serde_derive::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Shape of an oscillator.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+  Sine,
+  Square,
+  Saw,
+  Triangle,
+  /// White noise; deterministic, as it’s generated from a fixed seed on each note.
+  Noise
+}
+
+impl Waveform {
+  /// Sample of the waveform at a phase in [0; 1[.
+  ///
+  /// The noise doesn’t depend on the phase but on a generator state, updated on each sample.
+  pub fn sample(self, phase: f64, noise: &mut u32) -> f32 {
+    match self {
+      Waveform::Sine => (2. * PI * phase).sin() as f32,
+      Waveform::Square => if phase < 0.5 { 1. } else { -1. },
+      Waveform::Saw => (2. * phase - 1.) as f32,
+      Waveform::Triangle => (4. * (phase - 0.5).abs() - 1.) as f32,
+      Waveform::Noise => {
+        // xorshift32
+        *noise ^= *noise << 13;
+        *noise ^= *noise >> 17;
+        *noise ^= *noise << 5;
+        (*noise as f64 / u32::MAX as f64 * 2. - 1.) as f32
+      }
+    }
+  }
+}
+
+/// An oscillator of an instrument.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Oscillator {
+  pub waveform: Waveform,
+  /// Detune relative to the played note, in semitones.
+  #[serde(default)]
+  pub detune: f32,
+  /// Level of the oscillator in the instrument’s mix.
+  #[serde(default = "one")]
+  pub level: f32
+}
+
+impl Oscillator {
+  pub fn new(waveform: Waveform) -> Self {
+    Oscillator { waveform, detune: 0., level: 1. }
+  }
+
+  pub fn detune(self, detune: f32) -> Self {
+    Oscillator { detune, ..self }
+  }
+
+  pub fn level(self, level: f32) -> Self {
+    Oscillator { level, ..self }
+  }
+}
+
+/// Attack, decay, sustain and release envelope.
+///
+/// Durations are in seconds and the sustain is a level in [0; 1].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Adsr {
+  pub attack: f32,
+  pub decay: f32,
+  pub sustain: f32,
+  pub release: f32
+}
+
+impl Adsr {
+  pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+    Adsr { attack, decay, sustain, release }
+  }
+
+  /// Level of the envelope at a time since the note started, given the time at which it was
+  /// released – if it was.
+  pub fn level(&self, t: f32, released_at: Option<f32>) -> f32 {
+    match released_at {
+      Some(released_at) if t >= released_at => {
+        let since = t - released_at;
+
+        if since >= self.release {
+          0.
+        } else {
+          self.held(released_at) * (1. - since / self.release)
+        }
+      }
+
+      _ => self.held(t)
+    }
+  }
+
+  /// Whether the envelope is over, at a time since the note started.
+  pub fn is_over(&self, t: f32, released_at: Option<f32>) -> bool {
+    match released_at {
+      Some(released_at) => t >= released_at + self.release,
+      None => false
+    }
+  }
+
+  /// Level of the envelope while the note is held.
+  fn held(&self, t: f32) -> f32 {
+    if t < self.attack {
+      t / self.attack
+    } else if t < self.attack + self.decay {
+      1. - (1. - self.sustain) * (t - self.attack) / self.decay
+    } else {
+      self.sustain
+    }
+  }
+}
+
+impl Default for Adsr {
+  fn default() -> Self {
+    Adsr::new(0.01, 0.1, 0.8, 0.1)
+  }
+}
+
+/// Kind of filter.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+  LowPass,
+  HighPass,
+  BandPass
+}
+
+/// Resonant state variable filter, of 12dB per octave.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Filter {
+  pub kind: FilterKind,
+  /// Cutoff frequency, in Hz.
+  pub cutoff: f32,
+  /// Resonance, in [0; 1[.
+  #[serde(default)]
+  pub resonance: f32
+}
+
+impl Filter {
+  pub fn new(kind: FilterKind, cutoff: f32) -> Self {
+    Filter { kind, cutoff, resonance: 0. }
+  }
+
+  pub fn resonance(self, resonance: f32) -> Self {
+    Filter { resonance, ..self }
+  }
+
+  /// Filter a sample.
+  pub fn process(&self, state: &mut FilterState, x: f32, sample_rate: u32) -> f32 {
+    let nyquist = sample_rate as f64 * 0.5;
+    let g = (PI * (self.cutoff as f64).clamp(1., nyquist * 0.99) / sample_rate as f64).tan();
+    // damping: Butterworth without resonance
+    let k = std::f64::consts::SQRT_2 * (1. - (self.resonance as f64).clamp(0., 0.99));
+    let a1 = 1. / (1. + g * (g + k));
+    let a2 = g * a1;
+    let a3 = g * a2;
+
+    let x = x as f64;
+    let v3 = x - state.ic2;
+    let v1 = a1 * state.ic1 + a2 * v3;
+    let v2 = state.ic2 + a2 * state.ic1 + a3 * v3;
+
+    state.ic1 = 2. * v1 - state.ic1;
+    state.ic2 = 2. * v2 - state.ic2;
+
+    let y = match self.kind {
+      FilterKind::LowPass => v2,
+      FilterKind::HighPass => x - k * v1 - v2,
+      FilterKind::BandPass => k * v1
+    };
+
+    y as f32
+  }
+}
+
+/// State of a [`Filter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterState {
+  ic1: f64,
+  ic2: f64
+}
+
+/// Feedback delay.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Delay {
+  /// Time between two echoes, in seconds.
+  pub time: f32,
+  /// Level of an echo relative to the previous one, in [0; 1[.
+  pub feedback: f32,
+  /// Level of the echoes in the output.
+  pub mix: f32
+}
+
+impl Delay {
+  pub fn new(time: f32, feedback: f32, mix: f32) -> Self {
+    Delay { time, feedback, mix }
+  }
+
+  pub fn state(&self, sample_rate: u32) -> DelayState {
+    DelayState::new(((self.time * sample_rate as f32).round() as usize).max(1))
+  }
+
+  /// Delay a sample.
+  pub fn process(&self, state: &mut DelayState, x: f32) -> f32 {
+    let echo = state.read();
+    state.write(x + echo * self.feedback);
+
+    x + echo * self.mix
+  }
+}
+
+/// Memory of a delay line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelayState {
+  buffer: Vec<f32>,
+  cursor: usize
+}
+
+impl DelayState {
+  fn new(len: usize) -> Self {
+    DelayState { buffer: vec![0.; len], cursor: 0 }
+  }
+
+  /// Sample written `len` samples ago.
+  fn read(&self) -> f32 {
+    self.buffer[self.cursor]
+  }
+
+  fn write(&mut self, x: f32) {
+    self.buffer[self.cursor] = x;
+    self.cursor = (self.cursor + 1) % self.buffer.len();
+  }
+}
+
+/// Lengths, in samples at 44.1kHz, of the comb filters of the reverb.
+const COMBS: [usize; 4] = [1116, 1188, 1277, 1356];
+
+/// Lengths, in samples at 44.1kHz, of the all-pass filters of the reverb.
+const ALL_PASSES: [usize; 2] = [556, 441];
+
+/// Schroeder reverb.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Reverb {
+  /// Feedback of the comb filters, in [0; 1[: the longer the tail, the bigger the room.
+  pub decay: f32,
+  /// Level of the reverberated signal in the output – the dry signal is attenuated accordingly.
+  pub mix: f32
+}
+
+impl Reverb {
+  pub fn new(decay: f32, mix: f32) -> Self {
+    Reverb { decay, mix }
+  }
+
+  pub fn state(&self, sample_rate: u32) -> ReverbState {
+    let scaled = |len: usize| DelayState::new(((len * sample_rate as usize) / 44_100).max(1));
+
+    ReverbState {
+      combs: COMBS.iter().cloned().map(scaled).collect(),
+      all_passes: ALL_PASSES.iter().cloned().map(scaled).collect()
+    }
+  }
+
+  /// Reverberate a sample.
+  pub fn process(&self, state: &mut ReverbState, x: f32) -> f32 {
+    let mut wet = 0.;
+
+    for comb in &mut state.combs {
+      let y = comb.read();
+      comb.write(x + y * self.decay);
+      wet += y;
+    }
+
+    wet /= state.combs.len() as f32;
+
+    for all_pass in &mut state.all_passes {
+      let delayed = all_pass.read();
+      let y = delayed - 0.5 * wet;
+      all_pass.write(wet + 0.5 * y);
+      wet = y;
+    }
+
+    x * (1. - self.mix) + wet * self.mix
+  }
+}
+
+/// Memory of a reverb.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReverbState {
+  combs: Vec<DelayState>,
+  all_passes: Vec<DelayState>
+}
+
+fn one() -> f32 {
+  1.
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+  }
+
+  #[test]
+  fn waveforms() {
+    let mut noise = 1;
+
+    assert!(close(Waveform::Sine.sample(0.25, &mut noise), 1.));
+    assert_eq!(Waveform::Square.sample(0.25, &mut noise), 1.);
+    assert_eq!(Waveform::Square.sample(0.75, &mut noise), -1.);
+    assert_eq!(Waveform::Saw.sample(0., &mut noise), -1.);
+    assert_eq!(Waveform::Saw.sample(0.75, &mut noise), 0.5);
+    assert_eq!(Waveform::Triangle.sample(0., &mut noise), 1.);
+    assert_eq!(Waveform::Triangle.sample(0.5, &mut noise), -1.);
+
+    let samples = (0..1000).map(|_| Waveform::Noise.sample(0., &mut noise)).collect::<Vec<_>>();
+    assert!(samples.iter().all(|x| x.abs() <= 1.));
+    assert!((samples.iter().sum::<f32>() / 1000.).abs() < 0.1);
+
+    let mut again = 1;
+    assert!(samples.iter().all(|&x| x == Waveform::Noise.sample(0., &mut again)));
+  }
+
+  #[test]
+  fn adsr() {
+    let adsr = Adsr::new(0.1, 0.2, 0.5, 0.4);
+
+    assert!(close(adsr.level(0., None), 0.));
+    assert!(close(adsr.level(0.05, None), 0.5));
+    assert!(close(adsr.level(0.1, None), 1.));
+    assert!(close(adsr.level(0.2, None), 0.75));
+    assert!(close(adsr.level(10., None), 0.5));
+
+    // released while sustained, then during the attack
+    assert!(close(adsr.level(1.2, Some(1.)), 0.25));
+    assert!(close(adsr.level(0.15, Some(0.05)), 0.375));
+    assert!(close(adsr.level(2., Some(1.)), 0.));
+    assert!(!adsr.is_over(1.2, Some(1.)));
+    assert!(adsr.is_over(1.4, Some(1.)));
+    assert!(!adsr.is_over(100., None));
+  }
+
+  /// RMS level of a filtered sine, once settled.
+  fn filtered(filter: Filter, freq: f64) -> f32 {
+    let mut state = FilterState::default();
+    let samples = (0..8000)
+      .map(|i| filter.process(&mut state, (2. * PI * freq * i as f64 / 8000.).sin() as f32, 8000))
+      .skip(4000)
+      .collect::<Vec<_>>();
+
+    (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt() * 2f32.sqrt()
+  }
+
+  #[test]
+  fn filters() {
+    let low = Filter::new(FilterKind::LowPass, 200.);
+    let high = Filter::new(FilterKind::HighPass, 2000.);
+    let band = Filter::new(FilterKind::BandPass, 1000.);
+
+    assert!(filtered(low, 50.) > 0.95 && filtered(low, 3000.) < 0.02);
+    assert!(filtered(high, 3500.) > 0.9 && filtered(high, 50.) < 0.01);
+    assert!(filtered(band, 1000.) > 0.95 && filtered(band, 100.) < 0.2 && filtered(band, 3500.) < 0.3);
+
+    // resonance boosts the cutoff frequency
+    assert!(filtered(low.resonance(0.9), 200.) > 3.);
+  }
+
+  #[test]
+  fn delay() {
+    let delay = Delay::new(0.001, 0.5, 0.5);
+    let mut state = delay.state(4000);
+    let out = (0..10).map(|i| delay.process(&mut state, if i == 0 { 1. } else { 0. })).collect::<Vec<_>>();
+
+    assert_eq!(out, vec![1., 0., 0., 0., 0.5, 0., 0., 0., 0.25, 0.]);
+  }
+
+  #[test]
+  fn reverb() {
+    let reverb = Reverb::new(0.8, 0.5);
+    let mut state = reverb.state(8000);
+    let out = (0..16000).map(|i| reverb.process(&mut state, if i == 0 { 1. } else { 0. })).collect::<Vec<_>>();
+    let energy = |range: std::ops::Range<usize>| out[range].iter().map(|x| x * x).sum::<f32>();
+
+    assert_eq!(out[0], 0.5);
+    assert!(energy(1..4000) > 0.);
+    assert!(energy(12000..16000) < energy(1..4000) * 0.01);
+  }
+}
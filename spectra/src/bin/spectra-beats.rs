@@ -0,0 +1,80 @@
+//! Beat and onset detection.
+//!
+//! Decodes a soundtrack, detects its tempo and onsets and writes them as JSON
+//! [`Beats`](spectra::audio::beat::Beats) – the tempo and a marker track, the format of demos’
+//! timelines – to a file or to the standard output. The estimated tempo is also reported on the
+//! standard error.
+
+use spectra::audio::Soundtrack;
+use spectra::audio::beat::Detector;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "spectra-beats")]
+struct Opt {
+  /// Soundtrack to analyze, as OGG Vorbis or WAV.
+  #[structopt(parse(from_os_str))]
+  soundtrack: PathBuf,
+
+  /// File to write the tempo and markers to; the standard output if absent.
+  #[structopt(short = "o", long = "output", parse(from_os_str))]
+  output: Option<PathBuf>,
+
+  /// Slowest tempo to look for, in BPM.
+  #[structopt(long = "min-bpm", default_value = "60")]
+  min_bpm: f64,
+
+  /// Fastest tempo to look for, in BPM.
+  #[structopt(long = "max-bpm", default_value = "200")]
+  max_bpm: f64,
+
+  /// How much stronger than its neighborhood an onset must be.
+  #[structopt(short = "t", long = "threshold", default_value = "1.5")]
+  threshold: f32
+}
+
+fn main() {
+  let opt = Opt::from_args();
+
+  if let Err(e) = run(&opt) {
+    eprintln!("spectra-beats: {}", e);
+    process::exit(1);
+  }
+}
+
+fn run(opt: &Opt) -> Result<(), String> {
+  if opt.min_bpm <= 0. || opt.min_bpm >= opt.max_bpm {
+    return Err(format!("invalid tempo range: {} to {} BPM", opt.min_bpm, opt.max_bpm));
+  }
+
+  let soundtrack = Soundtrack::load(&opt.soundtrack).map_err(|e| e.to_string())?;
+  let detection = Detector::new()
+    .bpm_range(opt.min_bpm, opt.max_bpm)
+    .threshold(opt.threshold)
+    .detect(&soundtrack);
+
+  match detection.tempo {
+    Some(tempo) => eprintln!("tempo: {:.2} BPM, first beat at {:.3}s", tempo.bpm, tempo.phase.as_secs()),
+    None => eprintln!("no tempo found")
+  }
+
+  eprintln!(
+    "onsets: {} bass, {} mid, {} high",
+    detection.onsets.bass.len(),
+    detection.onsets.mid.len(),
+    detection.onsets.high.len()
+  );
+
+  let json = serde_json::to_string_pretty(&detection.to_beats()).map_err(|e| e.to_string())?;
+
+  match opt.output {
+    Some(ref path) => fs::write(path, json).map_err(|e| format!("cannot write {}: {}", path.display(), e)),
+    None => {
+      println!("{}", json);
+      Ok(())
+    }
+  }
+}
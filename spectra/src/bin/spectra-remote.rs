@@ -0,0 +1,73 @@
+//! Remote control client of the debug runner.
+//!
+//! Sends the command given on the command line – or one command per line read from the standard
+//! input if there’s none – to a debug runner started with `--remote`, and prints the responses.
+//! The exit code is 1 if the connection fails or if the runner rejects a command given on the
+//! command line.
+
+use spectra::app::runner::remote::{Client, DEFAULT_ADDRESS};
+use std::io::{self, BufRead};
+use std::process;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "spectra-remote")]
+struct Opt {
+  /// Address of the runner: “host:port” for TCP, or “unix:path” for a Unix socket.
+  #[structopt(short = "a", long = "address", default_value = "127.0.0.1:6660")]
+  address: String,
+
+  /// Command to send, for instance “seek 1m30s”, “pause”, “set exposure 1.5” or “stats”.
+  command: Vec<String>
+}
+
+fn main() {
+  let opt = Opt::from_args();
+
+  if let Err(e) = run(&opt) {
+    eprintln!("spectra-remote: {}", e);
+    process::exit(1);
+  }
+}
+
+fn run(opt: &Opt) -> io::Result<()> {
+  let mut client = Client::connect(&opt.address).map_err(|e| {
+    let hint = if opt.address == DEFAULT_ADDRESS { " (is the runner started with --remote?)" } else { "" };
+    io::Error::new(e.kind(), format!("cannot connect to {}: {}{}", opt.address, e, hint))
+  })?;
+
+  if !opt.command.is_empty() {
+    let response = send(&mut client, &opt.command.join(" "))?;
+
+    if is_error(&response) {
+      process::exit(1);
+    }
+
+    return Ok(());
+  }
+
+  let stdin = io::stdin();
+
+  for line in stdin.lock().lines() {
+    let line = line?;
+
+    if !line.trim().is_empty() {
+      send(&mut client, &line)?;
+    }
+  }
+
+  Ok(())
+}
+
+fn send(client: &mut Client, command: &str) -> io::Result<String> {
+  let response = client.send(command)?;
+  println!("{}", response);
+  Ok(response)
+}
+
+fn is_error(response: &str) -> bool {
+  match serde_json::from_str::<serde_json::Value>(response) {
+    Ok(response) => response["type"] == "error",
+    Err(_) => true
+  }
+}
@@ -1,8 +1,13 @@
+#![feature(associated_type_defaults)]
 #![feature(proc_macro_hygiene)]
 
 #[macro_use] pub mod logger;
 
 pub mod app;
+pub mod audio;
 pub mod render;
 pub mod resource;
 pub mod time;
+pub mod tween;
+
+#[cfg(test)] mod testing;
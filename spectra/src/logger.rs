@@ -1,7 +1,8 @@
 //! Logger.
 
 use chrono::{Datelike, Local, Timelike};
-use std::fmt::Arguments;
+use std::fmt::{self, Arguments};
+use std::str::FromStr;
 
 /// Trait use to log activity.
 pub trait Logger {
@@ -15,6 +16,148 @@ pub trait Logger {
   fn error(&mut self, args: Arguments);
 }
 
+impl<L> Logger for &mut L where L: ?Sized + Logger {
+  fn info(&mut self, args: Arguments) {
+    (**self).info(args)
+  }
+
+  fn debug(&mut self, args: Arguments) {
+    (**self).debug(args)
+  }
+
+  fn warn(&mut self, args: Arguments) {
+    (**self).warn(args)
+  }
+
+  fn error(&mut self, args: Arguments) {
+    (**self).error(args)
+  }
+}
+
+/// Verbosity of a logger, from the least to the most verbose.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+  Error,
+  Warn,
+  Info,
+  Debug
+}
+
+impl fmt::Display for Level {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Level::Error => f.write_str("error"),
+      Level::Warn => f.write_str("warn"),
+      Level::Info => f.write_str("info"),
+      Level::Debug => f.write_str("debug")
+    }
+  }
+}
+
+impl FromStr for Level {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(Level::Error),
+      "warn" => Ok(Level::Warn),
+      "info" => Ok(Level::Info),
+      "debug" => Ok(Level::Debug),
+      _ => Err(format!("invalid log level “{}”; expected error, warn, info or debug", s))
+    }
+  }
+}
+
+/// Logger dropping the messages more verbose than a given level.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Filter<L> {
+  logger: L,
+  level: Level
+}
+
+impl<L> Filter<L> {
+  pub fn new(logger: L, level: Level) -> Self {
+    Filter { logger, level }
+  }
+
+  /// Most verbose level let through.
+  pub fn level(&self) -> Level {
+    self.level
+  }
+}
+
+impl<L> Logger for Filter<L> where L: Logger {
+  fn info(&mut self, args: Arguments) {
+    if self.level >= Level::Info {
+      self.logger.info(args);
+    }
+  }
+
+  fn debug(&mut self, args: Arguments) {
+    if self.level >= Level::Debug {
+      self.logger.debug(args);
+    }
+  }
+
+  fn warn(&mut self, args: Arguments) {
+    if self.level >= Level::Warn {
+      self.logger.warn(args);
+    }
+  }
+
+  fn error(&mut self, args: Arguments) {
+    self.logger.error(args);
+  }
+}
+
+/// Logger keeping the messages it gets, to forward them to another logger later.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Buffer {
+  messages: Vec<(Level, String)>
+}
+
+impl Buffer {
+  pub fn new() -> Self {
+    Buffer::default()
+  }
+
+  /// Kept messages, oldest first.
+  #[cfg(test)]
+  pub(crate) fn messages(&self) -> &[(Level, String)] {
+    &self.messages
+  }
+
+  /// Forward the kept messages to a logger, in order, and forget them.
+  pub fn flush<L>(&mut self, logger: &mut L) where L: ?Sized + Logger {
+    for (level, message) in self.messages.drain(..) {
+      match level {
+        Level::Error => logger.error(format_args!("{}", message)),
+        Level::Warn => logger.warn(format_args!("{}", message)),
+        Level::Info => logger.info(format_args!("{}", message)),
+        Level::Debug => logger.debug(format_args!("{}", message))
+      }
+    }
+  }
+}
+
+impl Logger for Buffer {
+  fn info(&mut self, args: Arguments) {
+    self.messages.push((Level::Info, args.to_string()));
+  }
+
+  fn debug(&mut self, args: Arguments) {
+    self.messages.push((Level::Debug, args.to_string()));
+  }
+
+  fn warn(&mut self, args: Arguments) {
+    self.messages.push((Level::Warn, args.to_string()));
+  }
+
+  fn error(&mut self, args: Arguments) {
+    self.messages.push((Level::Error, args.to_string()));
+  }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct StdoutLogger;
 
@@ -65,24 +208,62 @@ macro_rules! info {
 
 #[macro_export]
 macro_rules! debug {
-  ($logger:expr, $s:expr $(, $r:tt)*) => {
+  ($logger:expr, $s:expr $(, $r:tt)*) => {{
     use $crate::logger::Logger;
     $logger.debug(format_args!($s $(, $r)*));
-  }
+  }}
 }
 
 #[macro_export]
 macro_rules! warn {
-  ($logger:expr, $s:expr $(, $r:tt)*) => {
+  ($logger:expr, $s:expr $(, $r:tt)*) => {{
     use $crate::logger::Logger;
-    $logger.warn(format_args!($s, $($r)*));
-  }
+    $logger.warn(format_args!($s $(, $r)*));
+  }}
 }
 
 #[macro_export]
 macro_rules! error {
-  ($logger:expr, $s:expr, $($r:tt),*) => {
+  ($logger:expr, $s:expr $(, $r:tt)*) => {{
     use $crate::logger::Logger;
-    $logger.error(format_args!($s, $($r),*));
+    $logger.error(format_args!($s $(, $r)*));
+  }}
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_level() {
+    assert_eq!("warn".parse(), Ok(Level::Warn));
+    assert_eq!(Level::Debug.to_string(), "debug");
+    assert!("verbose".parse::<Level>().is_err());
+  }
+
+  #[test]
+  fn filter() {
+    let mut logger = Filter::new(Buffer::new(), Level::Warn);
+
+    debug!(logger, "a");
+    info!(logger, "b");
+    warn!(logger, "c");
+    error!(logger, "d");
+
+    assert_eq!(logger.logger.messages(), &[(Level::Warn, "c".to_owned()), (Level::Error, "d".to_owned())]);
+  }
+
+  #[test]
+  fn buffer() {
+    let mut buffer = Buffer::new();
+    let mut forwarded = Buffer::new();
+
+    info!(buffer, "a {}", 1);
+    error!(buffer, "b");
+    buffer.flush(&mut forwarded);
+    buffer.flush(&mut forwarded);
+
+    assert!(buffer.messages().is_empty());
+    assert_eq!(forwarded.messages(), &[(Level::Info, "a 1".to_owned()), (Level::Error, "b".to_owned())]);
   }
 }
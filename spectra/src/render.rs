@@ -1,4 +1,5 @@
 pub mod block;
+pub mod color;
 pub mod input;
 pub mod output;
 pub mod type_channel;
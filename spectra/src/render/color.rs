@@ -0,0 +1,41 @@
+//! Colors.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Linear RGBA color.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Color {
+  pub r: f32,
+  pub g: f32,
+  pub b: f32,
+  pub a: f32
+}
+
+impl Color {
+  /// Create a new color.
+  pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+    Color { r, g, b, a }
+  }
+
+  /// Create a new opaque color.
+  pub fn rgb(r: f32, g: f32, b: f32) -> Self {
+    Color::new(r, g, b, 1.)
+  }
+
+  /// Get the channels of the color as an array.
+  pub fn to_rgba(self) -> [f32; 4] {
+    [self.r, self.g, self.b, self.a]
+  }
+}
+
+impl From<[f32; 3]> for Color {
+  fn from([r, g, b]: [f32; 3]) -> Self {
+    Color::rgb(r, g, b)
+  }
+}
+
+impl From<[f32; 4]> for Color {
+  fn from([r, g, b, a]: [f32; 4]) -> Self {
+    Color::new(r, g, b, a)
+  }
+}
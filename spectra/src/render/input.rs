@@ -133,11 +133,28 @@ pub enum Role {
 }
 
 /// Built-ins.
+///
+/// Audio built-ins are computed from the soundtrack’s [analysis](crate::audio::analysis) at the
+/// current time.
 #[derive(Clone, Copy, Debug, Deserialize, Hash, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BuiltIn {
   Time,
-  FramebufferResolution
+  FramebufferResolution,
+  /// 1D texture of the amplitude of each frequency bin.
+  Spectrum,
+  /// Level of the bass band.
+  Bass,
+  /// Level of the mid band.
+  Mid,
+  /// Level of the high band.
+  High,
+  /// RMS level of the soundtrack.
+  Loudness,
+  /// Peak level of the soundtrack.
+  Peak,
+  /// Onset strength – high on attacks, such as kicks and snares.
+  Onset
 }
 
 #[cfg(test)]
@@ -215,12 +232,18 @@ mod tests {
   fn serialize_builtin() {
     assert_eq!(&to_string(&BuiltIn::Time).unwrap(), r#""time""#);
     assert_eq!(&to_string(&BuiltIn::FramebufferResolution).unwrap(), r#""framebuffer_resolution""#);
+    assert_eq!(&to_string(&BuiltIn::Spectrum).unwrap(), r#""spectrum""#);
+    assert_eq!(&to_string(&BuiltIn::Bass).unwrap(), r#""bass""#);
+    assert_eq!(&to_string(&BuiltIn::Onset).unwrap(), r#""onset""#);
   }
 
   #[test]
   fn deserialize_builtin() {
     assert_eq!(from_str::<BuiltIn>(r#""time""#).unwrap(), BuiltIn::Time);
     assert_eq!(from_str::<BuiltIn>(r#""framebuffer_resolution""#).unwrap(), BuiltIn::FramebufferResolution);
+    assert_eq!(from_str::<BuiltIn>(r#""spectrum""#).unwrap(), BuiltIn::Spectrum);
+    assert_eq!(from_str::<BuiltIn>(r#""mid""#).unwrap(), BuiltIn::Mid);
+    assert_eq!(from_str::<BuiltIn>(r#""loudness""#).unwrap(), BuiltIn::Loudness);
   }
 
   #[test]
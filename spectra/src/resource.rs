@@ -1,29 +1,123 @@
+pub mod data;
 pub mod error;
 pub mod key;
+pub mod timing;
 
-// /// Load helper.
-// ///
-// /// Call this function whenever you need to load a resource and that you want logged information,
-// /// such as failures, timing, etc.
-// pub fn load_with<T, A, E, F>(
-//   path: &Path,
-//   loader: F
-// ) -> Result<A, E>
-// where F: FnOnce() -> Result<A, E>,
-//       T: TyDesc {
-//   info!("loading {} {}", T::TY_DESC, path.display());
-// 
-//   let start_time = Instant::now();
-//   let r = loader();
-//   let t = start_time.elapsed();
-//   let ns = t.as_secs() as f64 * 1e9 + t.subsec_nanos() as f64;
-//   let (pretty_time, suffix) = load_time(ns);
-// 
-//   if let Ok(_) = r {
-//     info!("loaded {} {}: {:.3}{}", T::TY_DESC, path.display(), pretty_time, suffix);
-//   } else {
-//     err!("fail to load {} {}: {:.3}{}", T::TY_DESC, path.display(), pretty_time, suffix);
-//   }
-// 
-//   r
-// }
+use std::fmt::Display;
+use std::time::Instant;
+use warmy::Loaded;
+
+use crate::logger::Logger;
+use crate::resource::timing::{LoadRecord, PrettyDuration, kind_of, record};
+
+/// Load helper.
+///
+/// Call this function from [`Load`](warmy::Load) implementations to get logged information about
+/// the loads – the key, the kind of resource, the time it took, whether it failed and the number
+/// of dependencies – through the context’s logger. Loads made while initializing a demo are also
+/// [recorded](timing) for the summary that runners print afterwards.
+pub fn load_with<T, C, K, E, F>(key: &K, context: &mut C, loader: F) -> Result<Loaded<T, K>, E>
+where C: Logger,
+      K: Display,
+      E: Display,
+      F: FnOnce(&mut C) -> Result<Loaded<T, K>, E> {
+  instrument(key, context, false, loader)
+}
+
+/// Reload helper.
+///
+/// The reload counterpart of [`load_with`], for [`Load::reload`](warmy::Load::reload)
+/// implementations.
+pub fn reload_with<T, C, K, E, F>(key: &K, context: &mut C, loader: F) -> Result<T, E>
+where C: Logger,
+      K: Display,
+      E: Display,
+      F: FnOnce(&mut C) -> Result<Loaded<T, K>, E> {
+  instrument(key, context, true, loader).map(|loaded| loaded.res)
+}
+
+fn instrument<T, C, K, E, F>(key: &K, context: &mut C, reload: bool, loader: F) -> Result<Loaded<T, K>, E>
+where C: Logger,
+      K: Display,
+      E: Display,
+      F: FnOnce(&mut C) -> Result<Loaded<T, K>, E> {
+  let kind = kind_of::<T>();
+  let verb = if reload { "reload" } else { "load" };
+
+  debug!(context, "{}ing {} {}", verb, kind, key);
+
+  let start = Instant::now();
+  let r = loader(context);
+  let duration = start.elapsed();
+
+  let deps = match r {
+    Ok(ref loaded) => {
+      info!(
+        context,
+        "{}ed {} {} in {} ({} dependencies)",
+        verb,
+        kind,
+        key,
+        (PrettyDuration(duration)),
+        (loaded.deps.len())
+      );
+
+      loaded.deps.len()
+    }
+
+    Err(ref e) => {
+      error!(context, "cannot {} {} {} after {}: {}", verb, kind, key, (PrettyDuration(duration)), e);
+      0
+    }
+  };
+
+  record(LoadRecord { key: key.to_string(), kind, duration, deps, success: r.is_ok(), reload });
+
+  r
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::logger::{Buffer, Level};
+  use crate::resource::timing::{start_recording, take_records};
+
+  /// Strip the durations from the logs.
+  fn untimed(logs: &[(Level, String)]) -> Vec<String> {
+    logs.iter().map(|(level, log)| {
+      let log = format!("{}: {}", level, log);
+
+      match (log.find(" in "), log.find(" after ")) {
+        (Some(i), _) => format!("{} in _{}", &log[..i], &log[log.rfind(" (").unwrap()..]),
+        (_, Some(i)) => format!("{} after _{}", &log[..i], &log[log.rfind(':').unwrap()..]),
+        _ => log
+      }
+    }).collect()
+  }
+
+  #[test]
+  fn loads_are_logged_and_recorded() {
+    let mut journal = Buffer::new();
+    start_recording();
+
+    let loaded = load_with(&"fs:a.json", &mut journal, |_| {
+      Ok::<_, String>(Loaded::with_deps(3u32, vec!["fs:b.json", "fs:c.json"]))
+    });
+    assert_eq!(loaded.unwrap().res, 3);
+
+    let reloaded = reload_with(&"fs:a.json", &mut journal, |_| Err::<Loaded<u32, &str>, _>("nope".to_owned()));
+    assert_eq!(reloaded, Err("nope".to_owned()));
+
+    assert_eq!(untimed(journal.messages()), vec![
+      "debug: loading u32 fs:a.json",
+      "info: loaded u32 fs:a.json in _ (2 dependencies)",
+      "debug: reloading u32 fs:a.json",
+      "error: cannot reload u32 fs:a.json after _: nope"
+    ]);
+
+    let records = take_records();
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].kind.as_str(), records[0].deps, records[0].success, records[0].reload), ("u32", 2, true, false));
+    assert_eq!((records[1].key.as_str(), records[1].deps, records[1].success, records[1].reload), ("fs:a.json", 0, false, true));
+  }
+}
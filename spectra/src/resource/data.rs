@@ -0,0 +1,378 @@
+//! Resources deserialized from JSON, RON or TOML files.
+//!
+//! Any type implementing [`DeserializeOwned`] can be loaded from a file, in the format given by
+//! its extension – see [`Format`]. In a [`Store`](warmy::Store), such values are wrapped in
+//! [`Data`], or get a [`Load`] implementation of their own with [`impl_load_via_serde!`].
+//!
+//! RON and TOML require the “ron” and “toml” features, enabled by default.
+//!
+//! When a file changes and can’t be deserialized anymore, the reload fails and the store keeps the
+//! last good value, so that a typo doesn’t break a running demo.
+
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use warmy::{Load, Loaded, Storage};
+
+use crate::logger::Logger;
+use crate::resource::{load_with, reload_with};
+use crate::resource::error::Error;
+use crate::resource::key::Key;
+
+#[doc(hidden)]
+pub use warmy as __warmy;
+
+/// Format of a file.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Format {
+  /// “json” extension.
+  Json,
+  /// “ron” extension.
+  Ron,
+  /// “toml” extension.
+  Toml
+}
+
+impl Format {
+  /// Format of a file, given by its extension – case insensitive.
+  pub fn from_path<P>(path: P) -> Option<Self> where P: AsRef<Path> {
+    let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+
+    match ext.as_str() {
+      "json" => Some(Format::Json),
+      "ron" => Some(Format::Ron),
+      "toml" => Some(Format::Toml),
+      _ => None
+    }
+  }
+
+  /// Deserialize a value.
+  ///
+  /// The error contains the line and column of the failure, when known.
+  pub fn deserialize<T>(self, content: &str) -> Result<T, String> where T: DeserializeOwned {
+    match self {
+      Format::Json => from_json(content),
+      Format::Ron => from_ron(content),
+      Format::Toml => from_toml(content)
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Format::Json => f.write_str("JSON"),
+      Format::Ron => f.write_str("RON"),
+      Format::Toml => f.write_str("TOML")
+    }
+  }
+}
+
+/// Read and deserialize a file, in the format given by its extension.
+pub fn load<T, P>(path: P) -> Result<T, Error> where T: DeserializeOwned, P: AsRef<Path> {
+  let path = path.as_ref();
+  let fail = |reason: String| Error::CannotLoadFromFS(path.to_owned(), reason);
+  let format = Format::from_path(path).ok_or_else(|| fail("expected a json, ron or toml file".to_owned()))?;
+  let content = fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+
+  format.deserialize(&content).map_err(|e| fail(format!("invalid {}: {}", format, e)))
+}
+
+/// A deserialized resource.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Data<T>(pub T);
+
+impl<T> Data<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> Deref for Data<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<C, T> Load<C, Key> for Data<T> where C: Logger, T: 'static + DeserializeOwned {
+  type Error = Error;
+
+  fn load(key: Key, _: &mut Storage<C, Key>, context: &mut C) -> Result<Loaded<Self, Key>, Self::Error> {
+    load_with(&key, context, |_| load_key(&key).map(|value: T| Data(value).into()))
+  }
+
+  fn reload(&self, key: Key, _: &mut Storage<C, Key>, context: &mut C) -> Result<Self, Self::Error> {
+    reload_with(&key, context, |_| load_key(&key).map(|value: T| Data(value).into()))
+  }
+}
+
+/// Deserialize the file of a key.
+///
+/// This is the loading function used by [`Data`] and [`impl_load_via_serde!`], which also log the
+/// loads through the context.
+pub fn load_key<T>(key: &Key) -> Result<T, Error> where T: DeserializeOwned {
+  match key.path() {
+    Some(path) => load(path),
+    None => Err(Error::CannotLoadFromLogical(key.to_string(), "deserialized resources are loaded from files".to_owned()))
+  }
+}
+
+/// Implement [`Load`] for types implementing [`DeserializeOwned`], so that they can be stored
+/// without the [`Data`] wrapper.
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Camera {
+///   fovy: f32,
+///   position: [f32; 3]
+/// }
+///
+/// impl_load_via_serde!(Camera);
+/// ```
+#[macro_export]
+macro_rules! impl_load_via_serde {
+  ($t:ty) => {
+    impl<C> $crate::resource::data::__warmy::Load<C, $crate::resource::key::Key> for $t
+    where C: $crate::logger::Logger {
+      type Error = $crate::resource::error::Error;
+
+      fn load(
+        key: $crate::resource::key::Key,
+        _: &mut $crate::resource::data::__warmy::Storage<C, $crate::resource::key::Key>,
+        context: &mut C
+      ) -> Result<$crate::resource::data::__warmy::Loaded<Self, $crate::resource::key::Key>, Self::Error> {
+        $crate::resource::load_with(&key, context, |_| {
+          $crate::resource::data::load_key::<$t>(&key).map($crate::resource::data::__warmy::Loaded::from)
+        })
+      }
+
+      fn reload(
+        &self,
+        key: $crate::resource::key::Key,
+        _: &mut $crate::resource::data::__warmy::Storage<C, $crate::resource::key::Key>,
+        context: &mut C
+      ) -> Result<Self, Self::Error> {
+        $crate::resource::reload_with(&key, context, |_| {
+          $crate::resource::data::load_key::<$t>(&key).map($crate::resource::data::__warmy::Loaded::from)
+        })
+      }
+    }
+  }
+}
+
+/// Implement [`Load`] for types implementing [`DeserializeOwned`].
+///
+/// This is [`impl_load_via_serde!`] under its former name: despite it, the format is picked by
+/// the extension of the file, as for any deserialized resource.
+#[macro_export]
+macro_rules! impl_load_json_via {
+  ($t:ty) => {
+    $crate::impl_load_via_serde!($t);
+  }
+}
+
+fn from_json<T>(content: &str) -> Result<T, String> where T: DeserializeOwned {
+  serde_json::from_str(content).map_err(|e| {
+    // serde_json appends the position to its messages
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+    let message = message.trim_end_matches(suffix.as_str());
+
+    at(Some((e.line(), e.column())), message)
+  })
+}
+
+#[cfg(feature = "ron")]
+fn from_ron<T>(content: &str) -> Result<T, String> where T: DeserializeOwned {
+  ron::de::from_str(content).map_err(|e| match e {
+    ron::de::Error::Parser(code, position) => at(Some((position.line, position.col)), &code.to_string()),
+    e => at(None, &e.to_string())
+  })
+}
+
+#[cfg(not(feature = "ron"))]
+fn from_ron<T>(_: &str) -> Result<T, String> {
+  Err("RON resources require the “ron” feature".to_owned())
+}
+
+#[cfg(feature = "toml")]
+fn from_toml<T>(content: &str) -> Result<T, String> where T: DeserializeOwned {
+  toml::from_str(content).map_err(|e| {
+    // toml positions are 0-based, and the line is appended to its messages
+    let position = e.line_col().map(|(line, col)| (line + 1, col + 1));
+    let message = e.to_string();
+    let message = match position {
+      Some((line, _)) => message.trim_end_matches(format!(" at line {}", line).as_str()).to_owned(),
+      None => message
+    };
+
+    at(position, &message)
+  })
+}
+
+#[cfg(not(feature = "toml"))]
+fn from_toml<T>(_: &str) -> Result<T, String> {
+  Err("TOML resources require the “toml” feature".to_owned())
+}
+
+/// Prefix a message with a position, when known.
+fn at(position: Option<(usize, usize)>, message: &str) -> String {
+  match position {
+    Some((line, col)) => format!("line {}, column {}: {}", line, col, message),
+    None => message.to_owned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_derive::Deserialize;
+  use warmy::{Store, StoreOpt};
+
+  use crate::logger::{Buffer, Level, StdoutLogger};
+  use crate::testing::ScratchDir;
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, PartialEq)]
+  struct Camera {
+    fovy: f32,
+    position: [f32; 3]
+  }
+
+  impl_load_via_serde!(Camera);
+
+  #[derive(Debug, Deserialize, PartialEq)]
+  struct Exposure(f32);
+
+  impl_load_json_via!(Exposure);
+
+  fn camera() -> Camera {
+    Camera { fovy: 1.5, position: [0., 1., -2.] }
+  }
+
+  #[test]
+  fn formats() {
+    assert_eq!(Format::from_path("camera.json"), Some(Format::Json));
+    assert_eq!(Format::from_path("a/b/camera.RON"), Some(Format::Ron));
+    assert_eq!(Format::from_path("camera.toml"), Some(Format::Toml));
+    assert_eq!(Format::from_path("camera.yaml"), None);
+    assert_eq!(Format::from_path("camera"), None);
+  }
+
+  #[test]
+  fn load_json() {
+    let dir = ScratchDir::new("data-load-json");
+    let path = dir.write("camera.json", r#"{ "fovy": 1.5, "position": [0, 1, -2] }"#);
+    assert_eq!(load::<Camera, _>(&path).unwrap(), camera());
+  }
+
+  #[test]
+  fn json_errors_have_positions() {
+    let dir = ScratchDir::new("data-json-errors");
+    let path = dir.write("broken.json", "{\n  \"fovy\": 1.5,\n  \"position\": [0, 1,]\n}");
+
+    match load::<Camera, _>(&path) {
+      Err(Error::CannotLoadFromFS(p, reason)) => {
+        assert_eq!(p, path);
+        assert!(reason.starts_with("invalid JSON: line 3, column 21: "), "{}", reason);
+        assert!(!reason.contains(" at line "), "{}", reason);
+      }
+
+      r => panic!("unexpected result: {:?}", r)
+    }
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn load_toml() {
+    let dir = ScratchDir::new("data-load-toml");
+    let path = dir.write("camera.toml", "fovy = 1.5\nposition = [0.0, 1.0, -2.0]\n");
+    assert_eq!(load::<Camera, _>(&path).unwrap(), camera());
+
+    let path = dir.write("broken.toml", "fovy = 1.5\nposition = [0.0, 1.0, -2.0\n");
+    match load::<Camera, _>(&path) {
+      Err(Error::CannotLoadFromFS(_, reason)) => assert_eq!(reason, "invalid TOML: line 3, column 1: expected a right bracket, found eof"),
+      r => panic!("unexpected result: {:?}", r)
+    }
+  }
+
+  #[cfg(feature = "ron")]
+  #[test]
+  fn load_ron() {
+    let dir = ScratchDir::new("data-load-ron");
+    let path = dir.write("camera.ron", "(fovy: 1.5, position: (0, 1, -2))");
+    assert_eq!(load::<Camera, _>(&path).unwrap(), camera());
+
+    let path = dir.write("broken.ron", "(\n  fovy: 1.5,\n  position: (0, 1, -2\n");
+    match load::<Camera, _>(&path) {
+      Err(Error::CannotLoadFromFS(_, reason)) => assert!(reason.starts_with("invalid RON: line "), "{}", reason),
+      r => panic!("unexpected result: {:?}", r)
+    }
+  }
+
+  #[test]
+  fn load_errors() {
+    let dir = ScratchDir::new("data-load-errors");
+    let unknown = dir.write("camera.yaml", "fovy: 1.5");
+    assert!(matches!(load::<Camera, _>(&unknown), Err(Error::CannotLoadFromFS(..))));
+    assert!(matches!(load::<Camera, _>("no/such/camera.json"), Err(Error::CannotLoadFromFS(..))));
+
+    assert!(matches!(load_key::<Camera>(&Key::logical("camera")), Err(Error::CannotLoadFromLogical(..))));
+    assert!(matches!(load_key::<Camera>(&Key::pack("intro/camera.json")), Err(Error::CannotLoadFromLogical(..))));
+
+    let key = Key::fs(dir.write("key.json", "[1, 2]"));
+    assert_eq!(load_key::<Vec<u8>>(&key).unwrap(), vec![1, 2]);
+  }
+
+  #[test]
+  fn get_with_typed_key() {
+    let dir = ScratchDir::new("data-typed-key");
+    dir.write("typed.json", "[3, 4]");
+
+    let mut store = Store::new(StoreOpt::default().set_root(dir.path())).unwrap();
+    let mut logger = StdoutLogger;
+    let key: Key<Data<Vec<u8>>> = "fs:typed.json".parse().unwrap();
+
+    match key.get(&mut store, &mut logger) {
+      Ok(data) => assert_eq!(**data.borrow(), vec![3, 4]),
+      Err(_) => panic!("cannot get {}", key)
+    }
+  }
+
+  #[test]
+  fn failed_reloads_keep_the_last_good_value() {
+    let dir = ScratchDir::new("data-failed-reload");
+    dir.write("exposure.json", "1.5");
+
+    let mut store = Store::new(StoreOpt::default().set_root(dir.path())).unwrap();
+    let mut logger = Buffer::new();
+    let key: Key<Exposure> = "fs:exposure.json".parse().unwrap();
+    let exposure = match key.get(&mut store, &mut logger) {
+      Ok(exposure) => exposure,
+      Err(_) => panic!("cannot get {}", key)
+    };
+    assert_eq!(*exposure.borrow(), Exposure(1.5));
+
+    dir.write("exposure.json", "1.5,");
+
+    // reloads happen once the store has noticed the change
+    for _ in 0..200 {
+      store.sync(&mut logger);
+
+      if logger.messages().iter().any(|(level, _)| *level == Level::Error) {
+        break;
+      }
+
+      std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let errors: Vec<_> = logger.messages().iter().filter(|(level, _)| *level == Level::Error).collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].1.starts_with("cannot reload "), "{}", errors[0].1);
+    assert_eq!(*exposure.borrow(), Exposure(1.5));
+  }
+}
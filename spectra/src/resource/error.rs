@@ -1,5 +1,6 @@
 //! Error that might occur while loading a resource.
 
+use std::error;
 use std::fmt;
 use std::path::PathBuf;
 
@@ -26,3 +27,5 @@ impl fmt::Display for Error {
     }
   }
 }
+
+impl error::Error for Error {}
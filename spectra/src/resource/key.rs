@@ -1,29 +1,281 @@
 //! Key type used to index resources.
+//!
+//! Keys are namespaced names, written `namespace:name`:
+//!
+//!   - `fs:textures/noise.png` is a file, relative to the root of the store.
+//!   - `logical:gbuffer` is a resource that doesn’t come from a file, such as a framebuffer.
+//!   - `pack:intro/mesh.obj` is an entry of a pack.
+//!
+//! A name without any namespace is a file. Keys are parsed with [`FromStr`], written back with
+//! [`Display`](fmt::Display), and (de)serialized as such strings.
+//!
+//! A [`Key<T>`] is typed by the resource it indexes, so that a texture key can’t be used to fetch a
+//! mesh. The store itself is indexed by untyped keys – [`Key`], which is `Key<Any>`.
 
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
 use std::fmt;
-use std::ops::Deref;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use warmy::{Load, Res, SimpleKey, Store, StoreErrorOr};
 
-/// Type of key used to index resources.
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
-pub struct Key(warmy::SimpleKey);
+/// Namespace of a key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Namespace {
+  /// Files, relative to the root of the store.
+  Fs,
+  /// Resources that don’t come from files.
+  Logical,
+  /// Entries of packs.
+  Pack
+}
+
+impl Namespace {
+  /// Prefix of the namespace, without the colon.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Namespace::Fs => "fs",
+      Namespace::Logical => "logical",
+      Namespace::Pack => "pack"
+    }
+  }
+}
+
+impl fmt::Display for Namespace {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for Namespace {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "fs" => Ok(Namespace::Fs),
+      "logical" => Ok(Namespace::Logical),
+      "pack" => Ok(Namespace::Pack),
+      _ => Err(format!("invalid key namespace “{}”; expected fs, logical or pack", s))
+    }
+  }
+}
+
+/// Any kind of resource.
+///
+/// This is the type of untyped keys.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Any {}
+
+/// Type of key used to index resources of type `T`.
+pub struct Key<T = Any> {
+  namespace: Namespace,
+  // paths for files, logical names for the other namespaces
+  key: SimpleKey,
+  _resource: PhantomData<fn() -> T>
+}
+
+impl Key {
+  /// Key of a file, relative to the root of the store.
+  pub fn fs<P>(path: P) -> Self where P: Into<PathBuf> {
+    Key::new(Namespace::Fs, SimpleKey::Path(path.into()))
+  }
+
+  /// Key of a resource that doesn’t come from a file.
+  pub fn logical<N>(name: N) -> Self where N: Into<String> {
+    Key::new(Namespace::Logical, SimpleKey::Logical(name.into()))
+  }
+
+  /// Key of an entry of a pack.
+  pub fn pack<N>(name: N) -> Self where N: Into<String> {
+    Key::new(Namespace::Pack, SimpleKey::Logical(name.into()))
+  }
+
+  /// Type the key by the resource it indexes.
+  pub fn typed<T>(self) -> Key<T> {
+    Key::new(self.namespace, self.key)
+  }
+}
+
+impl<T> Key<T> {
+  fn new(namespace: Namespace, key: SimpleKey) -> Self {
+    Key { namespace, key, _resource: PhantomData }
+  }
+
+  /// Namespace of the key.
+  pub fn namespace(&self) -> Namespace {
+    self.namespace
+  }
+
+  /// Path of the file, for [`Namespace::Fs`] keys.
+  pub fn path(&self) -> Option<&Path> {
+    match self.key {
+      SimpleKey::Path(ref path) => Some(path),
+      SimpleKey::Logical(_) => None
+    }
+  }
+
+  /// Name of the resource, for [`Namespace::Logical`] and [`Namespace::Pack`] keys.
+  pub fn name(&self) -> Option<&str> {
+    match self.key {
+      SimpleKey::Path(_) => None,
+      SimpleKey::Logical(ref name) => Some(name)
+    }
+  }
+
+  /// Untyped key, as used to index the store.
+  pub fn untyped(&self) -> Key {
+    Key::new(self.namespace, self.key.clone())
+  }
+
+  /// Get the resource from a store, loading it if needed.
+  pub fn get<C>(&self, store: &mut Store<C, Key>, context: &mut C) -> Result<Res<T>, StoreErrorOr<T, C, Key>>
+  where T: Load<C, Key> {
+    store.get(&self.untyped(), context)
+  }
+}
+
+// the impls below are written by hand so that they don’t require anything from T
+
+impl<T> Clone for Key<T> {
+  fn clone(&self) -> Self {
+    Key::new(self.namespace, self.key.clone())
+  }
+}
+
+impl<T> fmt::Debug for Key<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(f, "Key({})", self)
+  }
+}
+
+impl<T> PartialEq for Key<T> {
+  fn eq(&self, rhs: &Self) -> bool {
+    self.namespace == rhs.namespace && self.key == rhs.key
+  }
+}
+
+impl<T> Eq for Key<T> {}
+
+impl<T> Hash for Key<T> {
+  fn hash<H>(&self, state: &mut H) where H: Hasher {
+    self.namespace.hash(state);
+    self.key.hash(state);
+  }
+}
 
-impl fmt::Display for Key {
+impl<T> fmt::Display for Key<T> {
   fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-    self.0.fmt(f)
+    match self.key {
+      SimpleKey::Path(ref path) => write!(f, "{}:{}", self.namespace, path.display()),
+      SimpleKey::Logical(ref name) => write!(f, "{}:{}", self.namespace, name)
+    }
+  }
+}
+
+impl<T> FromStr for Key<T> {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (namespace, name) = match s.find(':') {
+      Some(i) => (s[..i].parse()?, &s[i + 1..]),
+      None => (Namespace::Fs, s)
+    };
+
+    if name.is_empty() {
+      return Err(format!("invalid key “{}”; the name is empty", s));
+    }
+
+    let key = match namespace {
+      Namespace::Fs => Key::fs(name),
+      Namespace::Logical => Key::logical(name),
+      Namespace::Pack => Key::pack(name)
+    };
+
+    Ok(key.typed())
+  }
+}
+
+impl<T> Serialize for Key<T> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de, T> Deserialize<'de> for Key<T> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
   }
 }
 
 impl warmy::Key for Key {
   fn prepare_key(self, root: &Path) -> Self {
-    Key(self.0.prepare_key(root))
+    match self.namespace {
+      Namespace::Fs => Key::new(self.namespace, self.key.prepare_key(root)),
+      _ => self
+    }
   }
 }
 
-impl Deref for Key {
-  type Target = warmy::SimpleKey;
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct Texture;
+
+  #[test]
+  fn parse() {
+    assert_eq!("fs:textures/noise.png".parse(), Ok(Key::fs("textures/noise.png")));
+    assert_eq!("textures/noise.png".parse(), Ok(Key::fs("textures/noise.png")));
+    assert_eq!("logical:gbuffer".parse(), Ok(Key::logical("gbuffer")));
+    assert_eq!("pack:intro/mesh.obj".parse(), Ok(Key::pack("intro/mesh.obj")));
+    assert_eq!("logical:a:b".parse::<Key>().unwrap().name(), Some("a:b"));
+
+    assert!("logical:".parse::<Key>().is_err());
+    assert!("http://example.com".parse::<Key>().is_err());
+  }
+
+  #[test]
+  fn round_trip() {
+    for s in &["fs:textures/noise.png", "logical:gbuffer", "pack:intro/mesh.obj"] {
+      let key = s.parse::<Key>().unwrap();
+      assert_eq!(&key.to_string(), s);
+      assert_eq!(key.to_string().parse(), Ok(key));
+    }
+  }
+
+  #[test]
+  fn namespaces_are_distinct() {
+    assert_ne!(Key::logical("intro/mesh.obj"), Key::pack("intro/mesh.obj"));
+    assert_eq!(Key::logical("gbuffer").namespace(), Namespace::Logical);
+    assert_eq!(Key::fs("noise.png").path(), Some(Path::new("noise.png")));
+    assert_eq!(Key::fs("noise.png").name(), None);
+  }
+
+  #[test]
+  fn typed() {
+    let key: Key<Texture> = "fs:noise.png".parse().unwrap();
+
+    assert_eq!(key, Key::fs("noise.png").typed());
+    assert_eq!(key.untyped(), Key::fs("noise.png"));
+    assert_eq!(format!("{:?}", key), "Key(fs:noise.png)");
+  }
+
+  #[test]
+  fn serde() {
+    let keys: Vec<Key<Texture>> = serde_json::from_str(r#"["fs:noise.png", "pack:intro/a.png"]"#).unwrap();
+
+    assert_eq!(keys, vec![Key::fs("noise.png").typed(), Key::pack("intro/a.png").typed()]);
+    assert_eq!(serde_json::to_string(&keys).unwrap(), r#"["fs:noise.png","pack:intro/a.png"]"#);
+    assert!(serde_json::from_str::<Key>(r#""nope:a""#).is_err());
+  }
+
+  #[test]
+  fn prepare() {
+    use warmy::Key as _;
 
-  fn deref(&self) -> &Self::Target {
-    &self.0
+    assert_eq!(Key::fs("noise.png").prepare_key(Path::new("/data")), Key::fs("/data/noise.png"));
+    assert_eq!(Key::pack("intro/a.png").prepare_key(Path::new("/data")), Key::pack("intro/a.png"));
   }
 }
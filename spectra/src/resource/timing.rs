@@ -0,0 +1,212 @@
+//! Timings of resource loads.
+//!
+//! Loads and reloads going through [`load_with`](crate::resource::load_with) or
+//! [`reload_with`](crate::resource::reload_with) are recorded in a per-thread journal while it’s
+//! [recording](start_recording). Runners record the initialization of a demo and
+//! [log the slowest loads](log_slowest) afterwards, to find what makes it start slowly; the loads
+//! made later on – lazy loads, hot reloads – are not kept.
+
+use std::any::type_name;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::fmt;
+use std::time::Duration;
+
+use crate::logger::Logger;
+
+/// Number of loads listed by runners after initialization.
+pub const SLOWEST_COUNT: usize = 10;
+
+thread_local! {
+  // None while not recording
+  static JOURNAL: RefCell<Option<Vec<LoadRecord>>> = const { RefCell::new(None) };
+}
+
+/// A load – or reload – of a resource.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LoadRecord {
+  /// Key of the resource.
+  pub key: String,
+  /// Kind of resource, such as its type name.
+  pub kind: String,
+  pub duration: Duration,
+  /// Number of dependencies of the resource; 0 on failure.
+  pub deps: usize,
+  /// Whether the resource was loaded.
+  pub success: bool,
+  /// Whether it was a reload.
+  pub reload: bool
+}
+
+/// Start recording the loads of the current thread, until they’re [taken](take_records).
+///
+/// The loads recorded so far are kept.
+pub fn start_recording() {
+  JOURNAL.with(|journal| {
+    journal.borrow_mut().get_or_insert_with(Vec::new);
+  });
+}
+
+/// Record a load in the journal of the current thread, if it’s recording.
+pub fn record(record: LoadRecord) {
+  JOURNAL.with(|journal| {
+    if let Some(ref mut records) = *journal.borrow_mut() {
+      records.push(record);
+    }
+  });
+}
+
+/// Take the loads recorded in the current thread, in order, and stop recording.
+pub fn take_records() -> Vec<LoadRecord> {
+  JOURNAL.with(|journal| journal.borrow_mut().take().unwrap_or_default())
+}
+
+/// Log a table of the slowest loads recorded in the current thread, and stop recording.
+pub fn log_slowest<L>(logger: &mut L, count: usize) where L: ?Sized + Logger {
+  let mut records = take_records();
+
+  if records.is_empty() {
+    return;
+  }
+
+  let total = records.iter().map(|record| record.duration).sum::<Duration>();
+  records.sort_by_key(|record| Reverse(record.duration));
+
+  info!(
+    logger,
+    "{} resource loads in {}; the slowest are:",
+    (records.len()),
+    (PrettyDuration(total))
+  );
+
+  let shown = &records[..count.min(records.len())];
+  let kind_width = shown.iter().map(|record| record.kind.chars().count()).max().unwrap_or(0);
+
+  for record in shown {
+    info!(
+      logger,
+      "  {:>10}  {:<4$}  {}{}",
+      (PrettyDuration(record.duration).to_string()),
+      (record.kind),
+      (record.key),
+      (if record.success { "" } else { " (failed)" }),
+      kind_width
+    );
+  }
+}
+
+/// Short name of a type, without the paths: `Data<Camera>` instead of
+/// `spectra::resource::data::Data<demo::Camera>`.
+pub fn kind_of<T>() -> String where T: ?Sized {
+  let name = type_name::<T>();
+  let mut short = String::with_capacity(name.len());
+  let mut segment = 0;
+  let mut chars = name.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    if c == ':' && chars.peek() == Some(&':') {
+      chars.next();
+      short.truncate(segment);
+    } else {
+      short.push(c);
+
+      if !(c.is_alphanumeric() || c == '_') {
+        segment = short.len();
+      }
+    }
+  }
+
+  short
+}
+
+/// Duration displayed with the most fitting unit – ns, µs, ms or s.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrettyDuration(pub Duration);
+
+impl fmt::Display for PrettyDuration {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    let ns = self.0.as_nanos();
+
+    if ns < 1_000 {
+      write!(f, "{}ns", ns)
+    } else if ns < 1_000_000 {
+      write!(f, "{:.3}µs", ns as f64 * 1e-3)
+    } else if ns < 1_000_000_000 {
+      write!(f, "{:.3}ms", ns as f64 * 1e-6)
+    } else {
+      write!(f, "{:.3}s", ns as f64 * 1e-9)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::logger::{Buffer, Level};
+
+  fn load(key: &str, kind: &str, micros: u64, success: bool) -> LoadRecord {
+    LoadRecord {
+      key: key.to_owned(),
+      kind: kind.to_owned(),
+      duration: Duration::from_micros(micros),
+      deps: 0,
+      success,
+      reload: false
+    }
+  }
+
+  #[test]
+  fn pretty_durations() {
+    assert_eq!(PrettyDuration(Duration::from_nanos(532)).to_string(), "532ns");
+    assert_eq!(PrettyDuration(Duration::from_nanos(12_345)).to_string(), "12.345µs");
+    assert_eq!(PrettyDuration(Duration::from_micros(1_500)).to_string(), "1.500ms");
+    assert_eq!(PrettyDuration(Duration::from_millis(2_250)).to_string(), "2.250s");
+  }
+
+  #[test]
+  fn kinds() {
+    assert_eq!(kind_of::<u32>(), "u32");
+    assert_eq!(kind_of::<Vec<String>>(), "Vec<String>");
+    assert_eq!(kind_of::<LoadRecord>(), "LoadRecord");
+    assert_eq!(kind_of::<(Duration, Option<PrettyDuration>)>(), "(Duration, Option<PrettyDuration>)");
+  }
+
+  #[test]
+  fn slowest_loads() {
+    let mut logger = Buffer::new();
+
+    log_slowest(&mut logger, 2);
+    assert!(logger.messages().is_empty());
+
+    start_recording();
+    record(load("fs:a.json", "Data<Camera>", 300, true));
+    record(load("fs:noise.png", "Texture", 2_000, true));
+    record(load("logical:gbuffer", "Framebuffer", 50, true));
+    record(load("fs:broken.json", "Data<Camera>", 900, false));
+    log_slowest(&mut logger, 2);
+
+    let logs: Vec<_> = logger.messages().iter().map(|(level, log)| (*level, log.as_str())).collect();
+    assert_eq!(logs, vec![
+      (Level::Info, "4 resource loads in 3.250ms; the slowest are:"),
+      (Level::Info, "     2.000ms  Texture       fs:noise.png"),
+      (Level::Info, "   900.000µs  Data<Camera>  fs:broken.json (failed)")
+    ]);
+    assert!(take_records().is_empty());
+  }
+
+  #[test]
+  fn records_only_while_recording() {
+    record(load("fs:lazy.json", "Data<Camera>", 100, true));
+    assert!(take_records().is_empty());
+
+    start_recording();
+    record(load("fs:a.json", "Data<Camera>", 300, true));
+    start_recording();
+    record(load("fs:b.json", "Data<Camera>", 200, true));
+    assert_eq!(take_records(), vec![load("fs:a.json", "Data<Camera>", 300, true), load("fs:b.json", "Data<Camera>", 200, true)]);
+
+    // the journal doesn’t grow once taken
+    record(load("fs:a.json", "Data<Camera>", 150, true));
+    assert!(take_records().is_empty());
+  }
+}
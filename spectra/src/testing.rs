@@ -0,0 +1,76 @@
+//! Test fixtures.
+
+use std::{env, fs, process};
+use std::fmt::Arguments;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+use crate::logger::{Buffer, Logger};
+
+/// Temporary directory, unique to a test and to the process running it, removed when dropped.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+  /// Create the directory of a test; `name` must be unique among the tests.
+  pub fn new(name: &str) -> Self {
+    let path = env::temp_dir().join(format!("spectra-{}-{}", name, process::id()));
+    fs::create_dir_all(&path).unwrap();
+    ScratchDir(path)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.0
+  }
+
+  /// Write a file in the directory and get its path.
+  pub fn write<C>(&self, name: &str, content: C) -> PathBuf where C: AsRef<[u8]> {
+    let path = self.0.join(name);
+    fs::write(&path, content).unwrap();
+    path
+  }
+}
+
+impl Drop for ScratchDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
+
+/// Demo context holding some state and keeping the messages logged to it.
+#[derive(Default)]
+pub struct Recorder<T> {
+  pub state: T,
+  pub logs: Buffer
+}
+
+impl<T> Deref for Recorder<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.state
+  }
+}
+
+impl<T> DerefMut for Recorder<T> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.state
+  }
+}
+
+impl<T> Logger for Recorder<T> {
+  fn info(&mut self, args: Arguments) {
+    self.logs.info(args);
+  }
+
+  fn debug(&mut self, args: Arguments) {
+    self.logs.debug(args);
+  }
+
+  fn warn(&mut self, args: Arguments) {
+    self.logs.warn(args);
+  }
+
+  fn error(&mut self, args: Arguments) {
+    self.logs.error(args);
+  }
+}
@@ -1,17 +1,52 @@
+use serde_derive::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
 use std::str::FromStr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::fmt;
 
+pub mod clock;
+pub mod marker;
+
 /// Absolute time.
-#[derive(Clone, Copy, Debug, PartialEq)]
+///
+/// Times can be added, subtracted, scaled and compared. A time serializes as its number of
+/// seconds.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
 pub struct Time(f64);
 
 impl Time {
+  /// Create a time from seconds.
+  pub fn from_secs(secs: f64) -> Self {
+    Time(secs)
+  }
+
+  /// Create a time from milliseconds.
+  pub fn from_millis(millis: f64) -> Self {
+    Time(millis * 1e-3)
+  }
+
   /// Convert into seconds.
   pub fn as_secs(self) -> f64 {
     self.0
   }
 
+  /// Convert into milliseconds.
+  pub fn as_millis(self) -> f64 {
+    self.0 * 1e3
+  }
+
+  /// Convert into a [`Duration`].
+  ///
+  /// Returns `None` if the time is negative or not finite.
+  pub fn to_duration(self) -> Option<Duration> {
+    if self.0 >= 0. && self.0.is_finite() {
+      Some(Duration::new(self.0.trunc() as u64, (self.0.fract() * 1e9) as u32))
+    } else {
+      None
+    }
+  }
+
   /// Wrap time with a given duration.
   pub fn wrap_around(self, t: Time) -> Self {
     Time(self.0 % t.0)
@@ -19,7 +54,7 @@ impl Time {
 
   /// Offset a time by a given amount of time.
   pub fn offset(self, t: Time) -> Self {
-    Time(self.0 + t.0)
+    self + t
   }
 }
 
@@ -31,7 +66,152 @@ impl fmt::Display for Time {
 
 impl From<DurationSpec> for Time {
   fn from(spec: DurationSpec) -> Self {
-    Time(spec.mins as f64 * 60. + spec.secs as f64)
+    spec.to_time(DurationSpec::DEFAULT_FPS)
+  }
+}
+
+impl From<Duration> for Time {
+  fn from(d: Duration) -> Self {
+    Time(d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9)
+  }
+}
+
+impl Add for Time {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    Time(self.0 + rhs.0)
+  }
+}
+
+impl AddAssign for Time {
+  fn add_assign(&mut self, rhs: Self) {
+    self.0 += rhs.0;
+  }
+}
+
+impl Sub for Time {
+  type Output = Self;
+
+  fn sub(self, rhs: Self) -> Self {
+    Time(self.0 - rhs.0)
+  }
+}
+
+impl SubAssign for Time {
+  fn sub_assign(&mut self, rhs: Self) {
+    self.0 -= rhs.0;
+  }
+}
+
+impl Mul<f64> for Time {
+  type Output = Self;
+
+  fn mul(self, rhs: f64) -> Self {
+    Time(self.0 * rhs)
+  }
+}
+
+impl Div<f64> for Time {
+  type Output = Self;
+
+  fn div(self, rhs: f64) -> Self {
+    Time(self.0 / rhs)
+  }
+}
+
+/// Ratio between two times.
+impl Div for Time {
+  type Output = f64;
+
+  fn div(self, rhs: Self) -> f64 {
+    self.0 / rhs.0
+  }
+}
+
+/// A range of time, including its start and excluding its end.
+///
+/// As with [`TimeRange::new`], the bounds of a deserialized range are swapped if needed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "Bounds")]
+pub struct TimeRange {
+  start: Time,
+  end: Time
+}
+
+/// Unordered bounds of a [`TimeRange`], as deserialized.
+#[derive(Deserialize)]
+struct Bounds {
+  start: Time,
+  end: Time
+}
+
+impl From<Bounds> for TimeRange {
+  fn from(bounds: Bounds) -> Self {
+    TimeRange::new(bounds.start, bounds.end)
+  }
+}
+
+impl TimeRange {
+  /// Create a range between two times.
+  ///
+  /// If `end` is before `start`, they’re swapped.
+  pub fn new(start: Time, end: Time) -> Self {
+    if end < start {
+      TimeRange { start: end, end: start }
+    } else {
+      TimeRange { start, end }
+    }
+  }
+
+  /// Start of the range.
+  pub fn start(&self) -> Time {
+    self.start
+  }
+
+  /// End of the range.
+  pub fn end(&self) -> Time {
+    self.end
+  }
+
+  /// Duration of the range.
+  pub fn duration(&self) -> Time {
+    self.end - self.start
+  }
+
+  /// Check whether a time lies in the range.
+  pub fn contains(&self, t: Time) -> bool {
+    t >= self.start && t < self.end
+  }
+
+  /// Progress of a time through the range, from 0 at its start to 1 at its end.
+  ///
+  /// The progress is clamped to [0; 1]. An empty range has a progress of 1 at its start and after.
+  pub fn progress(&self, t: Time) -> f64 {
+    if t < self.start {
+      0.
+    } else if t >= self.end {
+      1.
+    } else {
+      (t - self.start) / self.duration()
+    }
+  }
+
+  /// Check whether two ranges overlap.
+  pub fn overlaps(&self, other: &TimeRange) -> bool {
+    self.start < other.end && other.start < self.end
+  }
+
+  /// Intersection of two ranges, if they overlap.
+  pub fn intersection(&self, other: &TimeRange) -> Option<TimeRange> {
+    if self.overlaps(other) {
+      let start = if self.start > other.start { self.start } else { other.start };
+      let end = if self.end < other.end { self.end } else { other.end };
+
+      Some(TimeRange { start, end })
+    } else {
+      None
+    }
   }
 }
 
@@ -45,31 +225,108 @@ impl Monotonic {
   }
 
   pub fn elapsed_secs(&self) -> Time {
-    let dur = self.0.elapsed();
-    let secs = dur.as_secs() as f64;
-    let nanos = dur.subsec_nanos() as f64;
-
-    Time(secs + nanos * 1e-9)
+    self.0.elapsed().into()
   }
 }
 
-/// A way to specify a duration with minutes and seconds.
+/// Number of nanoseconds in a second.
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// A way to specify a duration.
 ///
-/// The string format is the following:
+/// The string format supports several notations:
 ///
-///   - `MmSs`, if you want minutes (e.g. `3m43s`).
-///   - `Ss`, if you only have seconds (e.g. `23s`).
+///   - Unit-suffixed components, in decreasing order, each being optional: `1h2m3.25s`,
+///     `3m43s`, `23s`, `2m`, `500ms` or `1.5h`. Only the last component can have a fractional
+///     part. When a larger unit is present, minutes and seconds must not exceed 59 and
+///     milliseconds must not exceed 999.
+///   - Clock notation, `M:SS[.fff]` or `H:MM:SS[.fff]` (e.g. `1:02.250` or `1:00:00`).
+///   - Frame numbers, `@Nf` or `@Nf/FPS`. `@1440f/24` is the 1440th frame at 24 FPS, which is one
+///     minute; `@1440f` is the 1440th frame at whatever frame rate the duration gets resolved
+///     with (see [`DurationSpec::to_time`]).
+///
+/// The [`Display`](fmt::Display) implementation outputs a string that parses back to the same
+/// duration.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct DurationSpec {
-  mins: u8,
-  secs: u8
+pub enum DurationSpec {
+  /// Duration expressed in nanoseconds.
+  Nanos(u64),
+  /// Frame number, with an optional frame rate.
+  Frame {
+    frame: u64,
+    fps: Option<u32>
+  }
+}
+
+impl DurationSpec {
+  /// Frame rate used to resolve frame numbers when none is specified.
+  pub const DEFAULT_FPS: u32 = 60;
+
+  /// Build a duration out of seconds and nanoseconds.
+  ///
+  /// Durations too long to be represented saturate to the longest one, about 584 years.
+  pub fn new(secs: u64, nanos: u32) -> Self {
+    DurationSpec::Nanos(secs.saturating_mul(NANOS_PER_SEC).saturating_add(nanos as u64))
+  }
+
+  /// Build a duration out of a frame number at a given frame rate.
+  pub fn frame(frame: u64, fps: u32) -> Self {
+    DurationSpec::Frame { frame, fps: Some(fps) }
+  }
+
+  /// Resolve the duration as a [`Time`].
+  ///
+  /// `fps` is the frame rate to use for frame numbers without an explicit frame rate.
+  pub fn to_time(self, fps: u32) -> Time {
+    match self {
+      DurationSpec::Nanos(nanos) => {
+        Time((nanos / NANOS_PER_SEC) as f64 + (nanos % NANOS_PER_SEC) as f64 * 1e-9)
+      }
+
+      DurationSpec::Frame { frame, fps: spec_fps } => {
+        Time(frame as f64 / spec_fps.unwrap_or(fps) as f64)
+      }
+    }
+  }
 }
 
 impl Default for DurationSpec {
   fn default() -> Self {
-    DurationSpec {
-      mins: 0,
-      secs: 0
+    DurationSpec::Nanos(0)
+  }
+}
+
+impl fmt::Display for DurationSpec {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      DurationSpec::Nanos(0) => f.write_str("0s"),
+
+      DurationSpec::Nanos(nanos) => {
+        let secs = nanos / NANOS_PER_SEC;
+        let subsec_nanos = nanos % NANOS_PER_SEC;
+        let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+        if hours != 0 {
+          write!(f, "{}h", hours)?;
+        }
+
+        if mins != 0 {
+          write!(f, "{}m", mins)?;
+        }
+
+        if subsec_nanos != 0 {
+          let frac = format!("{:09}", subsec_nanos);
+          write!(f, "{}.{}s", secs, frac.trim_end_matches('0'))
+        } else if secs != 0 {
+          write!(f, "{}s", secs)
+        } else {
+          Ok(())
+        }
+      }
+
+      DurationSpec::Frame { frame, fps: None } => write!(f, "@{}f", frame),
+
+      DurationSpec::Frame { frame, fps: Some(fps) } => write!(f, "@{}f/{}", frame, fps),
     }
   }
 }
@@ -78,58 +335,588 @@ impl FromStr for DurationSpec {
   type Err = DurationSpecError;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    if !s.ends_with('s') {
-      if s.ends_with('m') {
-        // only minutes
-        let mins = s.trim_end_matches('m').parse().map_err(|_| DurationSpecError::CannotParseMinutes)?;
+    let mut parser = Parser::new(s);
+
+    let spec = if s.is_empty() {
+      return Err(DurationSpecError::new(DurationSpecErrorKind::Empty, 0));
+    } else if s.starts_with('@') {
+      parser.frame()?
+    } else if s.contains(':') {
+      parser.clock()?
+    } else {
+      parser.components()?
+    };
+
+    parser.end()?;
+    Ok(spec)
+  }
+}
+
+/// Units available in the unit-suffixed notation, from the smallest to the largest.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Unit {
+  Millis,
+  Secs,
+  Mins,
+  Hours
+}
+
+impl Unit {
+  fn nanos(self) -> u64 {
+    match self {
+      Unit::Millis => 1_000_000,
+      Unit::Secs => NANOS_PER_SEC,
+      Unit::Mins => 60 * NANOS_PER_SEC,
+      Unit::Hours => 3600 * NANOS_PER_SEC,
+    }
+  }
+
+  /// Exclusive upper bound of the integral part of the unit when a larger unit is present.
+  fn bound(self) -> u64 {
+    match self {
+      Unit::Millis => 1000,
+      Unit::Secs | Unit::Mins => 60,
+      Unit::Hours => u64::MAX,
+    }
+  }
+}
+
+/// A decimal number with its fractional part expressed in nanoseconds.
+#[derive(Clone, Copy, Debug)]
+struct Decimal {
+  int: u64,
+  frac_nanos: u64,
+  // whether the number was written with a decimal point, even if its fractional part is zero
+  fractional: bool
+}
+
+impl Decimal {
+  /// Scale the number by a given unit, expressed in nanoseconds.
+  fn scale(self, unit_nanos: u64) -> Option<u64> {
+    let frac = self.frac_nanos as u128 * unit_nanos as u128 / NANOS_PER_SEC as u128;
+    self.int.checked_mul(unit_nanos)?.checked_add(frac as u64)
+  }
+}
+
+/// Hand-written parser over a duration string, tracking positions for error reporting.
+struct Parser<'a> {
+  input: &'a str,
+  pos: usize
+}
+
+impl<'a> Parser<'a> {
+  fn new(input: &'a str) -> Self {
+    Parser { input, pos: 0 }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.input[self.pos..].chars().next()
+  }
+
+  fn bump(&mut self) {
+    if let Some(c) = self.peek() {
+      self.pos += c.len_utf8();
+    }
+  }
+
+  fn error(&self, kind: DurationSpecErrorKind) -> DurationSpecError {
+    DurationSpecError::new(kind, self.pos)
+  }
+
+  /// Error to return when something was expected at the current position.
+  fn unexpected(&self) -> DurationSpecError {
+    match self.peek() {
+      Some(c) => self.error(DurationSpecErrorKind::UnexpectedChar(c)),
+      None => self.error(DurationSpecErrorKind::UnexpectedEnd),
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), DurationSpecError> {
+    if self.peek() == Some(expected) {
+      self.bump();
+      Ok(())
+    } else {
+      Err(self.unexpected())
+    }
+  }
+
+  fn end(&self) -> Result<(), DurationSpecError> {
+    match self.peek() {
+      Some(c) => Err(self.error(DurationSpecErrorKind::UnexpectedChar(c))),
+      None => Ok(())
+    }
+  }
+
+  fn natural(&mut self) -> Result<u64, DurationSpecError> {
+    let start = self.pos;
+    let mut n: u64 = 0;
+
+    while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+      n = n
+        .checked_mul(10)
+        .and_then(|n| n.checked_add(d as u64))
+        .ok_or_else(|| DurationSpecError::new(DurationSpecErrorKind::Overflow, start))?;
+      self.bump();
+    }
 
-        Ok(DurationSpec { mins, secs: 0 })
+    if self.pos == start {
+      Err(self.unexpected())
+    } else {
+      Ok(n)
+    }
+  }
+
+  fn decimal(&mut self) -> Result<Decimal, DurationSpecError> {
+    let int = self.natural()?;
+    let mut frac_nanos = 0;
+    let fractional = self.peek() == Some('.');
+
+    if fractional {
+      self.bump();
+
+      let start = self.pos;
+      let mut scale = NANOS_PER_SEC;
+
+      while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+        if scale == 1 {
+          return Err(self.error(DurationSpecErrorKind::TooPrecise));
+        }
+
+        scale /= 10;
+        frac_nanos += d as u64 * scale;
+        self.bump();
+      }
+
+      if self.pos == start {
+        return Err(self.unexpected());
+      }
+    }
+
+    Ok(Decimal { int, frac_nanos, fractional })
+  }
+
+  fn unit(&mut self) -> Result<Unit, DurationSpecError> {
+    let unit = match self.peek() {
+      Some('h') => Unit::Hours,
+      Some('s') => Unit::Secs,
+      Some('m') if self.input[self.pos..].starts_with("ms") => {
+        self.bump();
+        Unit::Millis
+      }
+      Some('m') => Unit::Mins,
+      Some(c) => return Err(self.error(DurationSpecErrorKind::UnexpectedChar(c))),
+      None => return Err(self.error(DurationSpecErrorKind::MissingUnit)),
+    };
+
+    self.bump();
+    Ok(unit)
+  }
+
+  /// Parse the unit-suffixed notation.
+  fn components(&mut self) -> Result<DurationSpec, DurationSpecError> {
+    let mut nanos: u64 = 0;
+    let mut last_unit: Option<Unit> = None;
+    let mut last_fractional = None;
+
+    while self.peek().is_some() {
+      if let Some(pos) = last_fractional {
+        return Err(DurationSpecError::new(DurationSpecErrorKind::FractionalField, pos));
+      }
+
+      let number_pos = self.pos;
+      let number = self.decimal()?;
+      let unit_pos = self.pos;
+      let unit = self.unit()?;
+
+      if let Some(last_unit) = last_unit {
+        if unit >= last_unit {
+          return Err(DurationSpecError::new(DurationSpecErrorKind::UnitOrder, unit_pos));
+        }
+
+        if number.int >= unit.bound() {
+          return Err(DurationSpecError::new(DurationSpecErrorKind::OutOfRange, number_pos));
+        }
+      }
+
+      nanos = number
+        .scale(unit.nanos())
+        .and_then(|n| nanos.checked_add(n))
+        .ok_or_else(|| DurationSpecError::new(DurationSpecErrorKind::Overflow, number_pos))?;
+      last_unit = Some(unit);
+
+      if number.fractional {
+        last_fractional = Some(number_pos);
+      }
+    }
+
+    Ok(DurationSpec::Nanos(nanos))
+  }
+
+  /// Parse the clock notation.
+  fn clock(&mut self) -> Result<DurationSpec, DurationSpecError> {
+    let mut fields = vec![(self.pos, self.natural()?)];
+    let secs;
+
+    loop {
+      self.expect(':')?;
+
+      let pos = self.pos;
+      let number = self.decimal()?;
+
+      if number.int >= 60 {
+        return Err(DurationSpecError::new(DurationSpecErrorKind::OutOfRange, pos));
+      }
+
+      if self.peek() == Some(':') && fields.len() < 2 {
+        if number.fractional {
+          return Err(DurationSpecError::new(DurationSpecErrorKind::FractionalField, pos));
+        }
+
+        fields.push((pos, number.int));
       } else {
-        Err(DurationSpecError::MissingSecondsSuffix)
+        secs = number;
+        break;
       }
-    } else if s.contains('m') {
-      // the first argument represents minutes, so let’s take them out first
-      let mut iter = s.split('m');
-      let mins = iter.next().and_then(|x| x.parse().ok()).ok_or(DurationSpecError::CannotParseMinutes)?;
-      let secs = iter.next().and_then(|x| x.trim_end_matches('s').parse().ok()).ok_or(DurationSpecError::CannotParseSeconds)?;
+    }
 
-      Ok(DurationSpec { mins, secs })
-    } else {
-      // only seconds
-      let secs = s.trim_end_matches('s').parse().map_err(|_| DurationSpecError::CannotParseSeconds)?;
+    let mut nanos = secs.int * NANOS_PER_SEC + secs.frac_nanos;
 
-      Ok(DurationSpec { mins: 0, secs })
+    for (&(pos, field), unit) in fields.iter().rev().zip(&[Unit::Mins, Unit::Hours]) {
+      nanos = field
+        .checked_mul(unit.nanos())
+        .and_then(|n| nanos.checked_add(n))
+        .ok_or_else(|| DurationSpecError::new(DurationSpecErrorKind::Overflow, pos))?;
     }
+
+    Ok(DurationSpec::Nanos(nanos))
+  }
+
+  /// Parse the frame notation.
+  fn frame(&mut self) -> Result<DurationSpec, DurationSpecError> {
+    self.expect('@')?;
+    let frame = self.natural()?;
+    self.expect('f')?;
+
+    let fps = if self.peek() == Some('/') {
+      self.bump();
+
+      let pos = self.pos;
+      let fps = self.natural()?;
+
+      if fps == 0 || fps > u32::MAX as u64 {
+        return Err(DurationSpecError::new(DurationSpecErrorKind::OutOfRange, pos));
+      }
+
+      Some(fps as u32)
+    } else {
+      None
+    };
+
+    Ok(DurationSpec::Frame { frame, fps })
   }
 }
 
 /// Possible error than can occurr while parsing a `DurationSpec` from a string.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum DurationSpecError {
-  MissingSecondsSuffix,
-  CannotParseMinutes,
-  CannotParseSeconds
+pub struct DurationSpecError {
+  kind: DurationSpecErrorKind,
+  pos: usize
+}
+
+impl DurationSpecError {
+  fn new(kind: DurationSpecErrorKind, pos: usize) -> Self {
+    DurationSpecError { kind, pos }
+  }
+
+  /// Kind of error.
+  pub fn kind(&self) -> DurationSpecErrorKind {
+    self.kind
+  }
+
+  /// Byte offset in the input string at which the error occurred.
+  pub fn position(&self) -> usize {
+    self.pos
+  }
 }
 
 impl fmt::Display for DurationSpecError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(f, "{} at position {}", self.kind, self.pos)
+  }
+}
+
+/// Kind of error that can occur while parsing a `DurationSpec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationSpecErrorKind {
+  /// The input string is empty.
+  Empty,
+  /// A character was found where it was not expected.
+  UnexpectedChar(char),
+  /// The input ended where more was expected.
+  UnexpectedEnd,
+  /// A number is not followed by a unit.
+  MissingUnit,
+  /// A unit is not smaller than the unit preceding it.
+  UnitOrder,
+  /// A fractional part has more digits than nanoseconds can represent.
+  TooPrecise,
+  /// A number is out of its allowed range (e.g. `75` seconds after minutes).
+  OutOfRange,
+  /// A number with a fractional part is followed by smaller units or fields (e.g. `1.5m30s`).
+  FractionalField,
+  /// The duration is too long to be represented.
+  Overflow
+}
+
+impl fmt::Display for DurationSpecErrorKind {
   fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     match *self {
-      DurationSpecError::MissingSecondsSuffix => f.write_str("missing the seconds suffix"),
-      DurationSpecError::CannotParseMinutes => f.write_str("cannot parse minutes"),
-      DurationSpecError::CannotParseSeconds => f.write_str("cannot parse seconds"),
+      DurationSpecErrorKind::Empty => f.write_str("empty duration"),
+      DurationSpecErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+      DurationSpecErrorKind::UnexpectedEnd => f.write_str("unexpected end of input"),
+      DurationSpecErrorKind::MissingUnit => f.write_str("missing unit (h, m, s or ms)"),
+      DurationSpecErrorKind::UnitOrder => f.write_str("units must appear in decreasing order"),
+      DurationSpecErrorKind::TooPrecise => f.write_str("precision cannot exceed nanoseconds"),
+      DurationSpecErrorKind::OutOfRange => f.write_str("number out of range"),
+      DurationSpecErrorKind::FractionalField => f.write_str("only the last number can have a fractional part"),
+      DurationSpecErrorKind::Overflow => f.write_str("duration too long"),
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
+  use quickcheck::{Arbitrary, Gen, QuickCheck};
+
   use super::*;
 
+  fn t(secs: f64) -> Time {
+    Time::from_secs(secs)
+  }
+
+  #[test]
+  fn time_arithmetic() {
+    assert_eq!(t(1.5) + t(2.), t(3.5));
+    assert_eq!(t(1.5) - t(2.), t(-0.5));
+    assert_eq!(t(1.5) * 2., t(3.));
+    assert_eq!(t(3.) / 2., t(1.5));
+    assert_eq!(t(3.) / t(2.), 1.5);
+    assert_eq!(t(1.5).offset(t(1.)), t(2.5));
+
+    let mut x = t(1.);
+    x += t(2.);
+    x -= t(0.5);
+    assert_eq!(x, t(2.5));
+  }
+
+  #[test]
+  fn time_ordering() {
+    assert!(t(1.) < t(2.));
+    assert!(t(-1.) < t(0.));
+    assert!(t(2.) >= t(2.));
+  }
+
+  #[test]
+  fn time_units() {
+    assert_eq!(Time::from_millis(1500.), t(1.5));
+    assert_eq!(t(0.25).as_millis(), 250.);
+  }
+
+  #[test]
+  fn time_duration_conversion() {
+    assert_eq!(Time::from(Duration::from_millis(1500)), t(1.5));
+    assert_eq!(t(1.5).to_duration(), Some(Duration::from_millis(1500)));
+    assert_eq!(t(-1.).to_duration(), None);
+    assert_eq!(t(f64::INFINITY).to_duration(), None);
+  }
+
+  #[test]
+  fn time_serde() {
+    use serde_json::{from_str, to_string};
+
+    assert_eq!(to_string(&t(1.5)).unwrap(), "1.5");
+    assert_eq!(from_str::<Time>("1.5").unwrap(), t(1.5));
+    assert_eq!(to_string(&TimeRange::new(t(1.), t(2.))).unwrap(), r#"{"start":1.0,"end":2.0}"#);
+    assert_eq!(from_str::<TimeRange>(r#"{"start":5,"end":1}"#).unwrap(), TimeRange::new(t(1.), t(5.)));
+  }
+
+  #[test]
+  fn time_range() {
+    let range = TimeRange::new(t(2.), t(6.));
+
+    assert_eq!(TimeRange::new(t(6.), t(2.)), range);
+    assert_eq!(range.duration(), t(4.));
+    assert!(range.contains(t(2.)));
+    assert!(range.contains(t(5.9)));
+    assert!(!range.contains(t(6.)));
+    assert!(!range.contains(t(1.)));
+  }
+
+  #[test]
+  fn time_range_progress() {
+    let range = TimeRange::new(t(2.), t(6.));
+
+    assert_eq!(range.progress(t(0.)), 0.);
+    assert_eq!(range.progress(t(2.)), 0.);
+    assert_eq!(range.progress(t(3.)), 0.25);
+    assert_eq!(range.progress(t(6.)), 1.);
+    assert_eq!(range.progress(t(10.)), 1.);
+    assert_eq!(TimeRange::new(t(1.), t(1.)).progress(t(1.)), 1.);
+  }
+
+  #[test]
+  fn time_range_overlap() {
+    let a = TimeRange::new(t(0.), t(4.));
+    let b = TimeRange::new(t(3.), t(5.));
+    let c = TimeRange::new(t(4.), t(5.));
+
+    assert!(a.overlaps(&b));
+    assert!(b.overlaps(&a));
+    assert!(!a.overlaps(&c));
+    assert_eq!(a.intersection(&b), Some(TimeRange::new(t(3.), t(4.))));
+    assert_eq!(a.intersection(&c), None);
+  }
+
+  fn parse(s: &str) -> Result<DurationSpec, DurationSpecError> {
+    s.parse()
+  }
+
+  fn err(kind: DurationSpecErrorKind, pos: usize) -> Result<DurationSpec, DurationSpecError> {
+    Err(DurationSpecError::new(kind, pos))
+  }
+
+  fn secs(secs: u64) -> DurationSpec {
+    DurationSpec::new(secs, 0)
+  }
+
   #[test]
   fn parse_duration_spec() {
-    assert_eq!("1s".parse::<DurationSpec>().unwrap(), DurationSpec { mins: 0, secs: 1 });
-    assert_eq!("2m".parse::<DurationSpec>().unwrap(), DurationSpec { mins: 2, secs: 0 });
-    assert_eq!("3m12s".parse::<DurationSpec>().unwrap(), DurationSpec { mins: 3, secs: 12 });
-    assert_eq!("3m12".parse::<DurationSpec>(), Err(DurationSpecError::MissingSecondsSuffix));
+    assert_eq!(parse("1s"), Ok(secs(1)));
+    assert_eq!(parse("2m"), Ok(secs(120)));
+    assert_eq!(parse("3m12s"), Ok(secs(192)));
+    assert_eq!(parse("3m12"), err(DurationSpecErrorKind::MissingUnit, 4));
+  }
+
+  #[test]
+  fn parse_duration_spec_units() {
+    assert_eq!(parse("1h2m3.25s"), Ok(DurationSpec::new(3723, 250_000_000)));
+    assert_eq!(parse("500ms"), Ok(DurationSpec::new(0, 500_000_000)));
+    assert_eq!(parse("1s500ms"), Ok(DurationSpec::new(1, 500_000_000)));
+    assert_eq!(parse("1.5h"), Ok(secs(5400)));
+    assert_eq!(parse("1h30s"), Ok(secs(3630)));
+    assert_eq!(parse("90s"), Ok(secs(90)));
+    assert_eq!(parse("0.000000001s"), Ok(DurationSpec::Nanos(1)));
+  }
+
+  #[test]
+  fn parse_duration_spec_clock() {
+    assert_eq!(parse("1:02.250"), Ok(DurationSpec::new(62, 250_000_000)));
+    assert_eq!(parse("0:05"), Ok(secs(5)));
+    assert_eq!(parse("1:00:00"), Ok(secs(3600)));
+    assert_eq!(parse("125:00"), Ok(secs(7500)));
+    assert_eq!(parse("1:2:3.5"), Ok(DurationSpec::new(3723, 500_000_000)));
+  }
+
+  #[test]
+  fn parse_duration_spec_frame() {
+    assert_eq!(parse("@1440f"), Ok(DurationSpec::Frame { frame: 1440, fps: None }));
+    assert_eq!(parse("@1440f/24"), Ok(DurationSpec::frame(1440, 24)));
+  }
+
+  #[test]
+  fn parse_duration_spec_errors() {
+    assert_eq!(parse(""), err(DurationSpecErrorKind::Empty, 0));
+    assert_eq!(parse("1m75s"), err(DurationSpecErrorKind::OutOfRange, 2));
+    assert_eq!(parse("1s1m"), err(DurationSpecErrorKind::UnitOrder, 3));
+    assert_eq!(parse("1s1s"), err(DurationSpecErrorKind::UnitOrder, 3));
+    assert_eq!(parse("1s2000ms"), err(DurationSpecErrorKind::OutOfRange, 2));
+    assert_eq!(parse("12x"), err(DurationSpecErrorKind::UnexpectedChar('x'), 2));
+    assert_eq!(parse("s"), err(DurationSpecErrorKind::UnexpectedChar('s'), 0));
+    assert_eq!(parse("1.s"), err(DurationSpecErrorKind::UnexpectedChar('s'), 2));
+    assert_eq!(parse("0.0000000001s"), err(DurationSpecErrorKind::TooPrecise, 11));
+    assert_eq!(parse("1:75"), err(DurationSpecErrorKind::OutOfRange, 2));
+    assert_eq!(parse("1:"), err(DurationSpecErrorKind::UnexpectedEnd, 2));
+    assert_eq!(parse("1:00:00:00"), err(DurationSpecErrorKind::UnexpectedChar(':'), 7));
+    assert_eq!(parse("1:02.0:03"), err(DurationSpecErrorKind::FractionalField, 2));
+    assert_eq!(parse("1:02.5:03"), err(DurationSpecErrorKind::FractionalField, 2));
+    assert_eq!(parse("1.5m30s"), err(DurationSpecErrorKind::FractionalField, 0));
+    assert_eq!(parse("1h2.0m3s"), err(DurationSpecErrorKind::FractionalField, 2));
+    assert_eq!(parse("@12"), err(DurationSpecErrorKind::UnexpectedEnd, 3));
+    assert_eq!(parse("@12f/0"), err(DurationSpecErrorKind::OutOfRange, 5));
+    assert_eq!(parse("99999999999999999999s"), err(DurationSpecErrorKind::Overflow, 0));
+    assert_eq!(parse("18446744074s"), err(DurationSpecErrorKind::Overflow, 0));
+    assert_eq!(parse("5124096h"), err(DurationSpecErrorKind::Overflow, 0));
+    assert_eq!(parse("307445735:00"), err(DurationSpecErrorKind::Overflow, 0));
+  }
+
+  #[test]
+  fn duration_spec_saturates() {
+    assert_eq!(DurationSpec::new(u64::MAX, 0), DurationSpec::Nanos(u64::MAX));
+    assert_eq!(DurationSpec::new(18_446_744_073, 999_999_999), DurationSpec::Nanos(u64::MAX));
+    assert_eq!(DurationSpec::new(18_446_744_073, 0), DurationSpec::Nanos(18_446_744_073_000_000_000));
+  }
+
+  #[test]
+  fn display_duration_spec() {
+    assert_eq!(DurationSpec::default().to_string(), "0s");
+    assert_eq!(secs(192).to_string(), "3m12s");
+    assert_eq!(secs(3600).to_string(), "1h");
+    assert_eq!(DurationSpec::new(3723, 250_000_000).to_string(), "1h2m3.25s");
+    assert_eq!(DurationSpec::new(0, 500_000_000).to_string(), "0.5s");
+    assert_eq!(DurationSpec::Frame { frame: 1440, fps: None }.to_string(), "@1440f");
+    assert_eq!(DurationSpec::frame(1440, 24).to_string(), "@1440f/24");
+  }
+
+  #[test]
+  fn duration_spec_to_time() {
+    assert_eq!(Time::from(DurationSpec::new(62, 250_000_000)).as_secs(), 62.25);
+    assert_eq!(DurationSpec::frame(1440, 24).to_time(60).as_secs(), 60.);
+    assert_eq!(DurationSpec::Frame { frame: 1440, fps: None }.to_time(48).as_secs(), 30.);
+  }
+
+  impl Arbitrary for DurationSpec {
+    fn arbitrary<G>(g: &mut G) -> Self where G: Gen {
+      if bool::arbitrary(g) {
+        DurationSpec::Nanos(u64::arbitrary(g))
+      } else {
+        let fps = Option::<u32>::arbitrary(g).map(|fps| fps.max(1));
+        DurationSpec::Frame { frame: u64::arbitrary(g), fps }
+      }
+    }
+  }
+
+  #[test]
+  fn prop_display_round_trips() {
+    fn prop(spec: DurationSpec) -> bool {
+      parse(&spec.to_string()) == Ok(spec)
+    }
+
+    QuickCheck::new().tests(1000).quickcheck(prop as fn(DurationSpec) -> bool);
+  }
+
+  #[test]
+  fn prop_units_match_clock() {
+    fn prop(h: u16, m: u8, s: u8, ms: u16) -> bool {
+      let (m, s, ms) = (m % 60, s % 60, ms % 1000);
+      let units = format!("{}h{}m{}s{}ms", h, m, s, ms);
+      let clock = format!("{}:{:02}:{:02}.{:03}", h, m, s, ms);
+      let expected = DurationSpec::new(h as u64 * 3600 + m as u64 * 60 + s as u64, ms as u32 * 1_000_000);
+
+      parse(&units) == Ok(expected) && parse(&clock) == Ok(expected)
+    }
+
+    QuickCheck::new().tests(1000).quickcheck(prop as fn(u16, u8, u8, u16) -> bool);
+  }
+
+  #[test]
+  fn prop_parse_never_panics() {
+    fn prop(s: String) -> bool {
+      match parse(&s) {
+        Ok(_) => true,
+        Err(e) => e.position() <= s.len()
+      }
+    }
+
+    QuickCheck::new().tests(1000).quickcheck(prop as fn(String) -> bool);
   }
 }
@@ -0,0 +1,436 @@
+//! Clocks.
+//!
+//! A [`Clock`] is a source of [`Time`] ticked once per frame by runners. Several clocks are
+//! available, depending on what should drive a demo:
+//!
+//!   - [`WallClock`] follows the real time.
+//!   - [`FixedStep`] advances by a fixed amount of time per frame, regardless of the real time.
+//!   - [`ManualClock`] only moves when explicitly told to, which is handy in tests.
+//!   - [`ExternalClock`] follows a time set from elsewhere (audio position, network sync, etc.).
+//!
+//! Clocks can be composed with adapters, such as [`Offset`] and [`WrapAt`].
+//!
+//! Some clocks follow a playback that can be controlled – such as the
+//! [audio player](crate::audio::Player), whose playback position is the time. Such clocks are
+//! [playbacks](Clock::is_playback): runners seek, pause and change their speed instead of
+//! accounting for it on their own.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::time::{Monotonic, Time};
+
+/// A source of time.
+pub trait Clock {
+  /// Get the current time.
+  ///
+  /// Runners call this method exactly once per frame.
+  fn tick(&mut self) -> Time;
+
+  /// Offset the time of this clock by a given amount of time.
+  fn offset(self, t: Time) -> Offset<Self> where Self: Sized {
+    Offset::new(self, t)
+  }
+
+  /// Wrap the time of this clock around to 0 whenever it reaches a given duration.
+  fn wrap_at<W>(self, wrap_at: W) -> WrapAt<Self> where Self: Sized, W: Into<Option<Time>> {
+    WrapAt::new(self, wrap_at)
+  }
+
+  /// Whether the clock follows a playback that can be sought, paused and sped up.
+  ///
+  /// The other playback methods are ignored by clocks that are not playbacks.
+  fn is_playback(&self) -> bool {
+    false
+  }
+
+  /// Move the playback to a given time.
+  fn seek(&mut self, _: Time) {}
+
+  /// Pause or resume the playback.
+  fn set_paused(&mut self, _: bool) {}
+
+  /// Set the speed factor of the playback.
+  fn set_speed(&mut self, _: f64) {}
+
+  /// Whether the playback reached its end, its time not advancing anymore.
+  ///
+  /// Clocks that are not playbacks never finish.
+  fn is_finished(&self) -> bool {
+    false
+  }
+}
+
+impl<C> Clock for &mut C where C: ?Sized + Clock {
+  fn tick(&mut self) -> Time {
+    (**self).tick()
+  }
+
+  fn is_playback(&self) -> bool {
+    (**self).is_playback()
+  }
+
+  fn is_finished(&self) -> bool {
+    (**self).is_finished()
+  }
+
+  fn seek(&mut self, t: Time) {
+    (**self).seek(t)
+  }
+
+  fn set_paused(&mut self, paused: bool) {
+    (**self).set_paused(paused)
+  }
+
+  fn set_speed(&mut self, speed: f64) {
+    (**self).set_speed(speed)
+  }
+}
+
+impl<C> Clock for Box<C> where C: ?Sized + Clock {
+  fn tick(&mut self) -> Time {
+    (**self).tick()
+  }
+
+  fn is_playback(&self) -> bool {
+    (**self).is_playback()
+  }
+
+  fn is_finished(&self) -> bool {
+    (**self).is_finished()
+  }
+
+  fn seek(&mut self, t: Time) {
+    (**self).seek(t)
+  }
+
+  fn set_paused(&mut self, paused: bool) {
+    (**self).set_paused(paused)
+  }
+
+  fn set_speed(&mut self, speed: f64) {
+    (**self).set_speed(speed)
+  }
+}
+
+/// Clock following the real time.
+///
+/// The clock starts when it’s ticked for the first time, so that the time spent initializing a
+/// demo is not accounted for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WallClock {
+  start: Option<Monotonic>
+}
+
+impl WallClock {
+  pub fn new() -> Self {
+    WallClock::default()
+  }
+}
+
+impl Clock for WallClock {
+  fn tick(&mut self) -> Time {
+    match self.start {
+      Some(ref start) => start.elapsed_secs(),
+
+      None => {
+        self.start = Some(Monotonic::now());
+        Time::from_secs(0.)
+      }
+    }
+  }
+}
+
+/// Deterministic clock advancing by one frame every tick, at a given frame rate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FixedStep {
+  fps: u32,
+  frame: u64
+}
+
+impl FixedStep {
+  /// Create a clock running at `fps` frames per second, starting at frame 0.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `fps` is 0.
+  pub fn new(fps: u32) -> Self {
+    assert!(fps > 0, "a fixed-step clock needs a non-zero frame rate");
+    FixedStep { fps, frame: 0 }
+  }
+
+  /// Frame rate of the clock.
+  pub fn fps(&self) -> u32 {
+    self.fps
+  }
+
+  /// Index of the next frame the clock will yield.
+  pub fn frame(&self) -> u64 {
+    self.frame
+  }
+}
+
+impl Clock for FixedStep {
+  fn tick(&mut self) -> Time {
+    let t = Time::from_secs(self.frame as f64 / self.fps as f64);
+    self.frame += 1;
+    t
+  }
+}
+
+/// Clock that only moves when explicitly told to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ManualClock {
+  t: Time
+}
+
+impl ManualClock {
+  /// Create a clock stopped at a given time.
+  pub fn new(t: Time) -> Self {
+    ManualClock { t }
+  }
+
+  /// Set the time of the clock.
+  pub fn set(&mut self, t: Time) {
+    self.t = t;
+  }
+
+  /// Move the clock forward by a given amount of time.
+  pub fn step(&mut self, dt: Time) {
+    self.t += dt;
+  }
+}
+
+impl Default for ManualClock {
+  fn default() -> Self {
+    ManualClock::new(Time::from_secs(0.))
+  }
+}
+
+impl Clock for ManualClock {
+  fn tick(&mut self) -> Time {
+    self.t
+  }
+}
+
+/// Clock driven from the outside.
+///
+/// The time is set through [`ExternalClockHandle`]s, that can be sent to other threads – for
+/// instance, an audio thread or a network sync listener.
+#[derive(Clone, Debug, Default)]
+pub struct ExternalClock {
+  secs: Arc<AtomicU64>
+}
+
+impl ExternalClock {
+  pub fn new() -> Self {
+    ExternalClock::default()
+  }
+
+  /// Get a handle used to drive this clock.
+  pub fn handle(&self) -> ExternalClockHandle {
+    ExternalClockHandle { secs: self.secs.clone() }
+  }
+}
+
+impl Clock for ExternalClock {
+  fn tick(&mut self) -> Time {
+    Time::from_secs(f64::from_bits(self.secs.load(Ordering::Acquire)))
+  }
+}
+
+/// Handle used to set the time of an [`ExternalClock`].
+#[derive(Clone, Debug)]
+pub struct ExternalClockHandle {
+  secs: Arc<AtomicU64>
+}
+
+impl ExternalClockHandle {
+  /// Set the time of the clock.
+  pub fn set(&self, t: Time) {
+    self.secs.store(t.as_secs().to_bits(), Ordering::Release);
+  }
+}
+
+/// Clock adapter offsetting the time of a clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Offset<C> {
+  clock: C,
+  offset: Time
+}
+
+impl<C> Offset<C> {
+  pub fn new(clock: C, offset: Time) -> Self {
+    Offset { clock, offset }
+  }
+
+  /// Get a reference on the adapted clock.
+  pub fn get_ref(&self) -> &C {
+    &self.clock
+  }
+
+  /// Get a mutable reference on the adapted clock.
+  pub fn get_mut(&mut self) -> &mut C {
+    &mut self.clock
+  }
+
+  /// Get the adapted clock back.
+  pub fn into_inner(self) -> C {
+    self.clock
+  }
+}
+
+impl<C> Clock for Offset<C> where C: Clock {
+  fn tick(&mut self) -> Time {
+    self.clock.tick().offset(self.offset)
+  }
+
+  fn is_playback(&self) -> bool {
+    self.clock.is_playback()
+  }
+
+  fn is_finished(&self) -> bool {
+    self.clock.is_finished()
+  }
+
+  fn seek(&mut self, t: Time) {
+    self.clock.seek(t - self.offset)
+  }
+
+  fn set_paused(&mut self, paused: bool) {
+    self.clock.set_paused(paused)
+  }
+
+  fn set_speed(&mut self, speed: f64) {
+    self.clock.set_speed(speed)
+  }
+}
+
+/// Clock adapter wrapping the time of a clock around to 0 whenever it reaches a given duration.
+///
+/// If no duration is set, the time is left unchanged. The adapted clock is never sought: a
+/// playback clock – such as an [audio player](crate::audio::Player) – keeps on playing past the
+/// duration. Runners wrap the time with their
+/// [transport](crate::app::runner::transport::Transport::wrap_at) instead, which seeks the clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WrapAt<C> {
+  clock: C,
+  wrap_at: Option<Time>
+}
+
+impl<C> WrapAt<C> {
+  pub fn new<W>(clock: C, wrap_at: W) -> Self where W: Into<Option<Time>> {
+    let wrap_at = wrap_at.into().filter(|t| t.as_secs() > 0.);
+    WrapAt { clock, wrap_at }
+  }
+
+  /// Get a reference on the adapted clock.
+  pub fn get_ref(&self) -> &C {
+    &self.clock
+  }
+
+  /// Get a mutable reference on the adapted clock.
+  pub fn get_mut(&mut self) -> &mut C {
+    &mut self.clock
+  }
+
+  /// Get the adapted clock back.
+  pub fn into_inner(self) -> C {
+    self.clock
+  }
+}
+
+impl<C> Clock for WrapAt<C> where C: Clock {
+  fn tick(&mut self) -> Time {
+    let t = self.clock.tick();
+
+    match self.wrap_at {
+      Some(wrap_at) => t.wrap_around(wrap_at),
+      None => t
+    }
+  }
+
+  fn is_playback(&self) -> bool {
+    self.clock.is_playback()
+  }
+
+  fn is_finished(&self) -> bool {
+    self.clock.is_finished()
+  }
+
+  fn seek(&mut self, t: Time) {
+    self.clock.seek(t)
+  }
+
+  fn set_paused(&mut self, paused: bool) {
+    self.clock.set_paused(paused)
+  }
+
+  fn set_speed(&mut self, speed: f64) {
+    self.clock.set_speed(speed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn secs(t: f64) -> Time {
+    Time::from_secs(t)
+  }
+
+  #[test]
+  fn fixed_step() {
+    let mut clock = FixedStep::new(4);
+
+    assert_eq!(clock.tick(), secs(0.));
+    assert_eq!(clock.tick(), secs(0.25));
+    assert_eq!(clock.tick(), secs(0.5));
+    assert_eq!(clock.frame(), 3);
+  }
+
+  #[test]
+  fn manual() {
+    let mut clock = ManualClock::default();
+
+    assert_eq!(clock.tick(), secs(0.));
+    assert_eq!(clock.tick(), secs(0.));
+
+    clock.step(secs(1.5));
+    assert_eq!(clock.tick(), secs(1.5));
+
+    clock.set(secs(0.5));
+    assert_eq!(clock.tick(), secs(0.5));
+  }
+
+  #[test]
+  fn external() {
+    let mut clock = ExternalClock::new();
+    let handle = clock.handle();
+
+    assert_eq!(clock.tick(), secs(0.));
+
+    handle.set(secs(12.5));
+    assert_eq!(clock.tick(), secs(12.5));
+  }
+
+  #[test]
+  fn wall_clock_starts_on_first_tick() {
+    let mut clock = WallClock::new();
+    assert_eq!(clock.tick(), secs(0.));
+    assert!(clock.tick().as_secs() >= 0.);
+  }
+
+  #[test]
+  fn adapters() {
+    let mut clock = ManualClock::default().offset(secs(2.)).wrap_at(secs(5.));
+
+    assert_eq!(clock.tick(), secs(2.));
+
+    clock.get_mut().get_mut().step(secs(4.));
+    assert_eq!(clock.tick(), secs(1.));
+
+    let mut clock = ManualClock::new(secs(7.)).wrap_at(None);
+    assert_eq!(clock.tick(), secs(7.));
+  }
+}
@@ -0,0 +1,339 @@
+//! Timeline markers and cues.
+//!
+//! A [`MarkerTrack`] holds named [`Cue`]s – discrete events placed on the timeline, such as a
+//! flash on a kick or a camera switch – carrying an optional payload. A [`CueCursor`] follows the
+//! playback and yields the cues that fire between two frames, taking seeks and loops into
+//! account.
+//!
+//! A marker track serializes as a list of cues, sorted by time:
+//!
+//! ```json
+//! [
+//!   { "name": "intro", "time": 0.0, "payload": null },
+//!   { "name": "kick", "time": 12.5, "payload": null }
+//! ]
+//! ```
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use std::iter::FromIterator;
+
+use crate::time::Time;
+
+/// A named event on the timeline.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Default"))]
+pub struct Cue<T> {
+  /// Name of the cue.
+  pub name: String,
+  /// Time at which the cue fires.
+  pub time: Time,
+  /// Data attached to the cue.
+  #[serde(default)]
+  pub payload: T
+}
+
+impl<T> Cue<T> {
+  pub fn new<N>(name: N, time: Time, payload: T) -> Self where N: Into<String> {
+    Cue { name: name.into(), time, payload }
+  }
+}
+
+/// A track of cues, sorted by time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarkerTrack<T> {
+  cues: Vec<Cue<T>>
+}
+
+impl<T> MarkerTrack<T> {
+  /// Create an empty track.
+  pub fn new() -> Self {
+    MarkerTrack { cues: Vec::new() }
+  }
+
+  /// Insert a cue in the track.
+  ///
+  /// Cues sharing the same time are kept in insertion order.
+  pub fn insert(&mut self, cue: Cue<T>) {
+    let index = self.index_after(cue.time);
+    self.cues.insert(index, cue);
+  }
+
+  /// All the cues, sorted by time.
+  pub fn cues(&self) -> &[Cue<T>] {
+    &self.cues
+  }
+
+  /// Find the first cue with a given name.
+  pub fn cue(&self, name: &str) -> Option<&Cue<T>> {
+    self.cues.iter().find(|cue| cue.name == name)
+  }
+
+  /// Times of all the cues, sorted.
+  pub fn times<'a>(&'a self) -> impl Iterator<Item = Time> + 'a {
+    self.cues.iter().map(|cue| cue.time)
+  }
+
+  /// Cues firing in ]`from`; `to`], sorted by time.
+  pub fn between(&self, from: Time, to: Time) -> &[Cue<T>] {
+    self.range(self.index_after(from), self.index_after(to))
+  }
+
+  /// Index of the first cue strictly after `t`.
+  fn index_after(&self, t: Time) -> usize {
+    self.cues.partition_point(|cue| cue.time <= t)
+  }
+
+  /// Index of the first cue at `t` or after.
+  fn index_from(&self, t: Time) -> usize {
+    self.cues.partition_point(|cue| cue.time < t)
+  }
+
+  fn range(&self, start: usize, end: usize) -> &[Cue<T>] {
+    if start < end {
+      &self.cues[start..end]
+    } else {
+      &[]
+    }
+  }
+}
+
+impl<T> Default for MarkerTrack<T> {
+  fn default() -> Self {
+    MarkerTrack::new()
+  }
+}
+
+impl<T> FromIterator<Cue<T>> for MarkerTrack<T> {
+  fn from_iter<I>(iter: I) -> Self where I: IntoIterator<Item = Cue<T>> {
+    let mut cues: Vec<_> = iter.into_iter().collect();
+    cues.sort_by(|a, b| a.time.as_secs().total_cmp(&b.time.as_secs()));
+
+    MarkerTrack { cues }
+  }
+}
+
+impl<T> Serialize for MarkerTrack<T> where T: Serialize {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    self.cues.serialize(serializer)
+  }
+}
+
+impl<'de, T> Deserialize<'de> for MarkerTrack<T> where T: Deserialize<'de> + Default {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+    let cues = Vec::<Cue<T>>::deserialize(deserializer)?;
+
+    if let Some(cue) = cues.iter().find(|cue| cue.time.as_secs().is_nan()) {
+      return Err(de::Error::custom(format!("cue “{}” has no valid time", cue.name)));
+    }
+
+    Ok(cues.into_iter().collect())
+  }
+}
+
+/// Playback cursor over a [`MarkerTrack`].
+///
+/// The cursor remembers the time of the previous frame. Every frame, [`CueCursor::advance`] yields
+/// the cues lying between the previous time (excluded) and the current time (included), so that a
+/// cue never fires twice and is never missed during normal playback.
+///
+/// The cursor never guesses what happened when time goes backward – it considers it a seek, and
+/// nothing fires. Runners tell it about discontinuities instead:
+///
+///   - After a [`CueCursor::reset`] – done on seeks – the next frame only fires the cues lying
+///     exactly at its time.
+///   - After a [`CueCursor::loop_back`] – done when looping or wrapping around – the next frame
+///     fires the cues up to the end of the loop, then the cues from its start to the current time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CueCursor {
+  last: Option<Time>,
+  looped: Option<(Time, Time)>
+}
+
+impl CueCursor {
+  pub fn new() -> Self {
+    CueCursor::default()
+  }
+
+  /// Forget about the previous time.
+  pub fn reset(&mut self) {
+    self.last = None;
+    self.looped = None;
+  }
+
+  /// Tell the cursor that the time reached `end` – excluded – and went on from `start` since the
+  /// previous frame.
+  pub fn loop_back(&mut self, end: Time, start: Time) {
+    self.looped = Some((end, start));
+  }
+
+  /// Move the cursor to `t` and get the cues that fired since the previous time.
+  pub fn advance<'a, T>(
+    &mut self,
+    track: &'a MarkerTrack<T>,
+    t: Time
+  ) -> impl Iterator<Item = &'a Cue<T>> + 'a {
+    let (first, second) = match (self.last.replace(t), self.looped.take()) {
+      (None, _) => (track.range(track.index_from(t), track.index_after(t)), &[][..]),
+
+      (Some(last), Some((end, start))) => {
+        let before_loop = track.range(track.index_after(last), track.index_from(end));
+        let after_loop = track.range(track.index_from(start), track.index_after(t));
+
+        (before_loop, after_loop)
+      }
+
+      (Some(last), None) if t >= last => (track.between(last, t), &[][..]),
+
+      (Some(_), None) => (&[][..], &[][..]),
+    };
+
+    first.iter().chain(second)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn t(secs: f64) -> Time {
+    Time::from_secs(secs)
+  }
+
+  fn track() -> MarkerTrack<u32> {
+    vec![
+      Cue::new("kick", t(12.5), 1),
+      Cue::new("start", t(0.), 0),
+      Cue::new("bar16", t(30.), 2),
+      Cue::new("end", t(59.5), 3)
+    ].into_iter().collect()
+  }
+
+  fn names<'a, I>(cues: I) -> Vec<&'a str> where I: Iterator<Item = &'a Cue<u32>> {
+    cues.map(|cue| cue.name.as_str()).collect()
+  }
+
+  #[test]
+  fn track_is_sorted() {
+    let track = track();
+
+    assert_eq!(names(track.cues().iter()), vec!["start", "kick", "bar16", "end"]);
+    assert_eq!(track.cue("bar16").map(|cue| cue.time), Some(t(30.)));
+    assert_eq!(track.cue("nope"), None);
+
+    let mut track = track;
+    track.insert(Cue::new("snare", t(13.), 4));
+    assert_eq!(track.times().collect::<Vec<_>>(), vec![t(0.), t(12.5), t(13.), t(30.), t(59.5)]);
+  }
+
+  #[test]
+  fn between() {
+    let track = track();
+
+    assert_eq!(names(track.between(t(0.), t(12.5)).iter()), vec!["kick"]);
+    assert_eq!(names(track.between(t(12.5), t(40.)).iter()), vec!["bar16"]);
+    assert!(track.between(t(40.), t(20.)).is_empty());
+  }
+
+  #[test]
+  fn cursor_playback() {
+    let track = track();
+    let mut cursor = CueCursor::new();
+
+    assert_eq!(names(cursor.advance(&track, t(0.))), vec!["start"]);
+    assert!(names(cursor.advance(&track, t(12.4))).is_empty());
+    assert_eq!(names(cursor.advance(&track, t(12.5))), vec!["kick"]);
+    assert!(names(cursor.advance(&track, t(12.5))).is_empty());
+    assert!(names(cursor.advance(&track, t(12.6))).is_empty());
+    assert_eq!(names(cursor.advance(&track, t(31.))), vec!["bar16"]);
+  }
+
+  #[test]
+  fn cursor_seek() {
+    let track = track();
+    let mut cursor = CueCursor::new();
+
+    cursor.advance(&track, t(20.)).count();
+
+    // going backward without wrap-around is a seek
+    assert!(names(cursor.advance(&track, t(5.))).is_empty());
+    assert_eq!(names(cursor.advance(&track, t(13.))), vec!["kick"]);
+
+    // seeking forward after a reset doesn’t fire skipped cues
+    cursor.reset();
+    assert!(names(cursor.advance(&track, t(40.))).is_empty());
+
+    // seeking exactly on a cue fires it once
+    cursor.reset();
+    assert_eq!(names(cursor.advance(&track, t(30.))), vec!["bar16"]);
+    assert!(names(cursor.advance(&track, t(30.1))).is_empty());
+  }
+
+  #[test]
+  fn cursor_wrap_around() {
+    let track = track();
+    let mut cursor = CueCursor::new();
+
+    cursor.advance(&track, t(59.)).count();
+    cursor.loop_back(t(60.), t(0.));
+    assert_eq!(names(cursor.advance(&track, t(0.5))), vec!["end", "start"]);
+    assert_eq!(names(cursor.advance(&track, t(13.))), vec!["kick"]);
+  }
+
+  #[test]
+  fn cursor_loop() {
+    let track = track();
+    let mut cursor = CueCursor::new();
+
+    // looping over [10; 31[ fires the cues of the loop region on every pass, and only them
+    cursor.advance(&track, t(5.)).count();
+
+    for _ in 0..3 {
+      assert_eq!(names(cursor.advance(&track, t(20.))), vec!["kick"]);
+      assert_eq!(names(cursor.advance(&track, t(30.5))), vec!["bar16"]);
+      cursor.loop_back(t(31.), t(10.));
+      assert!(names(cursor.advance(&track, t(10.5))).is_empty());
+    }
+
+    // a loop starting on a cue fires it
+    cursor.advance(&track, t(30.5)).count();
+    cursor.loop_back(t(31.), t(12.5));
+    assert_eq!(names(cursor.advance(&track, t(12.6))), vec!["kick"]);
+  }
+
+  #[test]
+  fn serde() {
+    use serde_json::{from_str, to_string};
+
+    let track: MarkerTrack<()> = vec![Cue::new("b", t(2.), ()), Cue::new("a", t(1.), ())].into_iter().collect();
+    let json = r#"[{"name":"a","time":1.0,"payload":null},{"name":"b","time":2.0,"payload":null}]"#;
+
+    assert_eq!(to_string(&track).unwrap(), json);
+    assert_eq!(from_str::<MarkerTrack<()>>(r#"[{"name":"b","time":2},{"name":"a","time":1}]"#).unwrap(), track);
+  }
+
+  #[test]
+  fn nan_times_dont_panic() {
+    let track: MarkerTrack<()> = vec![
+      Cue::new("b", t(2.), ()),
+      Cue::new("nan", t(f64::NAN), ()),
+      Cue::new("a", t(1.), ())
+    ].into_iter().collect();
+
+    assert_eq!(track.cues().iter().map(|cue| cue.name.as_str()).collect::<Vec<_>>(), vec!["a", "b", "nan"]);
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn deserialize_rejects_nan_times() {
+    #[derive(Debug, Deserialize)]
+    struct Markers {
+      #[allow(dead_code)]
+      cues: MarkerTrack<()>
+    }
+
+    let err = toml::from_str::<Markers>("[[cues]]\nname = \"kick\"\ntime = nan\n").unwrap_err();
+    assert!(err.to_string().contains("cue “kick” has no valid time"), "{}", err);
+  }
+}
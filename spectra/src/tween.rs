@@ -0,0 +1,681 @@
+//! Easing and tweening.
+//!
+//! An [`Easing`] maps a linear progress in [0; 1] to an eased progress. A [`Tween`] uses an easing
+//! to interpolate between two values over a [`TimeRange`].
+//!
+//! Each easing also has a GLSL counterpart, available as a render [`Block`], so that transitions
+//! computed on the GPU look the same as the ones computed on the CPU.
+
+use glsl::parser::Parse;
+use glsl::syntax::TranslationUnit;
+use serde_derive::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::render::block::Block;
+use crate::render::color::Color;
+use crate::render::input::{self, Input, InputType};
+use crate::render::output::{Output, OutputType};
+use crate::render::type_channel::TypeChan;
+use crate::render::types::{Float, RF, RGBAF, RGBF, RGF};
+use crate::time::{Time, TimeRange};
+
+/// Overshoot of the back easing.
+const BACK_C1: f64 = 1.70158;
+/// Period factor of the elastic easing.
+const ELASTIC_C4: f64 = 2. * PI / 3.;
+/// Bounce easing factors.
+const BOUNCE_N1: f64 = 7.5625;
+const BOUNCE_D1: f64 = 2.75;
+
+/// Direction of an easing.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ease {
+  /// The easing shape applies at the start of the transition.
+  In,
+  /// The easing shape applies at the end of the transition.
+  Out,
+  /// The easing shape applies at both ends of the transition.
+  InOut
+}
+
+/// Easing functions.
+///
+/// *Out* variants mirror the *in* ones, and *in-out* variants are made of a half *in* variant
+/// followed by a half *out* variant.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+  Linear,
+  Quad(Ease),
+  Cubic(Ease),
+  Expo(Ease),
+  Elastic(Ease),
+  Back(Ease),
+  Bounce(Ease)
+}
+
+impl Easing {
+  /// Ease a progress.
+  ///
+  /// The progress is clamped to [0; 1] first. Some easings (back, elastic) overshoot, so the
+  /// result can lie outside of [0; 1].
+  pub fn ease(self, t: f64) -> f64 {
+    let t = t.clamp(0., 1.);
+
+    match self.ease_dir() {
+      None => t,
+      Some(Ease::In) => self.ease_in(t),
+      Some(Ease::Out) => 1. - self.ease_in(1. - t),
+      Some(Ease::InOut) if t < 0.5 => self.ease_in(2. * t) * 0.5,
+      Some(Ease::InOut) => 1. - self.ease_in(2. - 2. * t) * 0.5,
+    }
+  }
+
+  /// Name of the easing, as used in GLSL identifiers.
+  pub fn name(self) -> String {
+    let dir = match self.ease_dir() {
+      None => return "linear".to_owned(),
+      Some(Ease::In) => "in",
+      Some(Ease::Out) => "out",
+      Some(Ease::InOut) => "in_out",
+    };
+
+    let shape = match self {
+      Easing::Linear => "linear",
+      Easing::Quad(_) => "quad",
+      Easing::Cubic(_) => "cubic",
+      Easing::Expo(_) => "expo",
+      Easing::Elastic(_) => "elastic",
+      Easing::Back(_) => "back",
+      Easing::Bounce(_) => "bounce",
+    };
+
+    format!("{}_{}", shape, dir)
+  }
+
+  /// GLSL expression easing the progress held in the variable `t`.
+  ///
+  /// The progress is expected to be in [0; 1] already.
+  pub fn to_glsl_expr(self, t: &str) -> String {
+    match self.ease_dir() {
+      None => t.to_owned(),
+      Some(Ease::In) => self.glsl_in(t),
+      Some(Ease::Out) => format!("(1. - {})", self.glsl_in(&format!("(1. - {})", t))),
+      Some(Ease::InOut) => {
+        format!(
+          "({t} < 0.5 ? {first} * 0.5 : 1. - {second} * 0.5)",
+          t = t,
+          first = self.glsl_in(&format!("(2. * {})", t)),
+          second = self.glsl_in(&format!("(2. - 2. * {})", t))
+        )
+      }
+    }
+  }
+
+  /// Render block easing a progress.
+  ///
+  /// The block has a single `t` float input – the progress – and a single `t` float output – the
+  /// eased progress. Its ID is `ease_<name>`, where `<name>` is given by [`Easing::name`].
+  pub fn block(self) -> Block {
+    let code = format!(
+      "Out call(In x) {{ float t = clamp(x.t, 0., 1.); return Out({}); }}",
+      self.to_glsl_expr("t")
+    );
+
+    Block::new(
+      format!("ease_{}", self.name()),
+      vec![Input::new::<Float, _>("t")],
+      vec![Output::new::<Float, _>("t")],
+      parse_glsl(&code)
+    )
+  }
+
+  /// Render block tweening between two values of a given type.
+  ///
+  /// The block has three inputs: `from` and `to` of type `T`, and `t` – the progress. It outputs
+  /// the tweened `value`. Its ID is `tween_<name>_<type>`, where `<name>` is given by
+  /// [`Easing::name`] and `<type>` is the GLSL type of `T`.
+  pub fn tween_block<T>(self) -> Block where T: TweenType {
+    let code = format!(
+      "Out call(In x) {{ float t = clamp(x.t, 0., 1.); return Out(mix(x.from, x.to, {})); }}",
+      self.to_glsl_expr("t")
+    );
+
+    Block::new(
+      format!("tween_{}_{}", self.name(), glsl_float_type_name(T::INPUT)),
+      vec![Input::new::<T, _>("from"), Input::new::<T, _>("to"), Input::new::<Float, _>("t")],
+      vec![Output::new::<T, _>("value")],
+      parse_glsl(&code)
+    )
+  }
+
+  fn ease_dir(self) -> Option<Ease> {
+    match self {
+      Easing::Linear => None,
+      Easing::Quad(e) | Easing::Cubic(e) | Easing::Expo(e) | Easing::Elastic(e) | Easing::Back(e) |
+      Easing::Bounce(e) => Some(e),
+    }
+  }
+
+  /// The *in* variant of the easing, from which all the others are derived.
+  fn ease_in(self, t: f64) -> f64 {
+    match self {
+      Easing::Linear => t,
+      Easing::Quad(_) => t * t,
+      Easing::Cubic(_) => t * t * t,
+      Easing::Expo(_) if t == 0. => 0.,
+      Easing::Expo(_) => (10. * t - 10.).exp2(),
+      Easing::Elastic(_) if t == 0. || t == 1. => t,
+      Easing::Elastic(_) => -(10. * t - 10.).exp2() * ((10. * t - 10.75) * ELASTIC_C4).sin(),
+      Easing::Back(_) => (BACK_C1 + 1.) * t * t * t - BACK_C1 * t * t,
+      Easing::Bounce(_) => 1. - bounce_out(1. - t),
+    }
+  }
+
+  /// GLSL counterpart of `ease_in`.
+  fn glsl_in(self, t: &str) -> String {
+    match self {
+      Easing::Linear => t.to_owned(),
+      Easing::Quad(_) => format!("({t} * {t})", t = t),
+      Easing::Cubic(_) => format!("({t} * {t} * {t})", t = t),
+      Easing::Expo(_) => format!("({t} == 0. ? 0. : pow(2., 10. * {t} - 10.))", t = t),
+      Easing::Elastic(_) => {
+        format!(
+          "({t} == 0. || {t} == 1. ? {t} : -pow(2., 10. * {t} - 10.) * sin((10. * {t} - 10.75) * {c4:?}))",
+          t = t,
+          c4 = ELASTIC_C4
+        )
+      }
+      Easing::Back(_) => {
+        format!("({c3:?} * {t} * {t} * {t} - {c1:?} * {t} * {t})", t = t, c1 = BACK_C1, c3 = BACK_C1 + 1.)
+      }
+      Easing::Bounce(_) => format!("(1. - {})", glsl_bounce_out(&format!("(1. - {})", t))),
+    }
+  }
+}
+
+fn bounce_out(t: f64) -> f64 {
+  if t < 1. / BOUNCE_D1 {
+    BOUNCE_N1 * t * t
+  } else if t < 2. / BOUNCE_D1 {
+    let t = t - 1.5 / BOUNCE_D1;
+    BOUNCE_N1 * t * t + 0.75
+  } else if t < 2.5 / BOUNCE_D1 {
+    let t = t - 2.25 / BOUNCE_D1;
+    BOUNCE_N1 * t * t + 0.9375
+  } else {
+    let t = t - 2.625 / BOUNCE_D1;
+    BOUNCE_N1 * t * t + 0.984375
+  }
+}
+
+fn glsl_bounce_out(t: &str) -> String {
+  let piece = |offset: f64, bias: f64| {
+    format!("{n1:?} * ({t} - {o:?}) * ({t} - {o:?}) + {b:?}", t = t, n1 = BOUNCE_N1, o = offset, b = bias)
+  };
+
+  format!(
+    "({t} < {a:?} ? {n1:?} * {t} * {t} : {t} < {b:?} ? {p1} : {t} < {c:?} ? {p2} : {p3})",
+    t = t,
+    n1 = BOUNCE_N1,
+    a = 1. / BOUNCE_D1,
+    b = 2. / BOUNCE_D1,
+    c = 2.5 / BOUNCE_D1,
+    p1 = piece(1.5 / BOUNCE_D1, 0.75),
+    p2 = piece(2.25 / BOUNCE_D1, 0.9375),
+    p3 = piece(2.625 / BOUNCE_D1, 0.984375)
+  )
+}
+
+/// Parse generated GLSL code.
+fn parse_glsl(code: &str) -> TranslationUnit {
+  TranslationUnit::parse(code).expect("generated easing GLSL code should parse")
+}
+
+/// GLSL type name of a floating input type.
+fn glsl_float_type_name(ty: input::Type) -> &'static str {
+  match ty {
+    input::Type::Float(TypeChan::One) => "float",
+    input::Type::Float(TypeChan::Two) => "vec2",
+    input::Type::Float(TypeChan::Three) => "vec3",
+    input::Type::Float(TypeChan::Four) => "vec4",
+    _ => unreachable!("tweened types are floating types"),
+  }
+}
+
+/// Types that can be linearly interpolated.
+pub trait Interpolate: Sized {
+  /// Interpolate between `self` – at 0 – and `other` – at 1.
+  ///
+  /// `t` is not clamped, so that overshooting easings can extrapolate.
+  fn lerp(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f32 {
+  fn lerp(&self, other: &Self, t: f64) -> Self {
+    self + (other - self) * t as f32
+  }
+}
+
+impl Interpolate for f64 {
+  fn lerp(&self, other: &Self, t: f64) -> Self {
+    self + (other - self) * t
+  }
+}
+
+impl Interpolate for Time {
+  fn lerp(&self, other: &Self, t: f64) -> Self {
+    *self + (*other - *self) * t
+  }
+}
+
+impl Interpolate for Color {
+  fn lerp(&self, other: &Self, t: f64) -> Self {
+    Color::new(
+      self.r.lerp(&other.r, t),
+      self.g.lerp(&other.g, t),
+      self.b.lerp(&other.b, t),
+      self.a.lerp(&other.a, t)
+    )
+  }
+}
+
+macro_rules! impl_interpolate_array {
+  ($($n:expr),*) => {
+    $(
+      impl<T> Interpolate for [T; $n] where T: Copy + Default + Interpolate {
+        fn lerp(&self, other: &Self, t: f64) -> Self {
+          let mut r = [T::default(); $n];
+
+          for (r, (a, b)) in r.iter_mut().zip(self.iter().zip(other)) {
+            *r = a.lerp(b, t);
+          }
+
+          r
+        }
+      }
+    )*
+  }
+}
+
+impl_interpolate_array!(2, 3, 4);
+
+/// Render types that can be tweened.
+///
+/// Render types are markers; this trait associates them with the CPU value they represent, so that
+/// tweens can be computed on the CPU ([`Tween`]) or on the GPU ([`Easing::tween_block`]).
+pub trait TweenType: InputType + OutputType {
+  /// CPU representation of the type.
+  type Value: Interpolate;
+}
+
+impl TweenType for Float {
+  type Value = f32;
+}
+
+impl TweenType for RF {
+  type Value = f32;
+}
+
+impl TweenType for RGF {
+  type Value = [f32; 2];
+}
+
+impl TweenType for RGBF {
+  type Value = [f32; 3];
+}
+
+impl TweenType for RGBAF {
+  type Value = [f32; 4];
+}
+
+/// A transition between two values over a range of time.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tween<T> {
+  from: T,
+  to: T,
+  range: TimeRange,
+  easing: Easing
+}
+
+impl<T> Tween<T> where T: Interpolate {
+  /// Create a tween going from `from` to `to` over `range`.
+  pub fn new(from: T, to: T, range: TimeRange, easing: Easing) -> Self {
+    Tween { from, to, range, easing }
+  }
+
+  /// Range of time the tween spans.
+  pub fn range(&self) -> TimeRange {
+    self.range
+  }
+
+  /// Easing used by the tween.
+  pub fn easing(&self) -> Easing {
+    self.easing
+  }
+
+  /// Value of the tween at a given time.
+  ///
+  /// Before the range, the value is `from`; after the range, it’s `to`.
+  pub fn at(&self, t: Time) -> T {
+    self.from.lerp(&self.to, self.easing.ease(self.range.progress(t)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DIRS: [Ease; 3] = [Ease::In, Ease::Out, Ease::InOut];
+
+  fn all_easings() -> Vec<Easing> {
+    let mut easings = vec![Easing::Linear];
+
+    for &e in &DIRS {
+      easings.extend_from_slice(&[
+        Easing::Quad(e),
+        Easing::Cubic(e),
+        Easing::Expo(e),
+        Easing::Elastic(e),
+        Easing::Back(e),
+        Easing::Bounce(e)
+      ]);
+    }
+
+    easings
+  }
+
+  fn assert_close(a: f64, b: f64, eps: f64) {
+    assert!((a - b).abs() <= eps, "{} is not close to {}", a, b);
+  }
+
+  #[test]
+  fn easing_bounds() {
+    for easing in all_easings() {
+      assert_close(easing.ease(0.), 0., 1e-9);
+      assert_close(easing.ease(1.), 1., 1e-9);
+      assert_close(easing.ease(-1.), 0., 1e-9);
+      assert_close(easing.ease(2.), 1., 1e-9);
+    }
+  }
+
+  #[test]
+  fn easing_values() {
+    assert_close(Easing::Quad(Ease::In).ease(0.5), 0.25, 1e-9);
+    assert_close(Easing::Quad(Ease::Out).ease(0.5), 0.75, 1e-9);
+    assert_close(Easing::Cubic(Ease::InOut).ease(0.25), 0.0625, 1e-9);
+    assert_close(Easing::Expo(Ease::In).ease(0.5), 0.03125, 1e-9);
+    assert_close(Easing::Bounce(Ease::Out).ease(0.5), 0.765625, 1e-9);
+    assert!(Easing::Back(Ease::In).ease(0.2) < 0.);
+    assert!(Easing::Elastic(Ease::Out).ease(0.2) > 1.);
+
+    for easing in all_easings() {
+      if let Some(Ease::InOut) = easing.ease_dir() {
+        assert_close(easing.ease(0.5), 0.5, 1e-9);
+      }
+    }
+  }
+
+  #[test]
+  fn easing_names() {
+    assert_eq!(Easing::Linear.name(), "linear");
+    assert_eq!(Easing::Bounce(Ease::InOut).name(), "bounce_in_out");
+  }
+
+  #[test]
+  fn easing_serde() {
+    use serde_json::{from_str, to_string};
+
+    assert_eq!(to_string(&Easing::Linear).unwrap(), r#""linear""#);
+    assert_eq!(to_string(&Easing::Quad(Ease::InOut)).unwrap(), r#"{"quad":"in_out"}"#);
+    assert_eq!(from_str::<Easing>(r#"{"elastic":"out"}"#).unwrap(), Easing::Elastic(Ease::Out));
+  }
+
+  #[test]
+  fn glsl_matches_cpu() {
+    for easing in all_easings() {
+      let expr = easing.to_glsl_expr("t");
+
+      for i in 0..=200 {
+        let t = i as f64 / 200.;
+        let glsl = glsl_eval::eval(&expr, t as f32) as f64;
+
+        assert!(
+          (glsl - easing.ease(t)).abs() < 1e-4,
+          "{:?} at {}: GLSL gives {}, CPU gives {}", easing, t, glsl, easing.ease(t)
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn easing_table() {
+    // reference values of the usual easing formulas
+    let table = [
+      (Easing::Quad(Ease::In), [0.0625, 0.25, 0.5625]),
+      (Easing::Quad(Ease::Out), [0.4375, 0.75, 0.9375]),
+      (Easing::Quad(Ease::InOut), [0.125, 0.5, 0.875]),
+      (Easing::Cubic(Ease::InOut), [0.0625, 0.5, 0.9375]),
+      (Easing::Expo(Ease::Out), [0.8232233047, 0.96875, 0.9944757283]),
+      (Easing::Elastic(Ease::In), [-0.0055242717, -0.015625, 0.0883883476]),
+      (Easing::Back(Ease::Out), [0.8174096875, 1.0876975, 1.0641365625]),
+      (Easing::Bounce(Ease::In), [0.02734375, 0.234375, 0.52734375]),
+      (Easing::Bounce(Ease::Out), [0.47265625, 0.765625, 0.97265625])
+    ];
+
+    for &(easing, values) in &table {
+      for (&t, &value) in [0.25, 0.5, 0.75].iter().zip(&values) {
+        assert_close(easing.ease(t), value, 1e-9);
+      }
+    }
+  }
+
+  #[test]
+  fn glsl_exprs() {
+    assert_eq!(Easing::Linear.to_glsl_expr("t"), "t");
+    assert_eq!(Easing::Quad(Ease::In).to_glsl_expr("t"), "(t * t)");
+    assert_eq!(Easing::Quad(Ease::Out).to_glsl_expr("t"), "(1. - ((1. - t) * (1. - t)))");
+    assert_eq!(
+      Easing::Cubic(Ease::InOut).to_glsl_expr("t"),
+      "(t < 0.5 ? ((2. * t) * (2. * t) * (2. * t)) * 0.5 : 1. - ((2. - 2. * t) * (2. - 2. * t) * (2. - 2. * t)) * 0.5)"
+    );
+  }
+
+  #[test]
+  fn easing_blocks() {
+    for easing in all_easings() {
+      assert!(easing.block().to_glsl().is_some());
+      assert!(easing.tween_block::<RGBF>().to_glsl().is_some());
+    }
+  }
+
+  #[test]
+  fn interpolate() {
+    assert_eq!(1f32.lerp(&3., 0.5), 2.);
+    assert_eq!([0f32, 10.].lerp(&[10., 20.], 0.25), [2.5, 12.5]);
+    assert_eq!(Time::from_secs(1.).lerp(&Time::from_secs(2.), 0.5), Time::from_secs(1.5));
+    assert_eq!(Color::rgb(0., 0., 0.).lerp(&Color::rgb(1., 0.5, 0.), 0.5), Color::rgb(0.5, 0.25, 0.));
+  }
+
+  #[test]
+  fn tween() {
+    let range = TimeRange::new(Time::from_secs(2.), Time::from_secs(4.));
+    let tween = Tween::new(10., 20., range, Easing::Quad(Ease::In));
+
+    assert_eq!(tween.at(Time::from_secs(0.)), 10.);
+    assert_eq!(tween.at(Time::from_secs(3.)), 12.5);
+    assert_eq!(tween.at(Time::from_secs(5.)), 20.);
+  }
+
+  /// A tiny evaluator for the subset of GLSL expressions generated by easings, computing in single
+  /// precision as a GPU would.
+  mod glsl_eval {
+    pub fn eval(expr: &str, t: f32) -> f32 {
+      let tokens = tokenize(expr);
+      let mut parser = Parser { tokens: &tokens, pos: 0, t };
+      let v = parser.ternary();
+
+      assert_eq!(parser.pos, tokens.len(), "trailing tokens in {}", expr);
+      v
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Token {
+      Num(f32),
+      Ident(String),
+      Op(&'static str)
+    }
+
+    fn tokenize(s: &str) -> Vec<Token> {
+      const OPS: [&str; 13] = ["==", "||", "<=", "<", "+", "-", "*", "/", "(", ")", "?", ":", ","];
+
+      let mut tokens = Vec::new();
+      let mut rest = s.trim_start();
+
+      while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+
+        if c.is_ascii_digit() || c == '.' {
+          let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+          tokens.push(Token::Num(rest[..len].parse().unwrap()));
+          rest = &rest[len..];
+        } else if c.is_alphabetic() {
+          let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+          tokens.push(Token::Ident(rest[..len].to_owned()));
+          rest = &rest[len..];
+        } else {
+          let op = OPS.iter().find(|op| rest.starts_with(*op)).expect("unknown operator");
+          tokens.push(Token::Op(op));
+          rest = &rest[op.len()..];
+        }
+
+        rest = rest.trim_start();
+      }
+
+      tokens
+    }
+
+    struct Parser<'a> {
+      tokens: &'a [Token],
+      pos: usize,
+      t: f32
+    }
+
+    impl<'a> Parser<'a> {
+      fn eat(&mut self, op: &'static str) -> bool {
+        if self.tokens.get(self.pos) == Some(&Token::Op(op)) {
+          self.pos += 1;
+          true
+        } else {
+          false
+        }
+      }
+
+      fn ternary(&mut self) -> f32 {
+        let cond = self.or();
+
+        if self.eat("?") {
+          let a = self.ternary();
+          assert!(self.eat(":"));
+          let b = self.ternary();
+
+          if cond != 0. { a } else { b }
+        } else {
+          cond
+        }
+      }
+
+      fn or(&mut self) -> f32 {
+        let mut a = self.comparison();
+
+        while self.eat("||") {
+          let b = self.comparison();
+          a = if a != 0. || b != 0. { 1. } else { 0. };
+        }
+
+        a
+      }
+
+      fn comparison(&mut self) -> f32 {
+        let a = self.additive();
+
+        if self.eat("<") {
+          (a < self.additive()) as i32 as f32
+        } else if self.eat("==") {
+          (a == self.additive()) as i32 as f32
+        } else {
+          a
+        }
+      }
+
+      fn additive(&mut self) -> f32 {
+        let mut a = self.multiplicative();
+
+        loop {
+          if self.eat("+") {
+            a += self.multiplicative();
+          } else if self.eat("-") {
+            a -= self.multiplicative();
+          } else {
+            return a;
+          }
+        }
+      }
+
+      fn multiplicative(&mut self) -> f32 {
+        let mut a = self.unary();
+
+        loop {
+          if self.eat("*") {
+            a *= self.unary();
+          } else if self.eat("/") {
+            a /= self.unary();
+          } else {
+            return a;
+          }
+        }
+      }
+
+      fn unary(&mut self) -> f32 {
+        if self.eat("-") {
+          -self.unary()
+        } else {
+          self.primary()
+        }
+      }
+
+      fn primary(&mut self) -> f32 {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+
+        match token {
+          Token::Num(x) => x,
+          Token::Op("(") => {
+            let v = self.ternary();
+            assert!(self.eat(")"));
+            v
+          }
+          Token::Ident(ref name) if name == "t" => self.t,
+          Token::Ident(name) => {
+            assert!(self.eat("("));
+            let mut args = vec![self.ternary()];
+
+            while self.eat(",") {
+              args.push(self.ternary());
+            }
+
+            assert!(self.eat(")"));
+
+            match (name.as_str(), args.as_slice()) {
+              ("pow", &[x, y]) => x.powf(y),
+              ("sin", &[x]) => x.sin(),
+              _ => panic!("unknown function {}", name)
+            }
+          }
+          token => panic!("unexpected token {:?}", token)
+        }
+      }
+    }
+  }
+}